@@ -4,6 +4,11 @@
 layout(location = 0) in vec3 pos;
 layout(location = 0) out vec4 ColorBuffer;
 
+// xyz: sun direction, w: sky light multiplier
+layout(set = 0, binding = 2) uniform Sky {
+    vec4 u_sky;
+};
+
 
 float dist_sphere(vec3 v1, vec3 v2){
     float cos_angle = dot(v1, v2);
@@ -12,16 +17,16 @@ float dist_sphere(vec3 v1, vec3 v2){
 }
 
 
-vec3 getSky(vec3 pos, vec3 sun_pos)
+vec3 getSky(vec3 pos, vec3 sun_pos, float sky_light)
 {
     float y_lim = clamp(pos.y, 0.0, 1.0) - 5*clamp(pos.y, -0.2, 0.0);
     float atmosphere = pow(1.0-y_lim, 1.4);
-    vec3 skyColor = vec3(0.2,0.4,0.8);
+    vec3 skyColor = vec3(0.2,0.4,0.8) * sky_light;
 
     float scatter = pow(1.0 - dist_sphere(pos, sun_pos)/(3.1415926535), 1.0 / 30.0);
     scatter = 1.0 - clamp(scatter,0.8,1.0);
 
-    vec3 scatterColor = mix(vec3(1.0),vec3(1.0,0.3,0.0) * 1.5,scatter);
+    vec3 scatterColor = mix(vec3(1.0),vec3(1.0,0.3,0.0) * 1.5,scatter) * sky_light;
     return mix(skyColor,vec3(scatterColor), atmosphere / 1.3);
 
 }
@@ -56,8 +61,8 @@ vec3 getSun(vec3 pos, vec3 sun_pos){
 
 void main() {
     vec3 pos_norm = normalize(pos);
-    vec3 sun_pos = normalize(vec3(1.0, 0.75, 1.0));
-    vec3 sky = getSky(pos_norm, sun_pos);
+    vec3 sun_pos = normalize(u_sky.xyz);
+    vec3 sky = getSky(pos_norm, sun_pos, u_sky.w);
     vec3 sun = getSun(pos_norm, sun_pos);
 
     ColorBuffer = vec4(sky + sun,1.0);
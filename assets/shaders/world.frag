@@ -12,6 +12,10 @@ layout(location = 0) out vec4 o_color;
 
 layout(set = 0, binding = 1) uniform sampler u_sampler;
 layout(set = 0, binding = 2) uniform texture2D u_texture_atlas;
+// xyz: sun direction, w: sky light multiplier
+layout(set = 0, binding = 3) uniform Sky {
+    vec4 u_sky;
+};
 
 const vec3 SUN_DIRECTION = normalize(vec3(0, 1, 0.5));
 const float SUN_FRACTION = 0.1;
@@ -28,9 +32,13 @@ void main() {
     vec2 actual_uv = i_texture_top_left + mod(corrected_uv, i_texture_size);
     // get texture value
     vec4 tex_color = textureGrad(sampler2D(u_texture_atlas, u_sampler), actual_uv, x_derivative, y_derivative);
+    // transparent pixels, for example around the leaves of a plant
+    if (tex_color.a < 0.5) {
+        discard;
+    }
 
     /* VARIOUS BRIGHTNESS FACTORS */
-    float light_factor = pow(0.8, 15.0 - i_light_level);
+    float light_factor = pow(0.8, 15.0 - i_light_level * u_sky.w);
     float normal_factor = 1.0 - SUN_FRACTION + SUN_FRACTION * dot(i_norm, SUN_DIRECTION);
     float total_factor = light_factor * i_occl * normal_factor;
 
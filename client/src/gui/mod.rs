@@ -84,6 +84,11 @@ impl Gui {
         }
     }
 
+    /// Draw a rectangle
+    pub fn rect(&mut self, x: i32, y: i32, w: i32, h: i32, color: [f32; 4], z: f32) {
+        self.primitives.draw_rect(x, y, w, h, color, z);
+    }
+
     /// Draw text, aligned to the left but centered vertically
     pub fn text(&mut self, x: i32, y: i32, h: i32, text: String, color: [f32; 4], z: f32) {
         self.primitives.draw_text_simple(x, y, h, text, color, z);
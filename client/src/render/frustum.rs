@@ -46,6 +46,22 @@ impl Frustum {
         proj.as_matrix() * self.get_view_matrix()
     }
 
+    /// Project a point to normalized device coordinates, between -1 and 1 with y going up.
+    /// Return `None` if the point is not visible.
+    pub fn project(&self, point: Vector3<f64>, aspect_ratio: f64) -> Option<(f64, f64)> {
+        let projected =
+            self.get_view_projection(aspect_ratio) * Vector4::new(point.x, point.y, point.z, 1.0);
+        if projected.w <= 0.0 {
+            return None;
+        }
+        let ndc = projected.xyz() / projected.w;
+        if ndc.iter().all(|c| (-1.0..=1.0).contains(c)) {
+            Some((ndc.x, ndc.y))
+        } else {
+            None
+        }
+    }
+
     pub fn get_view_matrix(&self) -> Matrix4<f64> {
         let rotation = Matrix4::from_euler_angles(-self.pitch.to_radians(), 0.0, 0.0)
             * Matrix4::from_euler_angles(0.0, -self.yaw.to_radians(), 0.0);
@@ -84,6 +84,8 @@ pub fn greedy_meshing(
 
     const N_SIZE: usize = (CHUNK_SIZE + 2) as usize;
     let mut chunk_mask = [false; N_SIZE * N_SIZE * N_SIZE];
+    // The faces that every block completely covers, see `BlockMesh::covered_faces`
+    let mut covered_faces = [0u8; N_SIZE * N_SIZE * N_SIZE];
     let mut light_levels = [15; N_SIZE * N_SIZE * N_SIZE];
 
     #[inline(always)]
@@ -130,6 +132,11 @@ pub fn greedy_meshing(
     // TODO: for light, we don't need the 8 corners
 
     let mut opaque_blocks_count = 0;
+    // Height of the fluid in every block, 0 if there is no fluid
+    let mut fluid_heights: Vec<f32> = unsafe { zero_initialized_vec(N_SIZE * N_SIZE * N_SIZE) };
+    let mut fluid_blocks_count = 0;
+    // Number of blocks that are neither full cubes nor fluids
+    let mut shaped_blocks_count = 0;
 
     for i in 0..N_SIZE {
         for j in 0..N_SIZE {
@@ -139,19 +146,26 @@ pub fn greedy_meshing(
                     unsafe {
                         let u_ind = uind(i, j, k);
 
-                        let masked =
-                            (*meshes.get_unchecked(chunk_data.chunk.get_block_at_unsafe((
-                                i as u32 - 1,
-                                j as u32 - 1,
-                                k as u32 - 1,
-                            )) as usize))
-                            .is_opaque();
+                        let mesh = meshes.get_unchecked(chunk_data.chunk.get_block_at_unsafe((
+                            i as u32 - 1,
+                            j as u32 - 1,
+                            k as u32 - 1,
+                        )) as usize);
+                        let masked = mesh.is_opaque();
                         // 13 = 9 + 3 + 1 is the current chunk
                         *chunk_mask.get_unchecked_mut(u_ind) = masked;
+                        *covered_faces.get_unchecked_mut(u_ind) = mesh.covered_faces();
 
                         if masked {
                             opaque_blocks_count += 1;
                         }
+                        if let BlockMesh::Fluid { height, .. } = mesh {
+                            *fluid_heights.get_unchecked_mut(u_ind) = *height;
+                            fluid_blocks_count += 1;
+                        }
+                        if let BlockMesh::Cuboids { .. } | BlockMesh::Cross { .. } = mesh {
+                            shaped_blocks_count += 1;
+                        }
 
                         *light_levels.get_unchecked_mut(u_ind) = chunk_data
                             .light_chunk
@@ -160,10 +174,14 @@ pub fn greedy_meshing(
                 } else {
                     unsafe {
                         if let Some(c) = &chunk_data.all_chunks[ci] {
-                            *chunk_mask.get_unchecked_mut(uind(i, j, k)) = (*meshes.get_unchecked(
+                            let mesh = meshes.get_unchecked(
                                 c.get_block_at_unsafe(outside_position(i, j, k)) as usize,
-                            ))
-                            .is_opaque();
+                            );
+                            *chunk_mask.get_unchecked_mut(uind(i, j, k)) = mesh.is_opaque();
+                            *covered_faces.get_unchecked_mut(uind(i, j, k)) = mesh.covered_faces();
+                            if let BlockMesh::Fluid { height, .. } = mesh {
+                                *fluid_heights.get_unchecked_mut(uind(i, j, k)) = *height;
+                            }
                         }
                         if let Some(lc) = &chunk_data.all_light_chunks[ci] {
                             *light_levels.get_unchecked_mut(uind(i, j, k)) =
@@ -225,12 +243,14 @@ pub fn greedy_meshing(
                         if *chunk_mask.get_unchecked(ind(i + 1, j + 1, k + 1)) {
                             opaque_blocks_count_pass -= 1;
                             *to_mesh_faces.get_unchecked_mut(s) += 1;
-                            //checking if not void
-                            if !*chunk_mask.get_unchecked(ind(
+                            //checking if the face is not hidden by the adjacent block
+                            if *covered_faces.get_unchecked(ind(
                                 i + 1 + D[s][0],
                                 j + 1 + D[s][1],
                                 k + 1 + D[s][2],
-                            )) {
+                            )) & (1 << (s ^ 1))
+                                == 0
+                            {
                                 let mut coins = [0; 4];
                                 let mut edge = [0; 4];
 
@@ -513,8 +533,8 @@ pub fn greedy_meshing(
                             }
 
                             let uv = match meshes[current_quad.block_id as usize] {
-                                BlockMesh::Empty => continue,
                                 BlockMesh::FullCube { textures } => textures[s],
+                                _ => continue,
                             };
 
                             let texture_top_left = [uv.x, uv.y];
@@ -583,6 +603,209 @@ pub fn greedy_meshing(
         }
     }
 
+    // Fluids are not greedy meshed: their faces depend on the height of the adjacent fluids
+    if fluid_blocks_count > 0 {
+        for i in 0..(CHUNK_SIZE as i32) {
+            for j in 0..(CHUNK_SIZE as i32) {
+                for k in 0..(CHUNK_SIZE as i32) {
+                    let block_id = chunk_data
+                        .chunk
+                        .get_block_at((i as u32, j as u32, k as u32));
+                    let (uv, height) = match meshes[block_id as usize] {
+                        BlockMesh::Fluid { texture, height } => (texture, height),
+                        _ => continue,
+                    };
+                    for s in 0..6 {
+                        let neighbor = ind(i + 1 + D[s][0], j + 1 + D[s][1], k + 1 + D[s][2]);
+                        if covered_faces[neighbor] & (1 << (s ^ 1)) != 0 {
+                            continue;
+                        }
+                        // Only draw the part of the face that is not hidden by the adjacent fluid
+                        let neighbor_height = fluid_heights[neighbor];
+                        let visible = match s {
+                            2 => neighbor_height == 0.0,
+                            3 => neighbor_height == 0.0,
+                            _ => neighbor_height < height,
+                        };
+                        if !visible {
+                            continue;
+                        }
+
+                        let light_level =
+                            light_levels[neighbor].max(light_levels[ind(i + 1, j + 1, k + 1)]);
+                        let v = (s as u32) + (3 << 3) + ((light_level as u32) << 5);
+                        let mut base = [i, j, k];
+                        if s % 2 == 0 {
+                            for d in 0..3 {
+                                base[d] += D_DELTA0[s][d];
+                            }
+                        }
+                        let corners = [
+                            base,
+                            [
+                                base[0] + D_DELTA2[s][0],
+                                base[1] + D_DELTA2[s][1],
+                                base[2] + D_DELTA2[s][2],
+                            ],
+                            [
+                                base[0] + D_DELTA1[s][0],
+                                base[1] + D_DELTA1[s][1],
+                                base[2] + D_DELTA1[s][2],
+                            ],
+                            [
+                                base[0] + D_DELTA1[s][0] + D_DELTA2[s][0],
+                                base[1] + D_DELTA1[s][1] + D_DELTA2[s][1],
+                                base[2] + D_DELTA1[s][2] + D_DELTA2[s][2],
+                            ],
+                        ];
+                        for kk in 0..4 {
+                            let [cx, cy, cz] = corners[kk];
+                            // Lower the top of the fluid
+                            let y = if cy > j { j as f32 + height } else { cy as f32 };
+                            res_vertex.push(ChunkVertex {
+                                pos: [cx as f32 + offset_x, y + offset_y, cz as f32 + offset_z],
+                                texture_top_left: [uv.x, uv.y],
+                                texture_uv: [uvs[s][kk][0] * uv.width, uvs[s][kk][1] * uv.height],
+                                texture_max_uv: [uv.width, uv.height],
+                                texture_size: [uv.width, uv.height],
+                                occl_and_face: v,
+                            });
+                        }
+                        for kk in 0..6 {
+                            res_index.push(n_of_different_vertex + order2[s][kk]);
+                        }
+                        n_of_different_vertex += 4;
+                        tot_quad += 1;
+                        act_quad += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // The other blocks are not greedy meshed either. Every face of their cuboids is drawn,
+    // except the faces that lie on a side of the block that is hidden by the adjacent block.
+    if shaped_blocks_count > 0 {
+        for i in 0..(CHUNK_SIZE as i32) {
+            for j in 0..(CHUNK_SIZE as i32) {
+                for k in 0..(CHUNK_SIZE as i32) {
+                    let block_id = chunk_data
+                        .chunk
+                        .get_block_at((i as u32, j as u32, k as u32));
+                    let block_light = light_levels[ind(i + 1, j + 1, k + 1)];
+                    match &meshes[block_id as usize] {
+                        BlockMesh::Cuboids { cuboids } => {
+                            for cuboid in cuboids {
+                                for s in 0..6 {
+                                    let axis = s / 2;
+                                    let on_block_side = if s % 2 == 0 {
+                                        cuboid.max[axis] >= 1.0
+                                    } else {
+                                        cuboid.min[axis] <= 0.0
+                                    };
+                                    let mut light_level = block_light;
+                                    if on_block_side {
+                                        let neighbor =
+                                            ind(i + 1 + D[s][0], j + 1 + D[s][1], k + 1 + D[s][2]);
+                                        if covered_faces[neighbor] & (1 << (s ^ 1)) != 0 {
+                                            continue;
+                                        }
+                                        light_level = light_level.max(light_levels[neighbor]);
+                                    }
+
+                                    let uv = cuboid.textures[s];
+                                    let v = (s as u32) + (3 << 3) + ((light_level as u32) << 5);
+                                    // The two axes along the face
+                                    let axis1 = (0..3).find(|&d| D_DELTA1[s][d] == 1).unwrap();
+                                    let axis2 = (0..3).find(|&d| D_DELTA2[s][d] == 1).unwrap();
+                                    for kk in 0..4 {
+                                        // Same corner order as the fluid faces
+                                        let mut corner = cuboid.min;
+                                        if s % 2 == 0 {
+                                            corner[axis] = cuboid.max[axis];
+                                        }
+                                        if kk >= 2 {
+                                            corner[axis1] = cuboid.max[axis1];
+                                        }
+                                        if kk % 2 == 1 {
+                                            corner[axis2] = cuboid.max[axis2];
+                                        }
+                                        // Only show the part of the texture that matches the part of the face that is covered
+                                        let (f1, f2) = (corner[axis1], corner[axis2]);
+                                        let texture_uv = [
+                                            uvs[s][0][0]
+                                                + f1 * (uvs[s][2][0] - uvs[s][0][0])
+                                                + f2 * (uvs[s][1][0] - uvs[s][0][0]),
+                                            uvs[s][0][1]
+                                                + f1 * (uvs[s][2][1] - uvs[s][0][1])
+                                                + f2 * (uvs[s][1][1] - uvs[s][0][1]),
+                                        ];
+                                        res_vertex.push(ChunkVertex {
+                                            pos: [
+                                                i as f32 + corner[0] + offset_x,
+                                                j as f32 + corner[1] + offset_y,
+                                                k as f32 + corner[2] + offset_z,
+                                            ],
+                                            texture_top_left: [uv.x, uv.y],
+                                            texture_uv: [
+                                                texture_uv[0] * uv.width,
+                                                texture_uv[1] * uv.height,
+                                            ],
+                                            texture_max_uv: [uv.width, uv.height],
+                                            texture_size: [uv.width, uv.height],
+                                            occl_and_face: v,
+                                        });
+                                    }
+                                    for kk in 0..6 {
+                                        res_index.push(n_of_different_vertex + order2[s][kk]);
+                                    }
+                                    n_of_different_vertex += 4;
+                                    tot_quad += 1;
+                                    act_quad += 1;
+                                }
+                            }
+                        }
+                        BlockMesh::Cross { texture: uv } => {
+                            // Plants are lit like the top faces
+                            let v = 2 + (3 << 3) + ((block_light as u32) << 5);
+                            // Each quad joins two opposite vertical edges of the block
+                            for &(x0, z0, x1, z1) in &[(0.0, 0.0, 1.0, 1.0), (0.0, 1.0, 1.0, 0.0)] {
+                                let corners = [
+                                    ([x0, 0.0, z0], [0.0, uv.height]),
+                                    ([x1, 0.0, z1], [uv.width, uv.height]),
+                                    ([x0, 1.0, z0], [0.0, 0.0]),
+                                    ([x1, 1.0, z1], [uv.width, 0.0]),
+                                ];
+                                for (corner, texture_uv) in corners.iter() {
+                                    res_vertex.push(ChunkVertex {
+                                        pos: [
+                                            i as f32 + corner[0] + offset_x,
+                                            j as f32 + corner[1] + offset_y,
+                                            k as f32 + corner[2] + offset_z,
+                                        ],
+                                        texture_top_left: [uv.x, uv.y],
+                                        texture_uv: *texture_uv,
+                                        texture_max_uv: [uv.width, uv.height],
+                                        texture_size: [uv.width, uv.height],
+                                        occl_and_face: v,
+                                    });
+                                }
+                                // Both sides of the quad are visible
+                                for index in &[0, 1, 2, 2, 1, 3, 0, 2, 1, 2, 3, 1] {
+                                    res_index.push(n_of_different_vertex + index);
+                                }
+                                n_of_different_vertex += 4;
+                                tot_quad += 2;
+                                act_quad += 2;
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+
     let res_index: Vec<u32> = res_index.iter().map(|x| *x as u32).collect();
     (res_vertex, res_index, tot_quad, act_quad)
 }
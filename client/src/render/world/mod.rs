@@ -7,7 +7,9 @@ use super::{buffer_from_slice, to_u8_slice};
 use crate::texture::load_image;
 use crate::window::WindowBuffers;
 use image::{ImageBuffer, Rgba};
-use nalgebra::{Matrix4, Similarity3, Translation3, UnitQuaternion, Vector3};
+use nalgebra::Matrix4;
+use voxel_rs_common::block::mining::CRACK_STAGES;
+use voxel_rs_common::clock::WorldTime;
 use voxel_rs_common::data::vox::VoxelModel;
 use voxel_rs_common::debug::send_debug_info;
 use voxel_rs_common::registry::Registry;
@@ -27,6 +29,8 @@ pub struct WorldRenderer {
     uniform_view_proj: wgpu::Buffer,
     // Model matrix
     uniform_model: wgpu::Buffer,
+    // Sun direction and sky light multiplier
+    uniform_sky: wgpu::Buffer,
     // Chunk rendering
     chunk_index_buffers: MultiBuffer<ChunkPos, u32>,
     chunk_vertex_buffers: MultiBuffer<ChunkPos, ChunkVertex>,
@@ -71,6 +75,12 @@ impl WorldRenderer {
             size: 64,
             usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
         });
+        let uniform_sky = device.create_buffer(&wgpu::BufferDescriptor {
+            mapped_at_creation: false,
+            label: None,
+            size: 16,
+            usage: (wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST),
+        });
 
         // Create uniform bind group
         let chunk_bind_group_layout = device.create_bind_group_layout(&CHUNK_BIND_GROUP_LAYOUT);
@@ -79,6 +89,7 @@ impl WorldRenderer {
             &chunk_bind_group_layout,
             &texture_atlas_view,
             &uniform_view_proj,
+            &uniform_sky,
         );
 
         // Create chunk pipeline
@@ -115,6 +126,7 @@ impl WorldRenderer {
             &vpm_bind_group_layout,
             &uniform_view_proj,
             &uniform_model,
+            &uniform_sky,
         );
 
         // Create skybox pipeline
@@ -145,7 +157,7 @@ impl WorldRenderer {
         let target_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             mapped_at_creation: false,
             label: None,
-            size: 8 * std::mem::size_of::<SkyboxVertex>() as u64,
+            size: MAX_TARGET_VERTICES * std::mem::size_of::<SkyboxVertex>() as u64,
             usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
         });
         let target_pipeline = {
@@ -210,6 +222,7 @@ impl WorldRenderer {
         Self {
             uniform_view_proj,
             uniform_model,
+            uniform_sky,
             chunk_index_buffers: MultiBuffer::with_capacity(
                 device,
                 123040,
@@ -243,7 +256,9 @@ impl WorldRenderer {
         frustum: &Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
+        crack_stage: Option<usize>,
         models: &[model::Model],
+        world_time: WorldTime,
     ) {
         //============= RENDER =============//
         // TODO: what if win_h is 0 ?
@@ -275,6 +290,17 @@ impl WorldRenderer {
             buffer_from_slice(device, wgpu::BufferUsage::COPY_SRC, to_u8_slice(&view_proj));
         encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_view_proj, 0, 64);
 
+        // Update sky uniform
+        let sun_direction = world_time.sun_direction();
+        let sky: [f32; 4] = [
+            sun_direction.x as f32,
+            sun_direction.y as f32,
+            sun_direction.z as f32,
+            world_time.sky_light_multiplier(),
+        ];
+        let src_buffer = buffer_from_slice(device, wgpu::BufferUsage::COPY_SRC, to_u8_slice(&sky));
+        encoder.copy_buffer_to_buffer(&src_buffer, 0, &self.uniform_sky, 0, 16);
+
         // Draw all the chunks
         {
             let mut rpass = super::render::create_default_render_pass(encoder, buffers);
@@ -342,17 +368,18 @@ impl WorldRenderer {
         if let Some((target_pos, target_face)) = pointed_block {
             // Generate the vertices
             // TODO: maybe check if they changed since last frame
-            let src_buffer = buffer_from_slice(
-                device,
-                wgpu::BufferUsage::COPY_SRC,
-                to_u8_slice(&create_target_vertices(target_face)),
-            );
+            let mut vertices = create_target_vertices(target_face);
+            if let Some(crack_stage) = crack_stage {
+                vertices.extend(create_crack_vertices(target_face, crack_stage));
+            }
+            let src_buffer =
+                buffer_from_slice(device, wgpu::BufferUsage::COPY_SRC, to_u8_slice(&vertices));
             encoder.copy_buffer_to_buffer(
                 &src_buffer,
                 0,
                 &self.target_vertex_buffer,
                 0,
-                8 * std::mem::size_of::<SkyboxVertex>() as u64,
+                (vertices.len() * std::mem::size_of::<SkyboxVertex>()) as u64,
             );
             // Update model buffer
             let src_buffer = buffer_from_slice(
@@ -382,25 +409,13 @@ impl WorldRenderer {
             rpass.set_pipeline(&self.target_pipeline);
             rpass.set_bind_group(0, &self.vpm_bind_group, &[]);
             rpass.set_vertex_buffer(0, self.target_vertex_buffer.slice(..));
-            rpass.draw(0..8, 0..1);
+            rpass.draw(0..vertices.len() as u32, 0..1);
         }
 
         // Draw the models
         for model in models {
             // Compute model matrix
-            let mut transform = Similarity3::identity();
-            transform.append_scaling_mut(model.scale);
-            let offset_translation = Translation3::from(-Vector3::from(model.rot_offset));
-            transform.append_translation_mut(&offset_translation);
-            transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
-                &Vector3::y_axis(),
-                model.rot_y,
-            ));
-            transform.append_translation_mut(&Translation3::from(
-                Vector3::new(model.pos_x, model.pos_y, model.pos_z)
-                    + &Vector3::from(model.rot_offset),
-            ));
-            let transformation_matrix: Matrix4<f32> = nalgebra::convert(transform);
+            let transformation_matrix: Matrix4<f32> = nalgebra::convert(model.transform());
             // Update model buffer
             let src_buffer = buffer_from_slice(
                 device,
@@ -526,6 +541,16 @@ const CHUNK_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
                 },
                 count: None,
             },
+            wgpu::BindGroupLayoutEntry {
+                // sky
+                binding: 3,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     };
 
@@ -535,6 +560,7 @@ fn create_chunk_bind_group(
     layout: &wgpu::BindGroupLayout,
     texture_atlas_view: &wgpu::TextureView,
     uniform_view_proj: &wgpu::Buffer,
+    uniform_sky: &wgpu::Buffer,
 ) -> wgpu::BindGroup {
     // Create texture sampler
     let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
@@ -567,6 +593,10 @@ fn create_chunk_bind_group(
                 binding: 2,
                 resource: wgpu::BindingResource::TextureView(texture_atlas_view),
             },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Buffer(uniform_sky.slice(0..16)),
+            },
         ],
     })
 }
@@ -610,6 +640,16 @@ const SKYBOX_BIND_GROUP_LAYOUT: wgpu::BindGroupLayoutDescriptor<'static> =
                 },
                 count: None,
             },
+            wgpu::BindGroupLayoutEntry {
+                // sky
+                binding: 2,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::UniformBuffer {
+                    dynamic: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
         ],
     };
 
@@ -619,6 +659,7 @@ fn create_vpm_bind_group(
     layout: &wgpu::BindGroupLayout,
     uniform_view_proj: &wgpu::Buffer,
     uniform_model: &wgpu::Buffer,
+    uniform_sky: &wgpu::Buffer,
 ) -> wgpu::BindGroup {
     device.create_bind_group(&wgpu::BindGroupDescriptor {
         label: None,
@@ -632,6 +673,10 @@ fn create_vpm_bind_group(
                 binding: 1,
                 resource: wgpu::BindingResource::Buffer(uniform_model.slice(0..64)),
             },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::Buffer(uniform_sky.slice(0..16)),
+            },
         ],
     })
 }
@@ -689,6 +734,51 @@ fn create_target_vertices(face: usize) -> Vec<SkyboxVertex> {
     vertices
 }
 
+/// The cracks drawn on the target while it is being mined, as segments of the face from (u0, v0) to (u1, v1).
+/// They start from the center of the face, and two more segments are drawn at every crack stage.
+const CRACK_SEGMENTS: [[f32; 4]; 2 * (CRACK_STAGES - 1)] = [
+    [0.5, 0.5, 0.62, 0.4],
+    [0.5, 0.5, 0.38, 0.58],
+    [0.62, 0.4, 0.7, 0.22],
+    [0.38, 0.58, 0.3, 0.75],
+    [0.62, 0.4, 0.8, 0.45],
+    [0.38, 0.58, 0.2, 0.5],
+    [0.7, 0.22, 0.68, 0.05],
+    [0.3, 0.75, 0.35, 0.95],
+    [0.8, 0.45, 0.95, 0.38],
+    [0.2, 0.5, 0.05, 0.58],
+    [0.5, 0.5, 0.55, 0.7],
+    [0.5, 0.5, 0.45, 0.3],
+    [0.55, 0.7, 0.7, 0.8],
+    [0.45, 0.3, 0.3, 0.2],
+    [0.7, 0.22, 0.85, 0.15],
+    [0.3, 0.75, 0.15, 0.85],
+    [0.7, 0.8, 0.75, 0.95],
+    [0.3, 0.2, 0.25, 0.05],
+];
+/// Size of the target vertex buffer: the outline of the face and every crack segment
+const MAX_TARGET_VERTICES: u64 = 8 + 2 * CRACK_SEGMENTS.len() as u64;
+
+/// Create the lines of the cracks of a block face at some crack stage
+fn create_crack_vertices(face: usize, crack_stage: usize) -> Vec<SkyboxVertex> {
+    let axis = face / 2;
+    let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+    // Move the cracks in front of the face, like the outline
+    let depth = if face % 2 == 0 { 1.001 } else { -0.001 };
+    let vertex = |u: f32, v: f32| {
+        let mut position = [0.0; 3];
+        position[axis] = depth;
+        position[u_axis] = u;
+        position[v_axis] = v;
+        SkyboxVertex { position }
+    };
+    let segment_count = (2 * crack_stage).min(CRACK_SEGMENTS.len());
+    CRACK_SEGMENTS[..segment_count]
+        .iter()
+        .flat_map(|&[u0, v0, u1, v1]| vec![vertex(u0, v0), vertex(u1, v1)])
+        .collect()
+}
+
 /*========== MODEL RENDERING ==========*/
 #[derive(Debug, Clone, Copy)]
 pub struct RgbVertex {
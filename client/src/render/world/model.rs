@@ -1,5 +1,7 @@
 use super::RgbVertex;
+use nalgebra::{Similarity3, Translation3, UnitQuaternion, Vector3};
 use voxel_rs_common::data::vox::VoxelModel;
+use voxel_rs_common::physics::player::PhysicsPlayer;
 
 /// Data structure used to draw a pre-loaded model
 /// Contains the position, scale and its id in the model registry
@@ -17,6 +19,47 @@ pub struct Model {
     pub rot_offset: [f32; 3],
 }
 
+impl Model {
+    /// Place the model of a player with yaw `yaw`, in degrees. The model is as tall as the player,
+    /// its bottom center is at the feet of the player and it is rotated to face where the player looks.
+    /// The models face -z when they are not rotated, like the players with a yaw of 0.
+    pub fn for_player(mesh_id: u32, model: &VoxelModel, player: &PhysicsPlayer, yaw: f64) -> Self {
+        let height = player.aabb.maxs.y - player.aabb.mins.y;
+        let scale = (height / model.size_y as f64) as f32;
+        let rot_offset = [
+            model.size_x as f32 * scale / 2.0,
+            0.0,
+            model.size_z as f32 * scale / 2.0,
+        ];
+        let position = player.position();
+        Self {
+            mesh_id,
+            pos_x: position.x as f32 - rot_offset[0],
+            pos_y: position.y as f32,
+            pos_z: position.z as f32 - rot_offset[2],
+            scale,
+            rot_y: yaw.to_radians() as f32,
+            rot_offset,
+        }
+    }
+
+    /// Compute the transform from the coordinates of the mesh to world coordinates
+    pub fn transform(&self) -> Similarity3<f32> {
+        let mut transform = Similarity3::identity();
+        transform.append_scaling_mut(self.scale);
+        let offset_translation = Translation3::from(-Vector3::from(self.rot_offset));
+        transform.append_translation_mut(&offset_translation);
+        transform.append_rotation_mut(&UnitQuaternion::from_axis_angle(
+            &Vector3::y_axis(),
+            self.rot_y,
+        ));
+        transform.append_translation_mut(&Translation3::from(
+            Vector3::new(self.pos_x, self.pos_y, self.pos_z) + &Vector3::from(self.rot_offset),
+        ));
+        transform
+    }
+}
+
 const D: [[i32; 3]; 6] = [
     [1, 0, 0],
     [-1, 0, 0],
@@ -292,3 +335,53 @@ pub fn mesh_model(model: &VoxelModel) -> (Vec<RgbVertex>, Vec<u32>) {
     let res_index: Vec<u32> = res_index.iter().map(|x| *x as u32).collect();
     (res_vertex, res_index)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Point3, Vector3};
+
+    fn knight() -> VoxelModel {
+        VoxelModel {
+            size_x: 20,
+            size_y: 21,
+            size_z: 20,
+            voxels: Vec::new(),
+            full: Vec::new(),
+        }
+    }
+
+    fn assert_close(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).norm() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_player_model_stands_at_the_feet_of_the_player() {
+        let player = PhysicsPlayer::from_coords(Point3::new(10.0, 20.0, -5.0));
+        let model = Model::for_player(0, &knight(), &player, 37.0);
+        let transform = model.transform();
+        // Bottom center and top center of the mesh
+        let bottom = transform.transform_point(&Point3::new(10.0, 0.0, 10.0));
+        let top = transform.transform_point(&Point3::new(10.0, 21.0, 10.0));
+        assert_close(bottom, Point3::new(10.0, 20.0, -5.0));
+        let height = (player.aabb.maxs.y - player.aabb.mins.y) as f32;
+        assert_close(top, Point3::new(10.0, 20.0 + height, -5.0));
+    }
+
+    #[test]
+    fn test_player_model_faces_where_the_player_looks() {
+        let player = PhysicsPlayer::from_coords(Point3::new(0.0, 0.0, 0.0));
+        let center = Point3::new(10.0, 0.0, 10.0);
+        let front = Point3::new(10.0, 0.0, 0.0);
+        for &(yaw, direction) in &[
+            (0.0, Vector3::new(0.0, 0.0, -1.0)),
+            (90.0, Vector3::new(-1.0, 0.0, 0.0)),
+            (180.0, Vector3::new(0.0, 0.0, 1.0)),
+            (270.0, Vector3::new(1.0, 0.0, 0.0)),
+        ] {
+            let transform = Model::for_player(0, &knight(), &player, yaw).transform();
+            let forward = transform.transform_point(&front) - transform.transform_point(&center);
+            assert!((forward.normalize() - direction).norm() < 1e-4);
+        }
+    }
+}
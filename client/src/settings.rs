@@ -4,6 +4,10 @@ use std::{
     fs::OpenOptions,
     io::{Read, Write},
     path::Path,
+    time::Duration,
+};
+use voxel_rs_common::physics::interpolation::{
+    InterpolationSettings, DEFAULT_EXTRAPOLATION_LIMIT, DEFAULT_INTERPOLATION_DELAY,
 };
 
 const CONFIG_FILENAME: &str = "config.toml";
@@ -65,6 +69,14 @@ pub struct Settings {
     pub window_size: [u16; 2],
     pub invert_mouse: bool,
     pub render_distance: (u64, u64, u64, u64, u64, u64),
+    /// The name displayed to the other players
+    pub player_name: String,
+    /// The model used to draw the other players
+    pub player_model: String,
+    /// How long in the past the other players are displayed, in milliseconds
+    pub interpolation_delay: u64,
+    /// How long the movement of the other players is extrapolated for when the server is late, in milliseconds
+    pub extrapolation_limit: u64,
 }
 
 impl Default for Settings {
@@ -73,6 +85,20 @@ impl Default for Settings {
             window_size: [1600, 900],
             invert_mouse: false,
             render_distance: (16, 16, 16, 16, 16, 16),
+            player_name: "Player".to_owned(),
+            player_model: "knight".to_owned(),
+            interpolation_delay: DEFAULT_INTERPOLATION_DELAY.as_millis() as u64,
+            extrapolation_limit: DEFAULT_EXTRAPOLATION_LIMIT.as_millis() as u64,
+        }
+    }
+}
+
+impl Settings {
+    /// How the other players are interpolated between the server states
+    pub fn interpolation(&self) -> InterpolationSettings {
+        InterpolationSettings {
+            delay: Duration::from_millis(self.interpolation_delay),
+            extrapolation_limit: Duration::from_millis(self.extrapolation_limit),
         }
     }
 }
@@ -1,10 +1,16 @@
 use anyhow::Result;
 use log::info;
+use nalgebra::{Point3, Vector3};
 
 use voxel_rs_common::{
-    block::Block,
+    block::{
+        mining::{self, MiningTool},
+        Block,
+    },
+    clock::WorldTime,
+    interaction::{self, BlockInteraction, InteractionId, DEFAULT_REACH},
     network::{messages::ToClient, messages::ToServer, Client, ClientEvent},
-    player::RenderDistance,
+    player::{PlayerId, RenderDistance},
     registry::Registry,
     world::BlockPos,
 };
@@ -23,14 +29,50 @@ use crate::{
     window::{State, StateTransition, WindowData, WindowFlags},
     world::World,
 };
-use nalgebra::Vector3;
-use std::time::Instant;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 use voxel_rs_common::data::vox::VoxelModel;
 use voxel_rs_common::debug::{send_debug_info, send_perf_breakdown, DebugInfo};
-use voxel_rs_common::item::{Item, ItemMesh};
+use voxel_rs_common::entity::{EntityId, EntityModel};
+use voxel_rs_common::health::{Health, MAX_AIR, MAX_HEALTH};
+use voxel_rs_common::item::{
+    inventory::{Inventory, InventoryKind, ItemStack, SlotRef, HOTBAR_SIZE},
+    recipe::CRAFTING_GRID_SIZE,
+    Item, ItemMesh, ItemType,
+};
+use voxel_rs_common::physics::item_entity::ItemEntity;
 use voxel_rs_common::physics::simulation::{ClientPhysicsSimulation, PhysicsState, ServerState};
+use voxel_rs_common::physics::BlockContainer;
 use voxel_rs_common::time::BreakdownCounter;
-use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode};
+use winit::event::{
+    ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// How long command feedback stays on the screen
+const FEEDBACK_DURATION: Duration = Duration::from_secs(10);
+/// Maximum number of feedback lines on the screen
+const MAX_FEEDBACK_LINES: usize = 8;
+/// Size of the items lying in the world
+const ITEM_ENTITY_SIZE: f32 = 0.4;
+/// Rotation speed of the items lying in the world, in radians per second
+const ITEM_ENTITY_ROTATION_SPEED: f32 = 1.5;
+/// The items lying in the world bob up and down by this many blocks
+const ITEM_ENTITY_BOB_HEIGHT: f32 = 0.1;
+/// Bobbing speed of the items lying in the world, in radians per second
+const ITEM_ENTITY_BOB_SPEED: f32 = 2.5;
+/// Delay between breaking a block and starting to mine the next one
+const MINING_COOLDOWN: Duration = Duration::from_millis(250);
+/// Height of the name tags above the other players
+const NAME_TAG_HEIGHT: i32 = 20;
+/// Distance between the head of a player and its name tag, in blocks
+const NAME_TAG_OFFSET: f64 = 0.3;
+/// Number of slots per row of the inventories in the inventory screen
+const SLOTS_PER_ROW: usize = 6;
+/// Gui ids of the inventory screen
+const CONTAINER_SLOT_GUI_ID: u32 = 1000;
+const PLAYER_SLOT_GUI_ID: u32 = 2000;
+const CRAFTING_SLOT_GUI_ID: u32 = 3000;
+const CRAFT_BUTTON_GUI_ID: u32 = 3100;
 
 /// State of a singleplayer world
 pub struct SinglePlayer {
@@ -40,7 +82,6 @@ pub struct SinglePlayer {
     gui: Gui,
     ui_renderer: UiRenderer,
     world: World,
-    #[allow(dead_code)] // TODO: remove this
     block_registry: Registry<Block>,
     item_registry: Registry<Item>,
     item_meshes: Vec<ItemMesh>,
@@ -51,9 +92,51 @@ pub struct SinglePlayer {
     physics_simulation: ClientPhysicsSimulation,
     yaw_pitch: YawPitch,
     debug_info: DebugInfo,
-    start_time: Instant,
     client_timing: BreakdownCounter,
     looking_at: Option<(BlockPos, usize)>,
+    /// Maximum distance between the camera and the pointed block, as sent by the server
+    reach: f64,
+    /// The id of the next block interaction
+    next_interaction_id: InteractionId,
+    /// Whether the player holds the mining button
+    mining_pressed: bool,
+    /// The block the player is mining, when it started mining it, the id of the interaction
+    /// and the tool it started with
+    mining: Option<(BlockPos, Instant, InteractionId, Option<MiningTool>)>,
+    /// The player can't start mining a new block before this instant
+    mining_cooldown_end: Instant,
+    /// The position of the last block entity requested from the server, until it is received
+    requested_block_entity: Option<BlockPos>,
+    /// Last world time received from the server
+    world_time: WorldTime,
+    /// When `world_time` was received
+    world_time_received: Instant,
+    /// The command being typed, if the command prompt is open
+    command_input: Option<String>,
+    /// Recent command feedback
+    command_feedback: VecDeque<(Instant, String)>,
+    /// The inventory of the player, as last received from the server
+    player_inventory: Inventory,
+    /// The selected slot of the hotbar
+    hotbar_slot: usize,
+    /// The health of the player, as last received from the server
+    health: Health,
+    /// The crafting grid of the player, as last received from the server
+    crafting_grid: Inventory,
+    /// What the crafting grid crafts
+    crafting_result: Option<ItemStack>,
+    /// Whether the inventory screen with the crafting grid is open
+    inventory_open: bool,
+    /// The position and the contents of the open container, if there is one
+    open_container: Option<(BlockPos, Inventory)>,
+    /// The slot of the inventory screen that was clicked first, its stack is moved to the next clicked slot
+    selected_slot: Option<SlotRef>,
+    /// The models of the entities, their positions are part of the physics simulation
+    entity_models: HashMap<EntityId, EntityModel>,
+    /// The model used to draw the other players
+    player_model: String,
+    /// The names of the other players
+    player_names: HashMap<PlayerId, String>,
 }
 
 impl Drop for SinglePlayer {
@@ -108,6 +191,7 @@ impl SinglePlayer {
             z_min: z2,
         };
         client.send(ToServer::SetRenderDistance(render_distance));
+        client.send(ToServer::SetPlayerName(settings.player_name.clone()));
 
         // Create the UI renderers
         let pause_menu_renderer = IcedRenderer::new(
@@ -130,7 +214,7 @@ impl SinglePlayer {
                 pause_menu_renderer,
                 gui: Gui::new(),
                 ui_renderer: UiRenderer::new(device),
-                world: World::new(data.meshes.clone(), world_renderer),
+                world: World::new(data.blocks.clone(), data.meshes.clone(), world_renderer),
                 block_registry: data.blocks,
                 model_registry: data.models,
                 item_registry: data.items,
@@ -144,12 +228,33 @@ impl SinglePlayer {
                         input: Default::default(),
                     },
                     player_id,
+                    settings.interpolation(),
                 ),
                 yaw_pitch: Default::default(),
                 debug_info: DebugInfo::new_current(),
-                start_time: Instant::now(),
                 client_timing: BreakdownCounter::new(),
                 looking_at: None,
+                reach: DEFAULT_REACH,
+                next_interaction_id: 0,
+                mining_pressed: false,
+                mining: None,
+                mining_cooldown_end: Instant::now(),
+                requested_block_entity: None,
+                world_time: WorldTime::default(),
+                world_time_received: Instant::now(),
+                command_input: None,
+                command_feedback: VecDeque::new(),
+                player_inventory: Inventory::new(0),
+                hotbar_slot: 0,
+                health: Health::new(MAX_HEALTH),
+                crafting_grid: Inventory::new(0),
+                crafting_result: None,
+                inventory_open: false,
+                open_container: None,
+                selected_slot: None,
+                entity_models: HashMap::new(),
+                player_model: settings.player_model.clone(),
+                player_names: HashMap::new(),
             }),
             encoder.finish(),
         ))
@@ -166,6 +271,72 @@ impl SinglePlayer {
                     ToClient::UpdatePhysics(server_state) => {
                         self.physics_simulation.receive_server_update(server_state);
                     }
+                    ToClient::UpdateTime(world_time) => {
+                        self.world_time = world_time;
+                        self.world_time_received = Instant::now();
+                    }
+                    ToClient::BlockEntity(pos, entity) => {
+                        self.world.set_block_entity(pos, entity);
+                        if self.requested_block_entity == Some(pos) {
+                            self.requested_block_entity = None;
+                        }
+                    }
+                    ToClient::PlayerInventory(inventory) => {
+                        self.player_inventory = inventory;
+                    }
+                    ToClient::UpdateHealth(health) => {
+                        self.health = health;
+                    }
+                    ToClient::Died(damage_source) => {
+                        self.push_feedback(format!("You {}", damage_source));
+                    }
+                    ToClient::SelectHotbarSlot(slot) => {
+                        self.hotbar_slot = slot;
+                    }
+                    ToClient::SetReach(reach) => {
+                        self.reach = reach;
+                    }
+                    ToClient::InteractionAccepted(interaction_id) => {
+                        self.world.confirm_block_edit(interaction_id);
+                    }
+                    ToClient::InteractionRejected(interaction_id, error) => {
+                        log::debug!("Interaction {} was rejected: {}", interaction_id, error);
+                        self.world.reject_block_edit(interaction_id);
+                        // Stop drawing the cracks of a block the server doesn't consider mined
+                        if let Some((_, _, mining_id, _)) = self.mining {
+                            if mining_id == interaction_id {
+                                self.mining = None;
+                            }
+                        }
+                    }
+                    ToClient::CraftingGrid(crafting_grid, result) => {
+                        self.crafting_grid = crafting_grid;
+                        self.crafting_result = result;
+                    }
+                    ToClient::OpenContainer(pos, inventory) => {
+                        self.inventory_open = false;
+                        if self.open_container.as_ref().map(|(p, _)| *p) != Some(pos) {
+                            self.selected_slot = None;
+                        }
+                        self.open_container = Some((pos, inventory));
+                    }
+                    ToClient::CloseContainer => {
+                        self.open_container = None;
+                        self.selected_slot = None;
+                    }
+                    ToClient::CommandFeedback(feedback) => {
+                        info!("Command feedback: {}", feedback);
+                        self.push_feedback(feedback);
+                    }
+                    ToClient::SpawnEntity(id, model) => {
+                        self.entity_models.insert(id, model);
+                    }
+                    ToClient::DespawnEntity(id) => {
+                        self.entity_models.remove(&id);
+                    }
+                    ToClient::PlayerName(id, name) => {
+                        self.player_names.insert(id, name);
+                    }
                     ToClient::GameData(_) => {}
                     ToClient::CurrentId(_) => {}
                 },
@@ -174,6 +345,375 @@ impl SinglePlayer {
             }
         }
     }
+
+    /// Get the world time, advanced locally since the last server update
+    fn current_world_time(&self) -> WorldTime {
+        self.world_time.predict(self.world_time_received.elapsed())
+    }
+
+    /// Handle a typed character: open the command prompt or edit the current command
+    fn handle_character(&mut self, c: char) {
+        if self.is_paused {
+            return;
+        }
+        match &mut self.command_input {
+            None => {
+                if c == '/' {
+                    self.command_input = Some("/".to_owned());
+                }
+            }
+            Some(command) => match c {
+                // Enter
+                '\r' | '\n' => {
+                    let command = std::mem::replace(command, String::new());
+                    self.command_input = None;
+                    if command.trim().len() > 1 {
+                        self.client.send(ToServer::Command(command));
+                    }
+                }
+                // Backspace
+                '\u{8}' => {
+                    command.pop();
+                    if command.is_empty() {
+                        self.command_input = None;
+                    }
+                }
+                c if !c.is_control() => command.push(c),
+                _ => {}
+            },
+        }
+    }
+
+    /// Show a line of feedback above the command prompt
+    fn push_feedback(&mut self, feedback: String) {
+        self.command_feedback.push_back((Instant::now(), feedback));
+        if self.command_feedback.len() > MAX_FEEDBACK_LINES {
+            self.command_feedback.pop_front();
+        }
+    }
+
+    /// Select a slot of the hotbar
+    fn select_hotbar_slot(&mut self, slot: usize) {
+        self.hotbar_slot = slot;
+        self.client.send(ToServer::SelectHotbarSlot(slot));
+    }
+
+    /// Return the name and the number of items of a stack, or an empty string if there is no stack
+    fn stack_text(&self, stack: Option<ItemStack>) -> String {
+        match stack {
+            Some(stack) => {
+                let name = self
+                    .item_registry
+                    .get_value_by_id(stack.item)
+                    .map_or("?", |item| item.name.as_str());
+                format!("{} x{}", name, stack.count)
+            }
+            None => String::new(),
+        }
+    }
+
+    /// Return the model of some item lying in the world at `position`, rotating and bobbing as it gets older
+    fn item_model(&self, item: &ItemEntity, position: Point3<f64>) -> Option<crate::render::Model> {
+        // The mesh, its scale and its center after scaling
+        let (mesh_id, scale, center) = match self.item_meshes.get(item.stack.item as usize)? {
+            ItemMesh::SimpleMesh {
+                mesh_id,
+                scale,
+                mesh_center,
+            } => {
+                let scale = scale * ITEM_ENTITY_SIZE;
+                let center = [
+                    mesh_center.0 * scale,
+                    mesh_center.1 * scale,
+                    mesh_center.2 * scale,
+                ];
+                (*mesh_id, scale, center)
+            }
+            ItemMesh::Block { block } => {
+                let name = &self.block_registry.get_value_by_id(*block as u32)?.name;
+                let mesh_id = self
+                    .model_registry
+                    .get_id_by_name(&format!("block:{}", name))?;
+                let model = self.model_registry.get_value_by_id(mesh_id)?;
+                let scale = ITEM_ENTITY_SIZE / model.size_x as f32;
+                (mesh_id, scale, [ITEM_ENTITY_SIZE / 2.0; 3])
+            }
+        };
+        let age = item.age as f32;
+        let bob = ITEM_ENTITY_BOB_HEIGHT * (1.0 + (age * ITEM_ENTITY_BOB_SPEED).sin()) / 2.0;
+        // The bottom of the model touches the bottom of the item entity at the lowest point
+        let model_center = [
+            position.x as f32,
+            position.y as f32 + ITEM_ENTITY_SIZE / 2.0 + bob,
+            position.z as f32,
+        ];
+        Some(crate::render::Model {
+            mesh_id,
+            pos_x: model_center[0] - center[0],
+            pos_y: model_center[1] - center[1],
+            pos_z: model_center[2] - center[2],
+            scale,
+            rot_offset: center,
+            rot_y: age * ITEM_ENTITY_ROTATION_SPEED,
+        })
+    }
+
+    /// Draw the hotbar at the bottom of the screen
+    fn draw_hotbar(&mut self, data: &WindowData) {
+        const SLOT_WIDTH: i32 = 130;
+        const SLOT_HEIGHT: i32 = 20;
+        let width = HOTBAR_SIZE as i32 * (SLOT_WIDTH + 5);
+        let x = (data.logical_window_size.width as i32 - width) / 2;
+        let y = data.logical_window_size.height as i32 - SLOT_HEIGHT - 10;
+        for slot in 0..HOTBAR_SIZE {
+            let slot_x = x + slot as i32 * (SLOT_WIDTH + 5);
+            let color = if slot == self.hotbar_slot {
+                [0.8, 0.8, 0.8, 0.8]
+            } else {
+                [0.2, 0.2, 0.2, 0.6]
+            };
+            self.gui
+                .rect(slot_x, y, SLOT_WIDTH, SLOT_HEIGHT, color, 0.01);
+            let text = format!(
+                "{} {}",
+                slot + 1,
+                self.stack_text(self.player_inventory.get(slot))
+            );
+            self.gui.text(
+                slot_x + 4,
+                y,
+                SLOT_HEIGHT,
+                text,
+                [1.0, 1.0, 1.0, 1.0],
+                0.005,
+            );
+        }
+    }
+
+    /// Draw the health of the player above the hotbar, and its air when it is under a fluid
+    fn draw_health(&mut self, data: &WindowData) {
+        // Only the players that can take damage have a health bar
+        if !self
+            .physics_simulation
+            .get_player()
+            .game_mode
+            .takes_damage()
+        {
+            return;
+        }
+        const BAR_WIDTH: i32 = 300;
+        const BAR_HEIGHT: i32 = 16;
+        const HOTBAR_HEIGHT: i32 = 30;
+        let x = (data.logical_window_size.width as i32 - BAR_WIDTH) / 2;
+        let mut y = data.logical_window_size.height as i32 - HOTBAR_HEIGHT - BAR_HEIGHT - 5;
+        let mut bars = vec![(
+            format!("Health {}/{}", self.health.health, self.health.max_health),
+            self.health.health as f64 / self.health.max_health.max(1) as f64,
+            [0.8, 0.1, 0.1, 0.8],
+        )];
+        if self.health.air < MAX_AIR {
+            bars.push((
+                format!("Air {:.0}", self.health.air.ceil()),
+                self.health.air / MAX_AIR,
+                [0.2, 0.4, 0.9, 0.8],
+            ));
+        }
+        for (text, fraction, color) in bars {
+            self.gui
+                .rect(x, y, BAR_WIDTH, BAR_HEIGHT, [0.2, 0.2, 0.2, 0.6], 0.01);
+            let filled_width = (BAR_WIDTH as f64 * fraction) as i32;
+            self.gui.rect(x, y, filled_width, BAR_HEIGHT, color, 0.009);
+            self.gui
+                .text(x + 4, y, BAR_HEIGHT, text, [1.0, 1.0, 1.0, 1.0], 0.005);
+            y -= BAR_HEIGHT + 5;
+        }
+    }
+
+    /// Return true if the inventory screen or a container is open.
+    /// The cursor is released and the player doesn't move while a screen is open.
+    fn is_screen_open(&self) -> bool {
+        self.inventory_open || self.open_container.is_some()
+    }
+
+    /// Close the inventory screen or the open container
+    fn close_screen(&mut self) {
+        if self.open_container.is_some() {
+            self.client.send(ToServer::CloseContainer);
+        }
+        self.inventory_open = false;
+        self.open_container = None;
+        self.selected_slot = None;
+    }
+
+    /// Draw the crafting grid or the open container above the inventory of the player.
+    /// Clicking a slot selects it, and clicking another slot moves the selected stack there.
+    fn draw_inventory_screen(&mut self, data: &WindowData) {
+        const SLOT_WIDTH: i32 = 150;
+        const SLOT_HEIGHT: i32 = 20;
+        const SLOT_OFFSET: i32 = 25;
+        let top_section = match &self.open_container {
+            Some((_, inventory)) => (
+                "Chest",
+                InventoryKind::Container,
+                inventory.clone(),
+                CONTAINER_SLOT_GUI_ID,
+                SLOTS_PER_ROW,
+            ),
+            None if self.inventory_open => (
+                "Crafting",
+                InventoryKind::CraftingGrid,
+                self.crafting_grid.clone(),
+                CRAFTING_SLOT_GUI_ID,
+                CRAFTING_GRID_SIZE,
+            ),
+            None => return,
+        };
+        let sections = [
+            top_section,
+            (
+                "Inventory",
+                InventoryKind::Player,
+                self.player_inventory.clone(),
+                PLAYER_SLOT_GUI_ID,
+                SLOTS_PER_ROW,
+            ),
+        ];
+        let x =
+            (data.logical_window_size.width as i32 - SLOTS_PER_ROW as i32 * (SLOT_WIDTH + 5)) / 2;
+        let mut y = 100;
+        let mut clicked = None;
+        for (title, kind, inventory, gui_id, slots_per_row) in sections.iter() {
+            self.gui.text(
+                x,
+                y,
+                SLOT_HEIGHT,
+                title.to_string(),
+                [1.0, 1.0, 1.0, 1.0],
+                0.02,
+            );
+            y += SLOT_OFFSET;
+            for (slot, stack) in inventory.slots().iter().enumerate() {
+                let slot_ref = SlotRef {
+                    inventory: *kind,
+                    slot,
+                };
+                let slot_x = x + (slot % slots_per_row) as i32 * (SLOT_WIDTH + 5);
+                let slot_y = y + (slot / slots_per_row) as i32 * SLOT_OFFSET;
+                let mut text = self.stack_text(*stack);
+                if self.selected_slot == Some(slot_ref) {
+                    text = format!("> {}", text);
+                }
+                if self
+                    .gui
+                    .button(
+                        gui_id + slot as u32,
+                        slot_x,
+                        slot_y,
+                        SLOT_WIDTH,
+                        SLOT_HEIGHT,
+                    )
+                    .text(text, [1.0, 1.0, 1.0, 1.0])
+                    .build()
+                {
+                    clicked = Some((slot_ref, stack.is_some()));
+                }
+            }
+            let rows = (inventory.size() + slots_per_row - 1) / slots_per_row;
+            if *kind == InventoryKind::CraftingGrid {
+                // The result is next to the grid
+                let result_x = x + (*slots_per_row as i32 + 1) * (SLOT_WIDTH + 5);
+                let text = format!("Craft: {}", self.stack_text(self.crafting_result));
+                if self
+                    .gui
+                    .button(CRAFT_BUTTON_GUI_ID, result_x, y, SLOT_WIDTH, SLOT_HEIGHT)
+                    .text(text, [1.0, 1.0, 1.0, 1.0])
+                    .build()
+                    && self.crafting_result.is_some()
+                {
+                    self.client.send(ToServer::Craft);
+                }
+            }
+            y += rows as i32 * SLOT_OFFSET + 10;
+        }
+        match (self.selected_slot, clicked) {
+            (None, Some((slot, true))) => self.selected_slot = Some(slot),
+            (Some(selected), Some((slot, _))) => {
+                self.client.send(ToServer::MoveStack(selected, slot));
+                self.selected_slot = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Draw the names of the other players above their heads
+    fn draw_name_tags(&mut self, data: &WindowData, frustum: &Frustum) {
+        let aspect_ratio =
+            data.physical_window_size.width as f64 / data.physical_window_size.height.max(1) as f64;
+        let (width, height) = (
+            data.logical_window_size.width,
+            data.logical_window_size.height,
+        );
+        for (id, player, _) in self.physics_simulation.get_other_players() {
+            let name = match self.player_names.get(&id) {
+                Some(name) => name,
+                None => continue,
+            };
+            let mut tag_position = player.position().coords;
+            tag_position.y = player.aabb.maxs.y + NAME_TAG_OFFSET;
+            if let Some((x, y)) = frustum.project(tag_position, aspect_ratio) {
+                // Rough estimate of the width of the text
+                let tag_width = name.chars().count() as i32 * NAME_TAG_HEIGHT / 2;
+                let x = ((x + 1.0) / 2.0 * width) as i32 - tag_width / 2;
+                let y = ((1.0 - y) / 2.0 * height) as i32 - NAME_TAG_HEIGHT;
+                self.gui.rect(
+                    x - 4,
+                    y,
+                    tag_width + 8,
+                    NAME_TAG_HEIGHT,
+                    [0.0, 0.0, 0.0, 0.4],
+                    0.05,
+                );
+                self.gui.text(
+                    x,
+                    y,
+                    NAME_TAG_HEIGHT,
+                    name.clone(),
+                    [1.0, 1.0, 1.0, 1.0],
+                    0.045,
+                );
+            }
+        }
+    }
+
+    /// Draw the command prompt and the recent command feedback
+    fn draw_command_prompt(&mut self, data: &WindowData) {
+        const LINE_HEIGHT: i32 = 20;
+        let x = 10;
+        let mut y = data.logical_window_size.height as i32 - 2 * LINE_HEIGHT;
+        if let Some(command) = &self.command_input {
+            self.gui.text(
+                x,
+                y,
+                LINE_HEIGHT,
+                format!("{}_", command),
+                [1.0, 1.0, 1.0, 1.0],
+                0.02,
+            );
+        }
+        self.command_feedback
+            .retain(|(time, _)| time.elapsed() < FEEDBACK_DURATION);
+        for (_, feedback) in self.command_feedback.iter().rev() {
+            y -= LINE_HEIGHT;
+            self.gui.text(
+                x,
+                y,
+                LINE_HEIGHT,
+                feedback.clone(),
+                [1.0, 1.0, 0.6, 1.0],
+                0.02,
+            );
+        }
+    }
 }
 
 impl State for SinglePlayer {
@@ -196,7 +736,10 @@ impl State for SinglePlayer {
         self.client_timing.record_part("Network events");
 
         // Collect input
-        let frame_input = input_state.get_physics_input(self.yaw_pitch, !self.is_paused);
+        let frame_input = input_state.get_physics_input(
+            self.yaw_pitch,
+            !self.is_paused && self.command_input.is_none() && !self.is_screen_open(),
+        );
 
         // Send input to server
         self.client.send(ToServer::UpdateInput(frame_input));
@@ -213,10 +756,8 @@ impl State for SinglePlayer {
         // Apply raytracing to get the pointed at block.
         let pp = self.physics_simulation.get_player();
         self.looking_at = {
-            let y = self.yaw_pitch.yaw.to_radians();
-            let p = self.yaw_pitch.pitch.to_radians();
-            let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-            pp.get_pointed_at(dir, 10.0, &self.world)
+            let dir = interaction::look_direction(self.yaw_pitch.yaw, self.yaw_pitch.pitch);
+            pp.get_pointed_at(dir, self.reach, &self.world)
         };
         if let Some((x, face)) = self.looking_at {
             send_debug_info(
@@ -230,8 +771,26 @@ impl State for SinglePlayer {
         } else {
             send_debug_info("Player", "pointedat", "Pointed block: None");
         }
+        // Block entities are only sent on demand
+        if let Some((pos, _)) = self.looking_at {
+            match self.world.get_block_entity(pos) {
+                Some(entity) => send_debug_info(
+                    "Player",
+                    "blockentity",
+                    format!("Block entity: {:?}", entity),
+                ),
+                None => {
+                    if self.requested_block_entity != Some(pos) {
+                        self.client.send(ToServer::RequestBlockEntity(pos));
+                        self.requested_block_entity = Some(pos);
+                    }
+                }
+            }
+        }
         self.client_timing.record_part("Raytrace");
 
+        self.update_mining();
+
         // Debug current player position, yaw and pitch
         send_debug_info(
             "Player",
@@ -241,6 +800,18 @@ impl State for SinglePlayer {
                 p[0], p[1], p[2], player_chunk.px, player_chunk.py, player_chunk.pz
             ),
         );
+        let world_time = self.current_world_time();
+        send_debug_info(
+            "World",
+            "time",
+            format!(
+                "day = {}\ntime = {} / {}\nsky light = {:.2}",
+                world_time.day(),
+                world_time.tick_of_day(),
+                world_time.day_length,
+                world_time.sky_light_multiplier(),
+            ),
+        );
         send_debug_info(
             "Player",
             "yawpitch",
@@ -266,7 +837,7 @@ impl State for SinglePlayer {
             format!("Client loaded {} chunks", self.world.num_loaded_chunks()),
         );
 
-        flags.grab_cursor = !self.is_paused;
+        flags.grab_cursor = !self.is_paused && !self.is_screen_open();
 
         if self.pause_menu_renderer.state.program().should_exit {
             self.pause_menu_renderer.reset(PauseMenuControls::new());
@@ -304,31 +875,70 @@ impl State for SinglePlayer {
         crate::render::clear_color_and_depth(&mut encoder, buffers);
 
         let mut models_to_draw = Vec::new();
-        models_to_draw.push(crate::render::Model {
-            mesh_id: self
-                .model_registry
-                .get_id_by_name(&"knight".to_owned())
-                .unwrap(),
-            pos_x: 0.0,
-            pos_y: 55.0,
-            pos_z: 0.0,
-            scale: 0.3,
-            rot_offset: [0.0, 0.0, 0.0],
-            rot_y: 0.0,
-        });
-        let item_rotation = (Instant::now() - self.start_time).as_secs_f32(); // TODO: use f64
-        models_to_draw.push(crate::render::Model {
-            mesh_id: self
+        if let Some(mesh_id) = self.model_registry.get_id_by_name(&self.player_model) {
+            let model = self.model_registry.get_value_by_id(mesh_id).unwrap();
+            for (_, player, yaw) in self.physics_simulation.get_other_players() {
+                models_to_draw.push(crate::render::Model::for_player(
+                    mesh_id, model, player, yaw,
+                ));
+            }
+        }
+        let entities = self.physics_simulation.get_entities();
+        for (id, falling_block) in entities.falling_blocks.iter() {
+            let block = self
+                .block_registry
+                .get_value_by_id(falling_block.block as u32)
+                .unwrap();
+            let mesh_id = self
                 .model_registry
-                .get_id_by_name(&"item:ingot_iron".to_owned())
-                .unwrap(),
-            pos_x: 30.0,
-            pos_y: 55.0,
-            pos_z: 30.0,
-            scale: 1.0 / 32.0,
-            rot_offset: [0.5, 0.5, 1.0 / 64.0],
-            rot_y: item_rotation,
-        });
+                .get_id_by_name(&format!("block:{}", block.name));
+            if let (Some(mesh_id), Some(aabb)) = (mesh_id, entities.aabb(id)) {
+                let model = self.model_registry.get_value_by_id(mesh_id).unwrap();
+                // The model has the size of a full block, centered on the smaller falling block
+                let position = aabb.center() - Vector3::repeat(0.5);
+                models_to_draw.push(crate::render::Model {
+                    mesh_id,
+                    pos_x: position.x as f32,
+                    pos_y: position.y as f32,
+                    pos_z: position.z as f32,
+                    scale: 1.0 / model.size_x as f32,
+                    rot_offset: [0.0, 0.0, 0.0],
+                    rot_y: 0.0,
+                });
+            }
+        }
+        for (id, position) in entities.positions.iter() {
+            let model = match self.entity_models.get(&id) {
+                Some(model) => model,
+                None => continue,
+            };
+            if let Some(mesh_id) = self.model_registry.get_id_by_name(&model.model) {
+                // The position of the entity is the bottom center of the model,
+                // and the model rotates around its vertical axis
+                let mesh = self.model_registry.get_value_by_id(mesh_id).unwrap();
+                let rot_offset = [
+                    mesh.size_x as f32 * model.scale / 2.0,
+                    0.0,
+                    mesh.size_z as f32 * model.scale / 2.0,
+                ];
+                let yaw = entities.yaws.get(id).copied().unwrap_or(0.0);
+                models_to_draw.push(crate::render::Model {
+                    mesh_id,
+                    pos_x: position.x as f32 - rot_offset[0],
+                    pos_y: position.y as f32,
+                    pos_z: position.z as f32 - rot_offset[2],
+                    scale: model.scale,
+                    rot_offset,
+                    rot_y: yaw.to_radians() as f32,
+                });
+            }
+        }
+        for (id, item) in entities.items.iter() {
+            let position = entities.positions.get(id).unwrap();
+            if let Some(model) = self.item_model(item, *position) {
+                models_to_draw.push(model);
+            }
+        }
         // Draw chunks
         self.world.render_chunks(
             device,
@@ -338,7 +948,9 @@ impl State for SinglePlayer {
             &frustum,
             input_state.enable_culling,
             self.looking_at,
+            self.crack_stage(),
             &models_to_draw,
+            self.current_world_time(),
         );
         self.client_timing.record_part("Render chunks");
 
@@ -349,6 +961,13 @@ impl State for SinglePlayer {
         // crate::render::encode_resolve_render_pass(&mut encoder, buffers);
         self.gui.prepare();
         crate::gui::experiments::render_debug_info(&mut self.gui, &mut self.debug_info);
+        self.draw_name_tags(data, &frustum);
+        self.draw_command_prompt(data);
+        if !self.is_paused {
+            self.draw_hotbar(data);
+            self.draw_health(data);
+            self.draw_inventory_screen(data);
+        }
         self.gui.finish();
         self.ui_renderer.render(
             buffers,
@@ -356,7 +975,7 @@ impl State for SinglePlayer {
             &mut encoder,
             &data,
             &mut self.gui,
-            !self.is_paused,
+            !self.is_paused && !self.is_screen_open(),
         );
         if self.is_paused {
             self.pause_menu_renderer
@@ -376,11 +995,29 @@ impl State for SinglePlayer {
     }
 
     fn handle_window_event(&mut self, event: winit::event::WindowEvent, _: &InputState) {
+        match event {
+            WindowEvent::ReceivedCharacter(c) => self.handle_character(c),
+            WindowEvent::MouseWheel { delta, .. } => {
+                if !self.is_paused && !self.is_screen_open() {
+                    let scroll = match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y as f64,
+                        MouseScrollDelta::PixelDelta(position) => position.y,
+                    };
+                    // Scrolling down selects the next slot
+                    if scroll < 0.0 {
+                        self.select_hotbar_slot((self.hotbar_slot + 1) % HOTBAR_SIZE);
+                    } else if scroll > 0.0 {
+                        self.select_hotbar_slot((self.hotbar_slot + HOTBAR_SIZE - 1) % HOTBAR_SIZE);
+                    }
+                }
+            }
+            _ => {}
+        }
         self.pause_menu_renderer.handle_window_event(event)
     }
 
     fn handle_mouse_motion(&mut self, _settings: &Settings, delta: (f64, f64)) {
-        if !self.is_paused {
+        if !self.is_paused && !self.is_screen_open() {
             self.yaw_pitch.update_cursor(delta.0, delta.1);
         }
     }
@@ -396,7 +1033,7 @@ impl State for SinglePlayer {
         &mut self,
         changes: Vec<(winit::event::MouseButton, winit::event::ElementState)>,
     ) {
-        if self.is_paused {
+        if self.is_paused || self.is_screen_open() {
             for (button, state) in changes.iter() {
                 match *button {
                     MouseButton::Left => match *state {
@@ -405,6 +1042,7 @@ impl State for SinglePlayer {
                         }
                         ElementState::Released => {
                             self.gui.update_mouse_button(false);
+                            self.mining_pressed = false;
                         }
                     },
                     _ => {}
@@ -412,28 +1050,25 @@ impl State for SinglePlayer {
             }
         } else {
             for (button, state) in changes.iter() {
-                let pp = self.physics_simulation.get_player();
-                let y = self.yaw_pitch.yaw;
-                let p = self.yaw_pitch.pitch;
                 match *button {
-                    MouseButton::Left => match *state {
-                        ElementState::Pressed => {
-                            self.client
-                                .send(ToServer::BreakBlock(pp.position().coords, y, p));
-                        }
-                        _ => {}
-                    },
+                    MouseButton::Left => {
+                        // Mining happens in `update_mining` while the button is held
+                        self.mining_pressed = *state == ElementState::Pressed;
+                    }
                     MouseButton::Right => match *state {
                         ElementState::Pressed => {
-                            self.client
-                                .send(ToServer::PlaceBlock(pp.position().coords, y, p));
+                            if let Some(interaction) = self.block_interaction() {
+                                self.client.send(ToServer::PlaceBlock(interaction));
+                                self.predict_placement(&interaction);
+                            }
                         }
                         _ => {}
                     },
                     MouseButton::Middle => match *state {
                         ElementState::Pressed => {
-                            self.client
-                                .send(ToServer::SelectBlock(pp.position().coords, y, p));
+                            if let Some(interaction) = self.block_interaction() {
+                                self.client.send(ToServer::PickBlock(interaction));
+                            }
                         }
                         _ => {}
                     },
@@ -443,15 +1078,195 @@ impl State for SinglePlayer {
         }
     }
 
+    /// Create an interaction with the pointed block, if there is one
+    fn block_interaction(&mut self) -> Option<BlockInteraction> {
+        let target = self.looking_at?;
+        let id = self.next_interaction_id;
+        self.next_interaction_id = self.next_interaction_id.wrapping_add(1);
+        Some(BlockInteraction {
+            id,
+            yaw: self.yaw_pitch.yaw,
+            pitch: self.yaw_pitch.pitch,
+            target,
+        })
+    }
+
+    /// Display the block placed by an interaction before the server confirms it,
+    /// if the client knows enough to predict what the server will do
+    fn predict_placement(&mut self, interaction: &BlockInteraction) {
+        let (target, face) = interaction.target;
+        // Using a container opens it instead of placing a block
+        if !matches!(self.world.get_block_entity(target), Some(None)) {
+            return;
+        }
+        let player = self.physics_simulation.get_player().clone();
+        if !player.game_mode.interacts_with_world() {
+            return;
+        }
+        let block = match self
+            .player_inventory
+            .get(self.hotbar_slot)
+            .and_then(|stack| self.item_registry.get_value_by_id(stack.item))
+            .map(|item| &item.ty)
+        {
+            Some(ItemType::Block { block }) => *block,
+            _ => return,
+        };
+        let state = self
+            .block_registry
+            .get_value_by_id(block as u32)
+            .map(|b| b.placement_state(face, interaction.yaw))
+            .unwrap_or(block);
+        let pos = target.adjacent(face);
+        let replaced = self.world.get_block(pos);
+        if interaction::check_replaceable(replaced, self.world.get_fluid_state(pos)).is_err() {
+            return;
+        }
+        if let Some(block) = self.block_registry.get_value_by_id(state as u32) {
+            let players = std::iter::once(&player).chain(
+                self.physics_simulation
+                    .get_other_players()
+                    .map(|(_, player, _)| player),
+            );
+            let entities = self.physics_simulation.get_solid_entity_aabbs();
+            if interaction::check_placement(&block.collision_boxes(pos), players, entities).is_err()
+            {
+                return;
+            }
+        }
+        self.world.predict_block_edit(interaction.id, pos, state);
+    }
+
+    /// Return the tool in the selected slot of the hotbar, if there is one
+    fn selected_tool(&self) -> Option<MiningTool> {
+        self.player_inventory
+            .get(self.hotbar_slot)
+            .and_then(|stack| self.item_registry.get_value_by_id(stack.item))
+            .and_then(|item| item.ty.mining_tool())
+    }
+
+    /// Return how many seconds it takes to break the block at `pos` with `tool`,
+    /// or `None` if it can't be broken
+    fn mining_time(&self, pos: BlockPos, tool: Option<MiningTool>) -> Option<f64> {
+        self.block_registry
+            .get_value_by_id(self.world.get_block(pos) as u32)
+            .and_then(|block| block.block_type.mining())
+            .map(|mining| mining.time(tool))
+    }
+
+    /// Start mining the pointed block while the mining button is held, and tell the server when it's broken
+    fn update_mining(&mut self) {
+        let now = Instant::now();
+        let game_mode = self.physics_simulation.get_player().game_mode;
+        let can_mine = self.mining_pressed
+            && game_mode.interacts_with_world()
+            && !self.is_paused
+            && self.command_input.is_none()
+            && !self.is_screen_open();
+        let target = self.looking_at.map(|(pos, _)| pos).filter(|_| can_mine);
+        let tool = self.selected_tool();
+
+        // Stop mining if the player looks at another block, releases the button or switches tools.
+        // The server uses the tool the block started being mined with.
+        if let Some((block, _, _, mining_tool)) = self.mining {
+            if Some(block) != target || mining_tool != tool {
+                self.client.send(ToServer::AbortMining);
+                self.mining = None;
+            }
+        }
+        if let (None, Some(block)) = (self.mining, target) {
+            if now >= self.mining_cooldown_end {
+                if let Some(interaction) = self.block_interaction() {
+                    self.client.send(ToServer::StartMining(interaction));
+                    // The server breaks the block as soon as it starts being mined in creative mode,
+                    // and the blocks that take no time to mine in survival
+                    let breaks_instantly = self.mining_time(block, tool).map(|mining_time| {
+                        game_mode.breaks_blocks_instantly()
+                            || mining::is_mining_finished(0.0, mining_time)
+                    });
+                    if breaks_instantly == Some(true) {
+                        self.world.predict_block_edit(interaction.id, block, 0);
+                        self.mining_cooldown_end = now + MINING_COOLDOWN;
+                    } else {
+                        self.mining = Some((block, now, interaction.id, tool));
+                    }
+                }
+            }
+        }
+        if let Some((block, start, _, tool)) = self.mining {
+            let mining_time = match self.mining_time(block, tool) {
+                Some(mining_time) => mining_time,
+                None => return,
+            };
+            if (now - start).as_secs_f64() >= mining_time {
+                if let Some(interaction) = self.block_interaction() {
+                    self.client.send(ToServer::FinishMining(interaction));
+                    self.world.predict_block_edit(interaction.id, block, 0);
+                }
+                self.mining = None;
+                self.mining_cooldown_end = now + MINING_COOLDOWN;
+            }
+        }
+    }
+
+    /// Return the crack stage of the pointed block, if it is being mined
+    fn crack_stage(&self) -> Option<usize> {
+        let (block, start, _, tool) = self.mining?;
+        if self.looking_at.map(|(pos, _)| pos) != Some(block) {
+            return None;
+        }
+        let mining_time = self.mining_time(block, tool)?;
+        Some(mining::crack_stage(
+            start.elapsed().as_secs_f64(),
+            mining_time,
+        ))
+    }
+
     fn handle_key_state_changes(
         &mut self,
         changes: Vec<(VirtualKeyCode, winit::event::ElementState)>,
     ) {
+        const HOTBAR_KEYS: [VirtualKeyCode; HOTBAR_SIZE] = [
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::Key2,
+            VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4,
+            VirtualKeyCode::Key5,
+            VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7,
+            VirtualKeyCode::Key8,
+            VirtualKeyCode::Key9,
+        ];
         for (key, state) in changes.into_iter() {
+            // Number keys select a slot of the hotbar, unless a command is being typed
+            if let Some(slot) = HOTBAR_KEYS.iter().position(|&k| k == key) {
+                if state == ElementState::Pressed && !self.is_paused && self.command_input.is_none()
+                {
+                    self.select_hotbar_slot(slot);
+                }
+            }
+            // E opens and closes the inventory screen
+            if key == VirtualKeyCode::E
+                && state == ElementState::Pressed
+                && !self.is_paused
+                && self.command_input.is_none()
+            {
+                if self.is_screen_open() {
+                    self.close_screen();
+                } else {
+                    self.inventory_open = true;
+                }
+            }
             // Escape key
             if key == VirtualKeyCode::Escape {
                 if let winit::event::ElementState::Pressed = state {
-                    self.is_paused = !self.is_paused;
+                    if self.command_input.is_some() {
+                        self.command_input = None;
+                    } else if self.is_screen_open() {
+                        self.close_screen();
+                    } else {
+                        self.is_paused = !self.is_paused;
+                    }
                 }
             }
         }
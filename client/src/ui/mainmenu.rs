@@ -59,7 +59,7 @@ impl MainMenu {
         let (client, server) = dummy::new();
 
         std::thread::spawn(move || {
-            if let Err(e) = launch_server(Box::new(server)) {
+            if let Err(e) = launch_server(Box::new(server), true) {
                 // TODO: rewrite this error reporting
                 log::error!(
                     "Error happened in the server code: {}\nPrinting chain:\n{}",
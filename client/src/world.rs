@@ -3,9 +3,12 @@ use crate::render::WorldRenderer;
 use std::collections::HashMap;
 use std::sync::Arc;
 use voxel_rs_common::{
-    block::BlockMesh,
-    physics::BlockContainer,
+    block::{entity::BlockEntity, Block, BlockId, BlockMesh, FluidState},
+    clock::WorldTime,
+    interaction::InteractionId,
+    physics::{BlockContainer, AABB},
     player::{CloseChunks, RenderDistance},
+    registry::Registry,
     world::{BlockPos, Chunk, ChunkPos, LightChunk},
 };
 
@@ -13,7 +16,10 @@ use voxel_rs_common::{
 /// It is currently responsible for:
 /// * storing chunk data
 /// * meshing and rendering the chunks
+/// * predicting the block edits of the player until the server confirms them
 pub struct World {
+    /// The block registry
+    block_registry: Registry<Block>,
     /// The chunks
     chunks: HashMap<ChunkPos, ClientChunk>,
     /// The meshing worker
@@ -22,6 +28,10 @@ pub struct World {
     close_chunks: CloseChunks,
     /// The renderer
     renderer: WorldRenderer,
+    /// The block entities received from the server, `None` if there is no block entity at that position
+    block_entities: HashMap<BlockPos, Option<BlockEntity>>,
+    /// The block edits of the player that the server didn't confirm yet
+    pending_edits: PendingEdits,
 }
 
 impl Drop for World {
@@ -30,12 +40,19 @@ impl Drop for World {
 
 impl World {
     /// Create a new empty world using the provided chunks
-    pub fn new(block_meshes: Vec<BlockMesh>, renderer: WorldRenderer) -> Self {
+    pub fn new(
+        block_registry: Registry<Block>,
+        block_meshes: Vec<BlockMesh>,
+        renderer: WorldRenderer,
+    ) -> Self {
         Self {
+            block_registry,
             chunks: HashMap::new(),
             meshing_worker: start_meshing_worker(block_meshes),
             close_chunks: CloseChunks::new(&RenderDistance::default()),
             renderer,
+            block_entities: HashMap::new(),
+            pending_edits: PendingEdits::default(),
         }
     }
 
@@ -43,6 +60,8 @@ impl World {
     pub fn add_chunk(&mut self, chunk: Arc<Chunk>, light_chunk: Arc<LightChunk>) {
         // TODO: make sure this only happens once
         let chunk_pos = chunk.pos;
+        // Keep displaying the edits the server didn't process yet
+        let chunk = self.pending_edits.receive_chunk(chunk);
         self.chunks.insert(
             chunk_pos,
             ClientChunk {
@@ -52,7 +71,23 @@ impl World {
                 needs_remesh: true,
             },
         );
-        // Queue adjacent chunks for meshing
+        // The block entities of the chunk may have changed
+        self.block_entities
+            .retain(|pos, _| pos.containing_chunk_pos() != chunk_pos);
+        self.queue_adjacent_chunks_for_meshing(chunk_pos);
+    }
+
+    /// Replace the displayed blocks of a chunk that is already loaded, and remesh it
+    fn replace_chunk(&mut self, chunk: Arc<Chunk>) {
+        let chunk_pos = chunk.pos;
+        if let Some(client_chunk) = self.chunks.get_mut(&chunk_pos) {
+            client_chunk.chunk = chunk;
+            self.queue_adjacent_chunks_for_meshing(chunk_pos);
+        }
+    }
+
+    /// Mark a chunk and the chunks around it as needing to be remeshed
+    fn queue_adjacent_chunks_for_meshing(&mut self, chunk_pos: ChunkPos) {
         for i in -1..=1 {
             for j in -1..=1 {
                 for k in -1..=1 {
@@ -65,6 +100,43 @@ impl World {
         }
     }
 
+    /// Display a block edit of the player before the server confirms it.
+    /// Return false if the chunk of the block is not loaded, in which case nothing is predicted.
+    pub fn predict_block_edit(&mut self, id: InteractionId, pos: BlockPos, block: BlockId) -> bool {
+        let displayed = match self.chunks.get(&pos.containing_chunk_pos()) {
+            Some(client_chunk) => client_chunk.chunk.clone(),
+            None => return false,
+        };
+        let chunk = self.pending_edits.predict(id, pos, block, displayed);
+        self.block_entities.remove(&pos);
+        self.replace_chunk(chunk);
+        true
+    }
+
+    /// The server accepted a block edit, the next chunk it sends will contain it
+    pub fn confirm_block_edit(&mut self, id: InteractionId) {
+        self.pending_edits.confirm(id);
+    }
+
+    /// The server rejected a block edit: display the blocks of the server again
+    pub fn reject_block_edit(&mut self, id: InteractionId) {
+        if let Some(chunk) = self.pending_edits.reject(id) {
+            self.block_entities
+                .retain(|pos, _| pos.containing_chunk_pos() != chunk.pos);
+            self.replace_chunk(chunk);
+        }
+    }
+
+    /// Return block at position `pos` in the world. 0 is returned if the chunk does not exists/is not loaded
+    pub fn get_block(&self, pos: BlockPos) -> BlockId {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => 0,
+            Some(client_chunk) => client_chunk
+                .chunk
+                .get_block_at(pos.pos_in_containing_chunk()),
+        }
+    }
+
     /// Fetch the new chunk meshes from the meshing worker
     pub fn get_new_chunk_meshes(
         &mut self,
@@ -93,7 +165,22 @@ impl World {
                 renderer.remove_chunk_mesh(*chunk_pos);
                 false
             }
-        })
+        });
+        self.block_entities.retain(|pos, _| {
+            render_distance.is_chunk_visible(player_chunk, pos.containing_chunk_pos())
+        });
+        self.pending_edits
+            .retain_chunks(|chunk_pos| render_distance.is_chunk_visible(player_chunk, chunk_pos));
+    }
+
+    /// Receive a block entity from the server
+    pub fn set_block_entity(&mut self, pos: BlockPos, entity: Option<BlockEntity>) {
+        self.block_entities.insert(pos, entity);
+    }
+
+    /// Return the block entity at `pos` if it was received, or `None` if it must be requested from the server
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&Option<BlockEntity>> {
+        self.block_entities.get(&pos)
     }
 
     /// Start the meshing of a few chunks
@@ -163,7 +250,9 @@ impl World {
         frustum: &crate::render::Frustum,
         enable_culling: bool,
         pointed_block: Option<(BlockPos, usize)>,
+        crack_stage: Option<usize>,
         models: &[crate::render::world::Model],
+        world_time: WorldTime,
     ) {
         // TODO: remove some of the parameters and calculate them here instead
         self.get_new_chunk_meshes(device, encoder);
@@ -175,7 +264,9 @@ impl World {
             frustum,
             enable_culling,
             pointed_block,
+            crack_stage,
             models,
+            world_time,
         );
     }
 
@@ -187,11 +278,30 @@ impl World {
 
 impl BlockContainer for World {
     fn is_block_full(&self, pos: BlockPos) -> bool {
-        // TODO: use BlockRegistry
-        match self.chunks.get(&pos.containing_chunk_pos()) {
-            None => false,
-            Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
-        }
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.is_solid())
+            .unwrap_or(false)
+    }
+
+    fn collision_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.collision_boxes(pos))
+            .unwrap_or_default()
+    }
+
+    fn selection_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.selection_boxes(pos))
+            .unwrap_or_default()
+    }
+
+    fn get_fluid_state(&self, pos: BlockPos) -> Option<FluidState> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .and_then(|block| block.fluid)
     }
 }
 
@@ -206,3 +316,174 @@ struct ClientChunk {
     /// True if the chunk needs to be meshed, for example before it never was meshed or because it changed.
     pub needs_remesh: bool,
 }
+
+/// A block edit that the client displays before the server confirms it
+#[derive(Debug, Clone, Copy)]
+struct PendingEdit {
+    id: InteractionId,
+    pos: BlockPos,
+    block: BlockId,
+}
+
+/// The block edits of the player that the server didn't confirm yet.
+///
+/// For every chunk with pending edits, the last version received from the server is kept, so that the edits
+/// can be rolled back if the server rejects them. The server acknowledges every interaction before it sends
+/// the modified chunk, so a confirmed edit is applied to the server version until the new chunk arrives.
+#[derive(Debug, Default)]
+struct PendingEdits {
+    edits: Vec<PendingEdit>,
+    server_chunks: HashMap<ChunkPos, Arc<Chunk>>,
+}
+
+impl PendingEdits {
+    /// Add an edit to the `displayed` chunk, and return the new chunk to display
+    pub fn predict(
+        &mut self,
+        id: InteractionId,
+        pos: BlockPos,
+        block: BlockId,
+        displayed: Arc<Chunk>,
+    ) -> Arc<Chunk> {
+        let chunk_pos = pos.containing_chunk_pos();
+        self.server_chunks
+            .entry(chunk_pos)
+            .or_insert_with(|| displayed.clone());
+        self.edits.push(PendingEdit { id, pos, block });
+        let mut chunk = displayed;
+        Arc::make_mut(&mut chunk).set_block_at(pos.pos_in_containing_chunk(), block);
+        chunk
+    }
+
+    /// Receive a chunk from the server, and return it with the pending edits applied
+    pub fn receive_chunk(&mut self, chunk: Arc<Chunk>) -> Arc<Chunk> {
+        let chunk_pos = chunk.pos;
+        if !self.server_chunks.contains_key(&chunk_pos) {
+            return chunk;
+        }
+        self.server_chunks.insert(chunk_pos, chunk);
+        self.displayed_chunk(chunk_pos)
+    }
+
+    /// The server accepted an edit: it is now part of the server version of the chunk
+    pub fn confirm(&mut self, id: InteractionId) {
+        if let Some(edit) = self.take_edit(id) {
+            let chunk_pos = edit.pos.containing_chunk_pos();
+            if let Some(server_chunk) = self.server_chunks.get_mut(&chunk_pos) {
+                Arc::make_mut(server_chunk)
+                    .set_block_at(edit.pos.pos_in_containing_chunk(), edit.block);
+            }
+            self.forget_unused_chunk(chunk_pos);
+        }
+    }
+
+    /// The server rejected an edit: return the chunk to display without it, if there was such an edit
+    pub fn reject(&mut self, id: InteractionId) -> Option<Arc<Chunk>> {
+        let edit = self.take_edit(id)?;
+        let chunk_pos = edit.pos.containing_chunk_pos();
+        let chunk = self.displayed_chunk(chunk_pos);
+        self.forget_unused_chunk(chunk_pos);
+        Some(chunk)
+    }
+
+    /// Only keep the edits of the chunks for which `f` returns true
+    pub fn retain_chunks(&mut self, mut f: impl FnMut(ChunkPos) -> bool) {
+        self.edits.retain(|edit| f(edit.pos.containing_chunk_pos()));
+        self.server_chunks.retain(|chunk_pos, _| f(*chunk_pos));
+    }
+
+    fn take_edit(&mut self, id: InteractionId) -> Option<PendingEdit> {
+        let index = self.edits.iter().position(|edit| edit.id == id)?;
+        Some(self.edits.remove(index))
+    }
+
+    /// Return the server version of a chunk with the pending edits applied, in the order they were made
+    fn displayed_chunk(&self, chunk_pos: ChunkPos) -> Arc<Chunk> {
+        let mut chunk = self.server_chunks[&chunk_pos].clone();
+        for edit in &self.edits {
+            if edit.pos.containing_chunk_pos() == chunk_pos {
+                Arc::make_mut(&mut chunk)
+                    .set_block_at(edit.pos.pos_in_containing_chunk(), edit.block);
+            }
+        }
+        chunk
+    }
+
+    /// Stop keeping the server version of a chunk once it has no pending edits
+    fn forget_unused_chunk(&mut self, chunk_pos: ChunkPos) {
+        if !self
+            .edits
+            .iter()
+            .any(|edit| edit.pos.containing_chunk_pos() == chunk_pos)
+        {
+            self.server_chunks.remove(&chunk_pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: BlockId = 1;
+    const DIRT: BlockId = 2;
+
+    fn block(chunk: &Chunk, pos: (i64, i64, i64)) -> BlockId {
+        chunk.get_block_at(BlockPos::from(pos).pos_in_containing_chunk())
+    }
+
+    fn server_chunk(blocks: &[((i64, i64, i64), BlockId)]) -> Arc<Chunk> {
+        let mut chunk = Chunk::new((0, 0, 0).into());
+        for &(pos, block) in blocks {
+            chunk.set_block_at(BlockPos::from(pos).pos_in_containing_chunk(), block);
+        }
+        Arc::new(chunk)
+    }
+
+    #[test]
+    fn test_rejected_edits_are_rolled_back() {
+        let mut edits = PendingEdits::default();
+        let displayed = server_chunk(&[((1, 1, 1), STONE)]);
+        let displayed = edits.predict(0, (1, 1, 1).into(), 0, displayed);
+        let displayed = edits.predict(1, (2, 1, 1).into(), DIRT, displayed);
+        assert_eq!(block(&displayed, (1, 1, 1)), 0);
+        assert_eq!(block(&displayed, (2, 1, 1)), DIRT);
+        // The other edit is still displayed
+        let displayed = edits.reject(0).unwrap();
+        assert_eq!(block(&displayed, (1, 1, 1)), STONE);
+        assert_eq!(block(&displayed, (2, 1, 1)), DIRT);
+        let displayed = edits.reject(1).unwrap();
+        assert_eq!(block(&displayed, (2, 1, 1)), 0);
+        assert!(edits.server_chunks.is_empty());
+        // Unknown or already processed edits are ignored
+        assert!(edits.reject(1).is_none());
+    }
+
+    #[test]
+    fn test_pending_edits_survive_server_updates() {
+        let mut edits = PendingEdits::default();
+        let displayed = server_chunk(&[]);
+        edits.predict(0, (1, 1, 1).into(), STONE, displayed);
+        edits.predict(
+            1,
+            (2, 1, 1).into(),
+            STONE,
+            server_chunk(&[((1, 1, 1), STONE)]),
+        );
+        // A chunk sent before the server processed the edits
+        let displayed = edits.receive_chunk(server_chunk(&[((3, 1, 1), DIRT)]));
+        assert_eq!(block(&displayed, (1, 1, 1)), STONE);
+        assert_eq!(block(&displayed, (2, 1, 1)), STONE);
+        assert_eq!(block(&displayed, (3, 1, 1)), DIRT);
+        // The first edit is confirmed, but the second one is rejected before the new chunk arrives
+        edits.confirm(0);
+        let displayed = edits.reject(1).unwrap();
+        assert_eq!(block(&displayed, (1, 1, 1)), STONE);
+        assert_eq!(block(&displayed, (2, 1, 1)), 0);
+        assert_eq!(block(&displayed, (3, 1, 1)), DIRT);
+        // Without pending edits, the chunks of the server are displayed as is
+        assert!(edits.server_chunks.is_empty());
+        let chunk = server_chunk(&[]);
+        assert!(Arc::ptr_eq(&edits.receive_chunk(chunk.clone()), &chunk));
+    }
+}
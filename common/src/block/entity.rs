@@ -0,0 +1,15 @@
+//! Block entities: data attached to a single block, for example the text of a sign or the contents of a chest.
+//!
+//! The chunks only store block ids, so the block entities are stored next to them, indexed by position.
+//! They are created by the server when some blocks are placed, and removed when the block changes.
+use crate::item::inventory::Inventory;
+use serde::{Deserialize, Serialize};
+
+/// The data of a block entity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum BlockEntity {
+    /// A sign with some text on it
+    Sign { text: String },
+    /// A chest holding some items
+    Chest { inventory: Inventory },
+}
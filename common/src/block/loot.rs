@@ -0,0 +1,254 @@
+//! What blocks drop when they are broken.
+//!
+//! The drops are declared in the block files, and resolved into a `LootTable` for every block id once
+//! every item is known. The random parts of the drops use a deterministic `Rng`, so the drops only depend
+//! on the seed of the generator.
+use crate::item::{inventory::ItemStack, Item, ItemId, ToolKind};
+use crate::random::Rng;
+use crate::registry::Registry;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// The drops of a block, as declared in the block files
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Drops {
+    /// The tool that must be used for the block to drop anything
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+    /// The possible drops. If they are missing, the block drops its own item.
+    #[serde(default)]
+    pub loot: Option<Vec<LootEntry>>,
+}
+
+/// Some item that may be dropped, as declared in the block files
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootEntry {
+    /// The name of the item or of the block
+    pub item: String,
+    /// The number of items is chosen uniformly between `min` and `max`
+    #[serde(default = "default_count")]
+    pub min: u32,
+    #[serde(default = "default_count")]
+    pub max: u32,
+    /// The probability that the entry drops anything
+    #[serde(default = "default_chance")]
+    pub chance: f64,
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+fn default_chance() -> f64 {
+    1.0
+}
+
+/// Some item that may be dropped, with the item resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct LootTableEntry {
+    pub item: ItemId,
+    pub min: u32,
+    pub max: u32,
+    pub chance: f64,
+}
+
+/// What a block drops when it is broken
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LootTable {
+    pub tool: Option<ToolKind>,
+    pub entries: Vec<LootTableEntry>,
+}
+
+impl LootTable {
+    /// Resolve and validate the drops of block `block_name`, whose own item is `block_item`
+    pub fn new(
+        block_name: &str,
+        drops: &Drops,
+        block_item: Option<ItemId>,
+        items: &Registry<Item>,
+    ) -> Result<Self> {
+        let entries = match &drops.loot {
+            None => block_item
+                .map(|item| LootTableEntry {
+                    item,
+                    min: 1,
+                    max: 1,
+                    chance: 1.0,
+                })
+                .into_iter()
+                .collect(),
+            Some(loot) => loot
+                .iter()
+                .map(|entry| {
+                    anyhow::ensure!(
+                        entry.min <= entry.max,
+                        "drop {} of block {} has min > max",
+                        entry.item,
+                        block_name
+                    );
+                    anyhow::ensure!(
+                        (0.0..=1.0).contains(&entry.chance),
+                        "drop {} of block {} must have a chance between 0 and 1",
+                        entry.item,
+                        block_name
+                    );
+                    Ok(LootTableEntry {
+                        item: items.get_id_by_name(&entry.item).context(format!(
+                            "unknown item or block {} dropped by block {}",
+                            entry.item, block_name
+                        ))?,
+                        min: entry.min,
+                        max: entry.max,
+                        chance: entry.chance,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+        Ok(Self {
+            tool: drops.tool,
+            entries,
+        })
+    }
+
+    /// Choose the drops of a block broken with `tool`
+    pub fn roll(&self, tool: Option<ToolKind>, rng: &mut Rng) -> Vec<ItemStack> {
+        if self.tool.is_some() && self.tool != tool {
+            return Vec::new();
+        }
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                if entry.chance < 1.0 && rng.gen_f64() >= entry.chance {
+                    return None;
+                }
+                let count = entry.min + rng.gen_range(u64::from(entry.max - entry.min) + 1) as u32;
+                Some(ItemStack {
+                    item: entry.item,
+                    count,
+                })
+                .filter(|stack| stack.count > 0)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemType;
+
+    fn items() -> Registry<Item> {
+        let mut items = Registry::default();
+        for name in &["stone", "dirt", "leaves"] {
+            let item = Item {
+                name: name.to_string(),
+                ty: ItemType::NormalItem {
+                    texture: name.to_string(),
+                },
+            };
+            items.register(name.to_string(), item).unwrap();
+        }
+        items
+    }
+
+    fn entry(item: &str, min: u32, max: u32, chance: f64) -> LootEntry {
+        LootEntry {
+            item: item.to_owned(),
+            min,
+            max,
+            chance,
+        }
+    }
+
+    #[test]
+    fn test_block_drops_itself_by_default() {
+        let table = LootTable::new("stone", &Drops::default(), Some(0), &items()).unwrap();
+        let drops = table.roll(None, &mut Rng::new(42));
+        assert_eq!(drops, vec![ItemStack { item: 0, count: 1 }]);
+        let table = LootTable::new("air", &Drops::default(), None, &items()).unwrap();
+        assert_eq!(table.roll(None, &mut Rng::new(42)), vec![]);
+    }
+
+    #[test]
+    fn test_block_needs_the_right_tool() {
+        let drops = Drops {
+            tool: Some(ToolKind::Pickaxe),
+            loot: None,
+        };
+        let table = LootTable::new("stone", &drops, Some(0), &items()).unwrap();
+        let mut rng = Rng::new(0);
+        assert_eq!(table.roll(None, &mut rng), vec![]);
+        assert_eq!(table.roll(Some(ToolKind::Shovel), &mut rng), vec![]);
+        assert_eq!(table.roll(Some(ToolKind::Pickaxe), &mut rng).len(), 1);
+    }
+
+    #[test]
+    fn test_drops_are_deterministic() {
+        let drops = Drops {
+            tool: None,
+            loot: Some(vec![
+                entry("dirt", 1, 4, 1.0),
+                entry("leaves", 1, 1, 0.5),
+                entry("stone", 0, 2, 1.0),
+            ]),
+        };
+        let table = LootTable::new("grass", &drops, Some(0), &items()).unwrap();
+        let roll_many = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..1000)
+                .map(|_| table.roll(None, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        let rolls = roll_many(1234);
+        assert_eq!(rolls, roll_many(1234));
+        assert_ne!(rolls, roll_many(1235));
+
+        let count = |item: ItemId| -> Vec<u32> {
+            rolls
+                .iter()
+                .flat_map(|drops| drops.iter())
+                .filter(|stack| stack.item == item)
+                .map(|stack| stack.count)
+                .collect()
+        };
+        // Every count of the range is possible
+        let dirt = count(1);
+        assert_eq!(dirt.len(), 1000);
+        for n in 1..=4 {
+            assert!(dirt.contains(&n));
+        }
+        assert!(dirt.iter().all(|n| (1..=4).contains(n)));
+        // Leaves drop about half of the time
+        let leaves = count(2).len();
+        assert!((400..600).contains(&leaves));
+        // Empty stacks are not dropped
+        assert!(count(0).iter().all(|&n| n > 0));
+    }
+
+    #[test]
+    fn test_exact_drops_for_seed() {
+        let drops = Drops {
+            tool: None,
+            loot: Some(vec![entry("dirt", 1, 4, 1.0)]),
+        };
+        let table = LootTable::new("grass", &drops, Some(0), &items()).unwrap();
+        let mut rng = Rng::new(7);
+        let counts = (0..8)
+            .map(|_| table.roll(None, &mut rng)[0].count)
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 2, 3, 4, 1, 3, 3, 1]);
+    }
+
+    #[test]
+    fn test_invalid_drops_are_rejected() {
+        let drops = |entry| Drops {
+            tool: None,
+            loot: Some(vec![entry]),
+        };
+        let table = |entry| LootTable::new("block", &drops(entry), None, &items());
+        assert!(table(entry("diamond", 1, 1, 1.0)).is_err());
+        assert!(table(entry("dirt", 2, 1, 1.0)).is_err());
+        assert!(table(entry("dirt", 1, 1, 1.5)).is_err());
+        assert!(table(entry("dirt", 1, 1, 0.5)).is_ok());
+    }
+}
@@ -0,0 +1,119 @@
+//! How long it takes to break a block.
+//!
+//! The mining time of a block depends on its hardness, and on the tool the player holds. The client
+//! announces when it starts and stops mining a block, and the server only breaks the block if enough
+//! time elapsed between the two.
+use crate::item::ToolKind;
+use serde::Deserialize;
+
+/// Seconds it takes to break a block of hardness 1 without the right tool
+pub const SECONDS_PER_HARDNESS: f64 = 1.5;
+/// Number of crack stages that are displayed while a block is being mined
+pub const CRACK_STAGES: usize = 10;
+/// The server accepts to break a block if the player mined it for this many seconds less than the
+/// mining time, to account for the network jitter
+pub const MINING_TIME_TOLERANCE: f64 = 0.15;
+
+/// How hard a block is to break, as declared in the block files
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Mining {
+    /// The block takes `hardness * SECONDS_PER_HARDNESS` seconds to break. Blocks with hardness 0 break instantly.
+    #[serde(default = "default_hardness")]
+    pub hardness: f64,
+    /// The tool that breaks the block faster
+    #[serde(default)]
+    pub tool: Option<ToolKind>,
+}
+
+fn default_hardness() -> f64 {
+    1.0
+}
+
+impl Default for Mining {
+    fn default() -> Self {
+        Self {
+            hardness: default_hardness(),
+            tool: None,
+        }
+    }
+}
+
+/// A tool that can be used to mine blocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MiningTool {
+    pub kind: ToolKind,
+    /// How many times faster the tool mines the blocks it is effective on
+    pub speed: f64,
+}
+
+impl Mining {
+    /// Return the number of seconds it takes to break the block with `tool`
+    pub fn time(&self, tool: Option<MiningTool>) -> f64 {
+        let time = self.hardness.max(0.0) * SECONDS_PER_HARDNESS;
+        match tool {
+            Some(tool) if Some(tool.kind) == self.tool && tool.speed > 0.0 => time / tool.speed,
+            _ => time,
+        }
+    }
+}
+
+/// Return true if a block that takes `mining_time` seconds to break was mined for long enough
+pub fn is_mining_finished(elapsed: f64, mining_time: f64) -> bool {
+    elapsed >= mining_time - MINING_TIME_TOLERANCE
+}
+
+/// Return the crack stage of a block that was mined for `elapsed` seconds, between 0 and `CRACK_STAGES - 1`
+pub fn crack_stage(elapsed: f64, mining_time: f64) -> usize {
+    if mining_time <= 0.0 {
+        return CRACK_STAGES - 1;
+    }
+    let progress = (elapsed / mining_time).max(0.0).min(1.0);
+    ((progress * CRACK_STAGES as f64) as usize).min(CRACK_STAGES - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PICKAXE: MiningTool = MiningTool {
+        kind: ToolKind::Pickaxe,
+        speed: 4.0,
+    };
+
+    #[test]
+    fn test_tools_mine_faster() {
+        let stone = Mining {
+            hardness: 2.0,
+            tool: Some(ToolKind::Pickaxe),
+        };
+        assert!((stone.time(None) - 3.0).abs() < 1e-9);
+        assert!((stone.time(Some(PICKAXE)) - 0.75).abs() < 1e-9);
+        let shovel = MiningTool {
+            kind: ToolKind::Shovel,
+            speed: 4.0,
+        };
+        assert!((stone.time(Some(shovel)) - 3.0).abs() < 1e-9);
+        let grass = Mining {
+            hardness: 0.0,
+            tool: None,
+        };
+        assert!(grass.time(Some(PICKAXE)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_server_validates_elapsed_time() {
+        assert!(!is_mining_finished(0.5, 1.5));
+        assert!(is_mining_finished(1.4, 1.5));
+        assert!(is_mining_finished(2.0, 1.5));
+        assert!(is_mining_finished(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_crack_stages_follow_progress() {
+        assert_eq!(crack_stage(0.0, 2.0), 0);
+        assert_eq!(crack_stage(1.0, 2.0), CRACK_STAGES / 2);
+        assert_eq!(crack_stage(1.99, 2.0), CRACK_STAGES - 1);
+        assert_eq!(crack_stage(5.0, 2.0), CRACK_STAGES - 1);
+        assert_eq!(crack_stage(0.0, 0.0), CRACK_STAGES - 1);
+    }
+}
@@ -1,4 +1,15 @@
+pub mod entity;
+pub mod loot;
+pub mod mining;
+pub mod state;
+
+use self::loot::Drops;
+use self::mining::Mining;
+use self::state::{BlockProperty, BlockState};
 use crate::data::TextureRect;
+use crate::world::BlockPos;
+use nalgebra::Point3;
+use ncollide3d::bounding_volume::AABB;
 use serde::Deserialize;
 
 pub type BlockId = u16;
@@ -9,7 +20,177 @@ pub type BlockId = u16;
 #[serde(rename = "Block")]
 pub enum BlockType {
     Air, // TODO: skip when deserializing
-    NormalCube { face_textures: Vec<String> },
+    NormalCube {
+        face_textures: Vec<String>,
+        /// Whether the block falls when nothing supports it
+        #[serde(default)]
+        falls: bool,
+        /// The properties of the states of the block
+        #[serde(default)]
+        properties: Vec<BlockProperty>,
+        /// What the block drops when it is broken
+        #[serde(default)]
+        drops: Drops,
+        /// How hard the block is to break
+        #[serde(default)]
+        mining: Mining,
+    },
+    /// A fluid. Every fluid block is registered multiple times, once for each possible `FluidState`.
+    Fluid {
+        texture: String,
+        /// How many blocks the fluid can flow horizontally from a source
+        #[serde(default = "default_fluid_range")]
+        range: u8,
+        /// Number of ticks between two flow updates
+        #[serde(default = "default_fluid_flow_delay")]
+        flow_delay: u64,
+        /// How much light is lost when it goes through one block of the fluid, in addition to the usual 1
+        #[serde(default = "default_fluid_light_attenuation")]
+        light_attenuation: u8,
+        /// Whether two adjacent sources create a new source
+        #[serde(default = "default_fluid_renewable")]
+        renewable: bool,
+    },
+    /// The lower half of a block
+    Slab {
+        face_textures: Vec<String>,
+        #[serde(default)]
+        properties: Vec<BlockProperty>,
+        #[serde(default)]
+        drops: Drops,
+        #[serde(default)]
+        mining: Mining,
+    },
+    /// A bottom slab with a step on top of its -z half
+    Stairs {
+        face_textures: Vec<String>,
+        #[serde(default)]
+        properties: Vec<BlockProperty>,
+        #[serde(default)]
+        drops: Drops,
+        #[serde(default)]
+        mining: Mining,
+    },
+    /// A plant, drawn as two crossed quads. Plants can't be walked on and break when the block below them is not solid.
+    Plant {
+        texture: String,
+        #[serde(default)]
+        drops: Drops,
+        #[serde(default)]
+        mining: Mining,
+    },
+    /// A block made of a list of cuboids
+    Cuboids {
+        cuboids: Vec<Cuboid>,
+        #[serde(default)]
+        properties: Vec<BlockProperty>,
+        #[serde(default)]
+        drops: Drops,
+        #[serde(default)]
+        mining: Mining,
+    },
+}
+
+/// A box of a block model, in block units: the whole block goes from (0, 0, 0) to (1, 1, 1)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Cuboid {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    /// The textures of the faces, in the same order as for the full cubes
+    pub face_textures: Vec<String>,
+}
+
+impl BlockType {
+    /// Return the cuboids of the model of the block, for the blocks that are neither full cubes nor plants
+    pub fn cuboids(&self) -> Vec<Cuboid> {
+        let cuboid = |from: [f32; 3], to: [f32; 3], face_textures: &Vec<String>| Cuboid {
+            from,
+            to,
+            face_textures: face_textures.clone(),
+        };
+        match self {
+            Self::Slab { face_textures, .. } => {
+                vec![cuboid([0.0; 3], [1.0, 0.5, 1.0], face_textures)]
+            }
+            Self::Stairs { face_textures, .. } => vec![
+                cuboid([0.0; 3], [1.0, 0.5, 1.0], face_textures),
+                cuboid([0.0, 0.5, 0.0], [1.0, 1.0, 0.5], face_textures),
+            ],
+            Self::Cuboids { cuboids, .. } => cuboids.clone(),
+            Self::Air | Self::NormalCube { .. } | Self::Fluid { .. } | Self::Plant { .. } => {
+                Vec::new()
+            }
+        }
+    }
+
+    /// Return the properties of the states of the block
+    pub fn properties(&self) -> &[BlockProperty] {
+        match self {
+            Self::NormalCube { properties, .. }
+            | Self::Slab { properties, .. }
+            | Self::Stairs { properties, .. }
+            | Self::Cuboids { properties, .. } => properties,
+            Self::Air | Self::Fluid { .. } | Self::Plant { .. } => &[],
+        }
+    }
+
+    /// Return how hard the block is to break. Air and fluids can't be broken.
+    pub fn mining(&self) -> Option<Mining> {
+        match self {
+            Self::NormalCube { mining, .. }
+            | Self::Slab { mining, .. }
+            | Self::Stairs { mining, .. }
+            | Self::Plant { mining, .. }
+            | Self::Cuboids { mining, .. } => Some(*mining),
+            Self::Air | Self::Fluid { .. } => None,
+        }
+    }
+
+    /// Return what the block drops when it is broken. Air and fluids don't drop anything.
+    pub fn drops(&self) -> Option<&Drops> {
+        match self {
+            Self::NormalCube { drops, .. }
+            | Self::Slab { drops, .. }
+            | Self::Stairs { drops, .. }
+            | Self::Plant { drops, .. }
+            | Self::Cuboids { drops, .. } => Some(drops),
+            Self::Air | Self::Fluid { .. } => None,
+        }
+    }
+}
+
+fn default_fluid_range() -> u8 {
+    7
+}
+
+fn default_fluid_flow_delay() -> u64 {
+    5
+}
+
+fn default_fluid_light_attenuation() -> u8 {
+    2
+}
+
+fn default_fluid_renewable() -> bool {
+    true
+}
+
+/// The state of a fluid block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FluidState {
+    /// The id of the source block of this fluid
+    pub source: BlockId,
+    /// The distance to the closest source. 0 for source and falling blocks.
+    pub level: u8,
+    /// True if the fluid is falling, i.e. there is some fluid just above
+    pub falling: bool,
+}
+
+impl FluidState {
+    /// Return true if this is a source block
+    pub fn is_source(&self) -> bool {
+        self.level == 0 && !self.falling
+    }
 }
 
 /// A general block in-memory representation.
@@ -17,6 +198,109 @@ pub enum BlockType {
 pub struct Block {
     pub name: String,
     pub block_type: BlockType,
+    /// The fluid state if this is a fluid block
+    pub fluid: Option<FluidState>,
+    /// The values of the properties of the block
+    pub state: BlockState,
+    /// The id of the default state of the block
+    pub base: BlockId,
+}
+
+/// Light attenuation of an opaque block: no light can go through it.
+pub const OPAQUE_LIGHT_ATTENUATION: u8 = 15;
+
+/// Horizontal margin between the selection box of a plant and the sides of the block
+const PLANT_SELECTION_MARGIN: f64 = 0.2;
+/// Height of the selection box of a plant
+const PLANT_SELECTION_HEIGHT: f64 = 0.8;
+
+impl Block {
+    /// Return true if nothing can be seen through this block
+    pub fn is_opaque(&self) -> bool {
+        matches!(self.block_type, BlockType::NormalCube { .. })
+    }
+
+    /// Return true if this block stops the movement of the entities
+    pub fn is_solid(&self) -> bool {
+        match self.block_type {
+            BlockType::NormalCube { .. }
+            | BlockType::Slab { .. }
+            | BlockType::Stairs { .. }
+            | BlockType::Cuboids { .. } => true,
+            BlockType::Air | BlockType::Fluid { .. } | BlockType::Plant { .. } => false,
+        }
+    }
+
+    /// Return the boxes of this block that stop the movement of the entities, if the block is at `pos`
+    pub fn collision_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        match self.block_type {
+            BlockType::NormalCube { .. } => vec![pos.aabb()],
+            _ => self
+                .cuboids()
+                .iter()
+                .map(|cuboid| {
+                    let offset = |c: [f32; 3]| {
+                        Point3::new(
+                            pos.px as f64 + c[0] as f64,
+                            pos.py as f64 + c[1] as f64,
+                            pos.pz as f64 + c[2] as f64,
+                        )
+                    };
+                    AABB::new(offset(cuboid.from), offset(cuboid.to))
+                })
+                .collect(),
+        }
+    }
+
+    /// Return the cuboids of the model of the block, rotated according to its state
+    pub fn cuboids(&self) -> Vec<Cuboid> {
+        self.block_type
+            .cuboids()
+            .iter()
+            .map(|cuboid| self.state.transform_cuboid(cuboid))
+            .collect()
+    }
+
+    /// Return the id of the state of this block that is placed against face `face` of another block,
+    /// by a player whose yaw is `yaw` degrees
+    pub fn placement_state(&self, face: usize, yaw: f64) -> BlockId {
+        let properties = self.block_type.properties();
+        self.base + BlockState::for_placement(properties, face, yaw).index(properties) as BlockId
+    }
+
+    /// Return the boxes of this block that can be pointed at, if the block is at `pos`
+    pub fn selection_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        match self.block_type {
+            BlockType::Plant { .. } => {
+                let m = PLANT_SELECTION_MARGIN;
+                let (x, y, z) = (pos.px as f64, pos.py as f64, pos.pz as f64);
+                vec![AABB::new(
+                    Point3::new(x + m, y, z + m),
+                    Point3::new(x + 1.0 - m, y + PLANT_SELECTION_HEIGHT, z + 1.0 - m),
+                )]
+            }
+            _ => self.collision_boxes(pos),
+        }
+    }
+
+    /// Return true if this block falls when nothing supports it
+    pub fn falls(&self) -> bool {
+        match self.block_type {
+            BlockType::NormalCube { falls, .. } => falls,
+            _ => false,
+        }
+    }
+
+    /// Light that is lost when going through this block, in addition to the usual 1
+    pub fn light_attenuation(&self) -> u8 {
+        match self.block_type {
+            BlockType::NormalCube { .. } => OPAQUE_LIGHT_ATTENUATION,
+            BlockType::Fluid {
+                light_attenuation, ..
+            } => light_attenuation,
+            _ => 0,
+        }
+    }
 }
 
 /// The mesh of a block.
@@ -26,13 +310,186 @@ pub enum BlockMesh {
     Empty,
     /// A usual full cube
     FullCube { textures: [TextureRect; 6] },
+    /// A fluid block, with a possibly lowered top face
+    Fluid { texture: TextureRect, height: f32 },
+    /// A block made of cuboids that don't fill the whole block, for example a slab
+    Cuboids { cuboids: Vec<MeshCuboid> },
+    /// Two crossed diagonal quads, for example a plant
+    Cross { texture: TextureRect },
 }
 
+/// A cuboid of a block mesh, in block units
+#[derive(Debug, Clone)]
+pub struct MeshCuboid {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub textures: [TextureRect; 6],
+}
+
+/// Return true if the faces of the cuboids cover the whole face `face` of the block
+fn cuboids_cover_block_face(cuboids: &[MeshCuboid], face: usize) -> bool {
+    let axis = face / 2;
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    // The rectangles of the cuboid faces that lie on the face of the block
+    let rects = cuboids
+        .iter()
+        .filter(|cuboid| {
+            if face % 2 == 0 {
+                cuboid.max[axis] >= 1.0
+            } else {
+                cuboid.min[axis] <= 0.0
+            }
+        })
+        .map(|cuboid| {
+            (
+                [cuboid.min[u], cuboid.min[v]],
+                [cuboid.max[u], cuboid.max[v]],
+            )
+        })
+        .collect::<Vec<_>>();
+    // Cut the face along the sides of the rectangles, and check that every piece is covered
+    let cuts = |d: usize| {
+        let mut cuts = vec![0.0, 1.0];
+        for (min, max) in &rects {
+            cuts.push(min[d].max(0.0).min(1.0));
+            cuts.push(max[d].max(0.0).min(1.0));
+        }
+        cuts.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        cuts.dedup();
+        cuts
+    };
+    let (u_cuts, v_cuts) = (cuts(0), cuts(1));
+    u_cuts.windows(2).all(|us| {
+        v_cuts.windows(2).all(|vs| {
+            let center = [(us[0] + us[1]) / 2.0, (vs[0] + vs[1]) / 2.0];
+            rects
+                .iter()
+                .any(|(min, max)| (0..2).all(|d| min[d] <= center[d] && center[d] <= max[d]))
+        })
+    })
+}
+
+/// Bitmask of the faces of a full cube
+pub const ALL_FACES: u8 = 0b111111;
+
 impl BlockMesh {
     pub fn is_opaque(&self) -> bool {
+        matches!(self, Self::FullCube { .. })
+    }
+
+    /// Return the faces of the block that are completely hidden by the mesh, as a bitmask:
+    /// bit `i` is set if face `i` is covered, with faces in the order x, -x, y, -y, z, -z.
+    /// The faces of the adjacent blocks that touch a covered face are not visible.
+    pub fn covered_faces(&self) -> u8 {
         match self {
-            Self::Empty => false,
-            Self::FullCube { .. } => true,
+            Self::FullCube { .. } => ALL_FACES,
+            Self::Cuboids { cuboids } => (0..6)
+                .filter(|&face| cuboids_cover_block_face(cuboids, face))
+                .fold(0, |faces, face| faces | (1 << face)),
+            Self::Empty | Self::Fluid { .. } | Self::Cross { .. } => 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(block_type: BlockType) -> BlockMesh {
+        BlockMesh::Cuboids {
+            cuboids: block_type
+                .cuboids()
+                .into_iter()
+                .map(|cuboid| MeshCuboid {
+                    min: cuboid.from,
+                    max: cuboid.to,
+                    textures: [TextureRect::default(); 6],
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_slab_covers_bottom_face() {
+        let slab = mesh(BlockType::Slab {
+            face_textures: Vec::new(),
+            properties: Vec::new(),
+            drops: Drops::default(),
+            mining: Mining::default(),
+        });
+        assert_eq!(slab.covered_faces(), 1 << 3);
+    }
+
+    #[test]
+    fn test_stairs_cover_bottom_and_back_faces() {
+        let stairs = mesh(BlockType::Stairs {
+            face_textures: Vec::new(),
+            properties: Vec::new(),
+            drops: Drops::default(),
+            mining: Mining::default(),
+        });
+        assert_eq!(stairs.covered_faces(), (1 << 3) | (1 << 5));
+    }
+
+    fn block(block_type: BlockType, state: BlockState) -> Block {
+        Block {
+            name: "block".to_owned(),
+            block_type,
+            fluid: None,
+            state,
+            base: 10,
+        }
+    }
+
+    fn slab_type() -> BlockType {
+        BlockType::Slab {
+            face_textures: Vec::new(),
+            properties: vec![BlockProperty::Half],
+            drops: Drops::default(),
+            mining: Mining::default(),
+        }
+    }
+
+    #[test]
+    fn test_slab_collision_box() {
+        let slab = block(slab_type(), BlockState::default());
+        assert_eq!(
+            slab.collision_boxes((1, 2, 3).into()),
+            vec![AABB::new(
+                Point3::new(1.0, 2.0, 3.0),
+                Point3::new(2.0, 2.5, 4.0)
+            )]
+        );
+        let top_slab = block(
+            slab_type(),
+            BlockState {
+                half: state::Half::Top,
+                ..BlockState::default()
+            },
+        );
+        assert_eq!(
+            top_slab.collision_boxes((1, 2, 3).into()),
+            vec![AABB::new(
+                Point3::new(1.0, 2.5, 3.0),
+                Point3::new(2.0, 3.0, 4.0)
+            )]
+        );
+    }
+
+    #[test]
+    fn test_placement_state_id() {
+        let stairs = block(
+            BlockType::Stairs {
+                face_textures: Vec::new(),
+                properties: vec![BlockProperty::Facing, BlockProperty::Half],
+                drops: Drops::default(),
+                mining: Mining::default(),
+            },
+            BlockState::default(),
+        );
+        // Default state
+        assert_eq!(stairs.placement_state(2, 0.0), 10);
+        // Facing east, upside down
+        assert_eq!(stairs.placement_state(3, 270.0), 13);
+    }
+}
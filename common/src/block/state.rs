@@ -0,0 +1,320 @@
+//! Block states.
+//!
+//! A block can declare some properties, for example the direction it is facing.
+//! Every combination of the values of its properties is registered as a separate block id,
+//! so a block state is still stored as a single `BlockId` in the chunks.
+use super::Cuboid;
+use serde::Deserialize;
+
+/// A property that can be declared by a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum BlockProperty {
+    /// The horizontal direction the block is facing, chosen from the direction the player is looking at
+    Facing,
+    /// The axis of the block, chosen from the face it is placed against
+    Axis,
+    /// Whether the block is in the bottom or in the top half of the block, chosen from the face it is placed against
+    Half,
+}
+
+/// A horizontal direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Facing {
+    /// -z
+    North,
+    /// +x
+    East,
+    /// +z
+    South,
+    /// -x
+    West,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    Y,
+    X,
+    Z,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Half {
+    Bottom,
+    Top,
+}
+
+const FACINGS: [Facing; 4] = [Facing::North, Facing::East, Facing::South, Facing::West];
+const AXES: [Axis; 3] = [Axis::Y, Axis::X, Axis::Z];
+const HALVES: [Half; 2] = [Half::Bottom, Half::Top];
+
+impl BlockProperty {
+    /// Number of values of the property
+    fn num_values(self) -> usize {
+        match self {
+            Self::Facing => FACINGS.len(),
+            Self::Axis => AXES.len(),
+            Self::Half => HALVES.len(),
+        }
+    }
+}
+
+/// The values of the properties of a block.
+/// The properties that the block doesn't declare keep their default value.
+/// The default state is the model of the block as it is defined in the data files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockState {
+    pub facing: Facing,
+    pub axis: Axis,
+    pub half: Half,
+}
+
+impl Default for BlockState {
+    fn default() -> Self {
+        Self {
+            facing: Facing::North,
+            axis: Axis::Y,
+            half: Half::Bottom,
+        }
+    }
+}
+
+impl BlockState {
+    /// Index of the value of some property
+    fn value_index(&self, property: BlockProperty) -> usize {
+        match property {
+            BlockProperty::Facing => FACINGS.iter().position(|&f| f == self.facing),
+            BlockProperty::Axis => AXES.iter().position(|&a| a == self.axis),
+            BlockProperty::Half => HALVES.iter().position(|&h| h == self.half),
+        }
+        .expect("Every value is listed")
+    }
+
+    /// Return every state of a block with the given properties, starting with the default state.
+    /// The states are registered in this order, so the id of a state is the id of the default state plus its index.
+    pub fn all_states(properties: &[BlockProperty]) -> Vec<BlockState> {
+        let num_states = properties.iter().map(|p| p.num_values()).product::<usize>();
+        (0..num_states)
+            .map(|mut index| {
+                let mut state = BlockState::default();
+                for property in properties.iter().rev() {
+                    let value = index % property.num_values();
+                    index /= property.num_values();
+                    match property {
+                        BlockProperty::Facing => state.facing = FACINGS[value],
+                        BlockProperty::Axis => state.axis = AXES[value],
+                        BlockProperty::Half => state.half = HALVES[value],
+                    }
+                }
+                state
+            })
+            .collect()
+    }
+
+    /// Return the index of this state in `all_states(properties)`
+    pub fn index(&self, properties: &[BlockProperty]) -> usize {
+        properties.iter().fold(0, |index, &property| {
+            index * property.num_values() + self.value_index(property)
+        })
+    }
+
+    /// Return the name of this state of block `name`, for example `stone_stairs[facing=east,half=top]`.
+    /// The default state keeps the name of the block.
+    pub fn name(&self, name: &str, properties: &[BlockProperty]) -> String {
+        if *self == Self::default() {
+            return name.to_owned();
+        }
+        let values = properties
+            .iter()
+            .map(|property| {
+                let (key, value) = match property {
+                    BlockProperty::Facing => ("facing", format!("{:?}", self.facing)),
+                    BlockProperty::Axis => ("axis", format!("{:?}", self.axis)),
+                    BlockProperty::Half => ("half", format!("{:?}", self.half)),
+                };
+                format!("{}={}", key, value.to_lowercase())
+            })
+            .collect::<Vec<_>>();
+        format!("{}[{}]", name, values.join(","))
+    }
+
+    /// Choose the state of a block with the given properties that is placed against face `face` of another block,
+    /// by a player whose yaw is `yaw` degrees.
+    pub fn for_placement(properties: &[BlockProperty], face: usize, yaw: f64) -> BlockState {
+        let mut state = BlockState::default();
+        for property in properties {
+            match property {
+                BlockProperty::Facing => {
+                    // Same direction as the player
+                    let y = yaw.to_radians();
+                    let (dx, dz) = (-y.sin(), -y.cos());
+                    state.facing = if dx.abs() > dz.abs() {
+                        if dx > 0.0 {
+                            Facing::East
+                        } else {
+                            Facing::West
+                        }
+                    } else if dz > 0.0 {
+                        Facing::South
+                    } else {
+                        Facing::North
+                    };
+                }
+                BlockProperty::Axis => state.axis = [Axis::X, Axis::Y, Axis::Z][face / 2],
+                BlockProperty::Half => {
+                    // Blocks placed against the bottom of another block hang from it
+                    state.half = if face == 3 { Half::Top } else { Half::Bottom };
+                }
+            }
+        }
+        state
+    }
+
+    /// Move a point of the model of the block, in block units, from the default state to this state
+    pub fn transform_point(&self, point: [f32; 3]) -> [f32; 3] {
+        let [x, y, z] = point;
+        let y = match self.half {
+            Half::Bottom => y,
+            Half::Top => 1.0 - y,
+        };
+        // Rotate the y axis to the axis of the block
+        let [x, y, z] = match self.axis {
+            Axis::Y => [x, y, z],
+            Axis::X => [y, 1.0 - x, z],
+            Axis::Z => [x, 1.0 - z, y],
+        };
+        // Rotate the -z side of the block to the facing of the block
+        match self.facing {
+            Facing::North => [x, y, z],
+            Facing::East => [1.0 - z, y, x],
+            Facing::South => [1.0 - x, y, 1.0 - z],
+            Facing::West => [z, y, 1.0 - x],
+        }
+    }
+
+    /// Return which face face `face` of the default state becomes in this state
+    pub fn transform_face(&self, face: usize) -> usize {
+        let mut center = [0.5; 3];
+        center[face / 2] = if face % 2 == 0 { 1.0 } else { 0.0 };
+        let center = self.transform_point(center);
+        let axis = (0..3)
+            .find(|&axis| (center[axis] - 0.5).abs() > 0.25)
+            .expect("The center of a face is on a side of the block");
+        2 * axis + if center[axis] > 0.5 { 0 } else { 1 }
+    }
+
+    /// Move the values of the faces of the default state, in the usual face order, to the faces of this state
+    pub fn transform_faces<T: Clone>(&self, faces: &[T]) -> Vec<T> {
+        let mut transformed = faces.to_vec();
+        if faces.len() == 6 {
+            for (face, value) in faces.iter().enumerate() {
+                transformed[self.transform_face(face)] = value.clone();
+            }
+        }
+        transformed
+    }
+
+    /// Move a cuboid of the model of the block from the default state to this state
+    pub fn transform_cuboid(&self, cuboid: &Cuboid) -> Cuboid {
+        let a = self.transform_point(cuboid.from);
+        let b = self.transform_point(cuboid.to);
+        Cuboid {
+            from: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            to: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+            face_textures: self.transform_faces(&cuboid.face_textures),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_states_are_flattened() {
+        let properties = [BlockProperty::Facing, BlockProperty::Half];
+        let states = BlockState::all_states(&properties);
+        assert_eq!(states.len(), 8);
+        assert_eq!(states[0], BlockState::default());
+        for (index, state) in states.iter().enumerate() {
+            assert_eq!(state.index(&properties), index);
+        }
+        assert_eq!(BlockState::all_states(&[]), vec![BlockState::default()]);
+    }
+
+    #[test]
+    fn test_state_names() {
+        let properties = [BlockProperty::Facing, BlockProperty::Half];
+        let state = BlockState {
+            facing: Facing::East,
+            half: Half::Top,
+            ..BlockState::default()
+        };
+        assert_eq!(
+            state.name("stone_stairs", &properties),
+            "stone_stairs[facing=east,half=top]"
+        );
+        assert_eq!(
+            BlockState::default().name("stone_stairs", &properties),
+            "stone_stairs"
+        );
+    }
+
+    #[test]
+    fn test_placement_facing_follows_yaw() {
+        let facing = |yaw| BlockState::for_placement(&[BlockProperty::Facing], 2, yaw).facing;
+        assert_eq!(facing(0.0), Facing::North);
+        assert_eq!(facing(90.0), Facing::West);
+        assert_eq!(facing(180.0), Facing::South);
+        assert_eq!(facing(270.0), Facing::East);
+        assert_eq!(facing(-80.0), Facing::East);
+    }
+
+    #[test]
+    fn test_placement_axis_and_half_follow_face() {
+        let properties = [BlockProperty::Axis, BlockProperty::Half];
+        let state = BlockState::for_placement(&properties, 1, 0.0);
+        assert_eq!((state.axis, state.half), (Axis::X, Half::Bottom));
+        let state = BlockState::for_placement(&properties, 3, 0.0);
+        assert_eq!((state.axis, state.half), (Axis::Y, Half::Top));
+    }
+
+    #[test]
+    fn test_faces_are_rotated() {
+        let state = |facing, axis, half| BlockState { facing, axis, half };
+        // The back of stairs facing east is on the +x side
+        let east = state(Facing::East, Axis::Y, Half::Bottom);
+        assert_eq!(east.transform_face(5), 0);
+        assert_eq!(east.transform_face(2), 2);
+        // The top of a log along x is on the +x side
+        let log = state(Facing::North, Axis::X, Half::Bottom);
+        assert_eq!(
+            log.transform_faces(&[0, 1, 2, 3, 4, 5]),
+            vec![2, 3, 1, 0, 4, 5]
+        );
+        let upside_down = state(Facing::North, Axis::Y, Half::Top);
+        assert_eq!(upside_down.transform_face(3), 2);
+    }
+
+    #[test]
+    fn test_stairs_are_rotated() {
+        let step = Cuboid {
+            from: [0.0, 0.5, 0.0],
+            to: [1.0, 1.0, 0.5],
+            face_textures: Vec::new(),
+        };
+        let east = BlockState {
+            facing: Facing::East,
+            ..BlockState::default()
+        };
+        let rotated = east.transform_cuboid(&step);
+        assert_eq!(rotated.from, [0.5, 0.5, 0.0]);
+        assert_eq!(rotated.to, [1.0, 1.0, 1.0]);
+        let top = BlockState {
+            half: Half::Top,
+            ..BlockState::default()
+        };
+        let rotated = top.transform_cuboid(&step);
+        assert_eq!(rotated.from, [0.0, 0.0, 0.0]);
+        assert_eq!(rotated.to, [1.0, 0.5, 0.5]);
+    }
+}
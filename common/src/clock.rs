@@ -0,0 +1,182 @@
+//! The world clock, owned by the server and replicated to the clients.
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Number of game ticks per second
+pub const TICKS_PER_SECOND: u64 = 20;
+/// Duration of a game tick
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND);
+/// Default length of a full day, in ticks (20 minutes)
+pub const DEFAULT_DAY_LENGTH: u64 = 20 * 60 * TICKS_PER_SECOND;
+/// Sky light multiplier at night
+const MIN_SKY_LIGHT: f32 = 0.25;
+
+/// The time of the world.
+/// A day starts at sunrise: 0.25 of the day is noon, 0.5 is sunset and 0.75 is midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "SavedWorldTime")]
+pub struct WorldTime {
+    /// Ticks elapsed since the creation of the world
+    pub ticks: u64,
+    /// Length of a full day in ticks
+    pub day_length: u64,
+}
+
+impl WorldTime {
+    /// Create a new clock at sunrise of the first day
+    pub fn new(day_length: u64) -> Self {
+        Self {
+            ticks: 0,
+            day_length: day_length.max(1),
+        }
+    }
+
+    /// Advance the clock by one tick
+    pub fn tick(&mut self) {
+        self.add_ticks(1);
+    }
+
+    /// Advance the clock by some ticks. The clock stops instead of overflowing.
+    pub fn add_ticks(&mut self, ticks: u64) {
+        self.ticks = self.ticks.saturating_add(ticks);
+    }
+
+    /// Current day, starting at 0
+    pub fn day(&self) -> u64 {
+        self.ticks / self.day_length
+    }
+
+    /// Ticks elapsed since the beginning of the current day
+    pub fn tick_of_day(&self) -> u64 {
+        self.ticks % self.day_length
+    }
+
+    /// Set the time of the current day, without changing the current day
+    pub fn set_tick_of_day(&mut self, tick_of_day: u64) {
+        self.ticks = (self.day() * self.day_length).saturating_add(tick_of_day % self.day_length);
+    }
+
+    /// Change the length of a day, keeping the same fraction of the current day.
+    /// The clock stops instead of overflowing.
+    pub fn set_day_length(&mut self, day_length: u64) {
+        let day_length = day_length.max(1);
+        let tick_of_day = (self.time_of_day() * day_length as f64) as u64;
+        self.ticks = self
+            .day()
+            .saturating_mul(day_length)
+            .saturating_add(tick_of_day);
+        self.day_length = day_length;
+    }
+
+    /// Fraction of the current day that has elapsed, in `[0, 1)`
+    pub fn time_of_day(&self) -> f64 {
+        self.tick_of_day() as f64 / self.day_length as f64
+    }
+
+    /// Predict the clock after some real time has passed, assuming the server is ticking normally.
+    /// This is used by the clients to advance the time smoothly between two server updates.
+    pub fn predict(&self, elapsed: Duration) -> Self {
+        Self {
+            ticks: self
+                .ticks
+                .saturating_add((elapsed.as_secs_f64() * TICKS_PER_SECOND as f64) as u64),
+            day_length: self.day_length,
+        }
+    }
+
+    /// Unit vector pointing towards the sun
+    pub fn sun_direction(&self) -> Vector3<f64> {
+        let angle = self.time_of_day() * 2.0 * std::f64::consts::PI;
+        Vector3::new(angle.cos(), angle.sin(), 0.4).normalize()
+    }
+
+    /// Multiplier that should be applied to the sky light, between `MIN_SKY_LIGHT` at night and 1 during the day.
+    pub fn sky_light_multiplier(&self) -> f32 {
+        // Height of the sun in the sky, between -1 and 1
+        let sun_height = (self.time_of_day() * 2.0 * std::f64::consts::PI).sin() as f32;
+        // Smooth the transition around sunrise and sunset
+        let daylight = ((sun_height + 0.1) / 0.4).max(0.0).min(1.0);
+        MIN_SKY_LIGHT + (1.0 - MIN_SKY_LIGHT) * daylight
+    }
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        Self::new(DEFAULT_DAY_LENGTH)
+    }
+}
+
+/// A `WorldTime` as it is saved, before its day length is validated
+#[derive(Deserialize)]
+struct SavedWorldTime {
+    ticks: u64,
+    day_length: u64,
+}
+
+impl From<SavedWorldTime> for WorldTime {
+    fn from(saved: SavedWorldTime) -> Self {
+        Self {
+            ticks: saved.ticks,
+            day_length: saved.day_length.max(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day() {
+        let mut time = WorldTime::new(100);
+        assert_eq!((time.day(), time.tick_of_day()), (0, 0));
+        time.add_ticks(250);
+        assert_eq!((time.day(), time.tick_of_day()), (2, 50));
+        assert!((time.time_of_day() - 0.5).abs() < 1e-9);
+        // The clock stops instead of overflowing
+        time.add_ticks(u64::MAX);
+        time.tick();
+        assert_eq!(time.ticks, u64::MAX);
+    }
+
+    #[test]
+    fn test_set_tick_of_day() {
+        let mut time = WorldTime::new(100);
+        time.add_ticks(250);
+        time.set_tick_of_day(10);
+        assert_eq!((time.day(), time.tick_of_day()), (2, 10));
+        // The time wraps around the day
+        time.set_tick_of_day(130);
+        assert_eq!((time.day(), time.tick_of_day()), (2, 30));
+    }
+
+    #[test]
+    fn test_set_day_length() {
+        let mut time = WorldTime::new(100);
+        time.add_ticks(250);
+        time.set_day_length(1000);
+        assert_eq!((time.day(), time.tick_of_day()), (2, 500));
+        time.set_day_length(0);
+        assert_eq!(time.day_length, 1);
+        // The clock stops instead of overflowing
+        time.set_day_length(u64::MAX);
+        assert_eq!(time.ticks, u64::MAX);
+        assert_eq!(time.day_length, u64::MAX);
+    }
+
+    #[test]
+    fn test_saved_day_length_is_validated() {
+        let time: WorldTime = ron::de::from_str("(ticks: 42, day_length: 0)").unwrap();
+        assert_eq!(time.day_length, 1);
+        assert_eq!(time.tick_of_day(), 0);
+        let time: WorldTime = ron::de::from_str(&ron::ser::to_string(&time).unwrap()).unwrap();
+        assert_eq!(
+            time,
+            WorldTime {
+                ticks: 42,
+                day_length: 1
+            }
+        );
+    }
+}
@@ -1,12 +1,19 @@
 pub mod vox;
 
 use crate::{
-    block::{Block, BlockMesh, BlockType},
+    block::{
+        loot::LootTable, state::BlockState, Block, BlockId, BlockMesh, BlockType, FluidState,
+        MeshCuboid,
+    },
     registry::Registry,
 };
 
 use crate::data::vox::{load_voxel_model, VoxelModel};
-use crate::item::{Item, ItemMesh, ItemType};
+use crate::item::{
+    recipe::{Recipe, RecipeType},
+    Item, ItemId, ItemMesh, ItemType,
+};
+use crate::mob::{Mob, MobType};
 use anyhow::{Context, Result};
 use image::{ImageBuffer, Rgba};
 use log::info;
@@ -23,6 +30,20 @@ pub struct Data {
     pub models: Registry<VoxelModel>,
     pub items: Registry<Item>,
     pub item_meshes: Vec<ItemMesh>,
+    pub recipes: Vec<Recipe>,
+    /// The loot table of every block, indexed by block id
+    pub loot_tables: Vec<LootTable>,
+    pub mobs: Vec<Mob>,
+}
+
+impl Data {
+    /// Return the item that places some block, if there is one.
+    /// Every state of a block has the same item.
+    pub fn block_item(&self, block: BlockId) -> Option<ItemId> {
+        let base = self.blocks.get_value_by_id(block as u32)?.base;
+        let name = &self.blocks.get_value_by_id(base as u32)?.name;
+        self.items.get_id_by_name(name)
+    }
 }
 
 // TODO: decent error handling
@@ -111,7 +132,7 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
     // Generate item models
     for (name, ty) in item_datas.into_iter() {
         match &ty {
-            ItemType::NormalItem { texture } => {
+            ItemType::NormalItem { texture } | ItemType::Tool { texture, .. } => {
                 let texture_rect =
                     texture_rects[texture_registry.get_id_by_name(texture).unwrap() as usize];
                 let model = self::vox::item::generate_item_model(texture_rect, &texture_atlas);
@@ -133,6 +154,7 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
                     mesh_center,
                 });
             }
+            ItemType::Block { .. } => unreachable!("Block items are generated with the blocks"),
         }
     }
 
@@ -150,36 +172,197 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
             Block {
                 name: "air".to_owned(),
                 block_type: BlockType::Air,
+                fluid: None,
+                state: BlockState::default(),
+                base: 0,
             },
         )
         .expect("Couldn't register air in the registry.");
     meshes.push(BlockMesh::Empty);
 
+    let texture_rect = |texture: &String, block: &String| -> Result<TextureRect> {
+        Ok(texture_rects[texture_registry
+            .get_id_by_name(texture)
+            .context(format!("unknown texture {} for block {}", texture, block))?
+            as usize])
+    };
+    let face_textures = |names: &Vec<String>, block: &String| -> Result<[TextureRect; 6]> {
+        anyhow::ensure!(
+            names.len() == 6,
+            "block {} must have exactly 6 face textures, found {}",
+            block,
+            names.len()
+        );
+        Ok([
+            texture_rect(&names[0], block)?,
+            texture_rect(&names[1], block)?,
+            texture_rect(&names[2], block)?,
+            texture_rect(&names[3], block)?,
+            texture_rect(&names[4], block)?,
+            texture_rect(&names[5], block)?,
+        ])
+    };
+
     for (name, block_type) in block_datas.into_iter() {
-        let block = Block {
-            name: name.clone(),
-            block_type: block_type.clone(),
-        };
-        blocks.register(name, block)?;
-        let mesh = match block_type {
-            BlockType::Air => BlockMesh::Empty,
-            // TODO: make sure there are exactly 6 face textures
+        // The id of the first registered state of the block
+        let base = blocks.get_number_of_ids() as BlockId;
+        // Every state of the block is registered, see `BlockState::all_states`. Fluids have their own states.
+        let properties = block_type.properties().to_vec();
+        let states = BlockState::all_states(&properties)
+            .into_iter()
+            .map(|state| Block {
+                name: state.name(&name, &properties),
+                block_type: block_type.clone(),
+                fluid: None,
+                state,
+                base,
+            })
+            .collect::<Vec<_>>();
+        match block_type {
+            BlockType::Air => {
+                for block in states {
+                    blocks.register(block.name.clone(), block)?;
+                    meshes.push(BlockMesh::Empty);
+                }
+            }
             BlockType::NormalCube {
-                face_textures: names,
-            } => BlockMesh::FullCube {
-                textures: [
-                    texture_rects[texture_registry.get_id_by_name(&names[0]).unwrap() as usize],
-                    texture_rects[texture_registry.get_id_by_name(&names[1]).unwrap() as usize],
-                    texture_rects[texture_registry.get_id_by_name(&names[2]).unwrap() as usize],
-                    texture_rects[texture_registry.get_id_by_name(&names[3]).unwrap() as usize],
-                    texture_rects[texture_registry.get_id_by_name(&names[4]).unwrap() as usize],
-                    texture_rects[texture_registry.get_id_by_name(&names[5]).unwrap() as usize],
-                ],
-            },
+                face_textures: ref names,
+                ..
+            } => {
+                for block in states {
+                    let textures = face_textures(&block.state.transform_faces(names), &name)?;
+                    // The model is used to draw the block when it is not part of a chunk, for example when it falls
+                    let model = self::vox::block::generate_block_model(&textures, &texture_atlas);
+                    models.register(format!("block:{}", block.name), model)?;
+                    blocks.register(block.name.clone(), block)?;
+                    meshes.push(BlockMesh::FullCube { textures });
+                }
+            }
+            BlockType::Fluid {
+                ref texture, range, ..
+            } => {
+                let texture = texture_rects[texture_registry
+                    .get_id_by_name(texture)
+                    .context(format!("unknown texture {} for fluid {}", texture, name))?
+                    as usize];
+                // Register one block for each possible state of the fluid:
+                // `<name>` is the source, `<name>_falling` the falling fluid and `<name>_flowing_<level>` the flowing fluid.
+                let source = base;
+                let mut states = vec![
+                    (name.clone(), 0, false),
+                    (format!("{}_falling", name), 0, true),
+                ];
+                for level in 1..=range {
+                    states.push((format!("{}_flowing_{}", name, level), level, false));
+                }
+                // The model of the source is used to draw the item of the fluid
+                let cuboid = MeshCuboid {
+                    min: [0.0; 3],
+                    max: [1.0, FLUID_SOURCE_HEIGHT, 1.0],
+                    textures: [texture; 6],
+                };
+                let model = self::vox::block::generate_cuboids_model(&[cuboid], &texture_atlas);
+                models.register(format!("block:{}", name), model)?;
+                for (state_name, level, falling) in states {
+                    blocks.register(
+                        state_name.clone(),
+                        Block {
+                            name: state_name,
+                            block_type: block_type.clone(),
+                            fluid: Some(FluidState {
+                                source,
+                                level,
+                                falling,
+                            }),
+                            state: BlockState::default(),
+                            base,
+                        },
+                    )?;
+                    let height = if falling {
+                        1.0
+                    } else {
+                        FLUID_SOURCE_HEIGHT * (range + 1 - level) as f32 / (range + 1) as f32
+                    };
+                    meshes.push(BlockMesh::Fluid { texture, height });
+                }
+            }
+            BlockType::Slab { .. } | BlockType::Stairs { .. } | BlockType::Cuboids { .. } => {
+                for block in states {
+                    let cuboids = block
+                        .cuboids()
+                        .into_iter()
+                        .map(|cuboid| {
+                            Ok(MeshCuboid {
+                                min: cuboid.from,
+                                max: cuboid.to,
+                                textures: face_textures(&cuboid.face_textures, &name)?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    let model = self::vox::block::generate_cuboids_model(&cuboids, &texture_atlas);
+                    models.register(format!("block:{}", block.name), model)?;
+                    blocks.register(block.name.clone(), block)?;
+                    meshes.push(BlockMesh::Cuboids { cuboids });
+                }
+            }
+            BlockType::Plant { ref texture, .. } => {
+                let texture = texture_rect(texture, &name)?;
+                for block in states {
+                    let model = self::vox::block::generate_cross_model(&texture, &texture_atlas);
+                    models.register(format!("block:{}", block.name), model)?;
+                    blocks.register(block.name.clone(), block)?;
+                    meshes.push(BlockMesh::Cross { texture });
+                }
+            }
+        }
+
+        // Generate the item that places the block
+        if !matches!(block_type, BlockType::Air) {
+            items
+                .register(
+                    name.clone(),
+                    Item {
+                        name: name.clone(),
+                        ty: ItemType::Block { block: base },
+                    },
+                )
+                .context(format!("block {} has the same name as an item", name))?;
+            item_meshes.push(ItemMesh::Block { block: base });
+        }
+    }
+
+    // Resolve the drops of every block, once every item and block is known
+    let mut loot_tables = Vec::new();
+    for id in 0..blocks.get_number_of_ids() {
+        let block = blocks.get_value_by_id(id).unwrap();
+        let loot_table = match block.block_type.drops() {
+            Some(drops) => LootTable::new(
+                &block.name,
+                drops,
+                items.get_id_by_name(&blocks.get_value_by_id(block.base as u32).unwrap().name),
+                &items,
+            )?,
+            None => LootTable::default(),
         };
-        meshes.push(mesh);
+        loot_tables.push(loot_table);
     }
 
+    // Load recipes, once every item and block is known
+    let recipes_directory = data_directory.join("recipes");
+    let recipe_datas: Vec<(String, RecipeType)> = try_load_files_from_folder(recipes_directory)?;
+    let recipes = recipe_datas
+        .into_iter()
+        .map(|(name, recipe_type)| Recipe::new(name, &recipe_type, &items))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Load mobs, once every block and model is known
+    let mobs_directory = data_directory.join("mobs");
+    let mob_datas: Vec<(String, MobType)> = load_files_from_folder(mobs_directory);
+    let mobs = mob_datas
+        .into_iter()
+        .map(|(name, mob_type)| Mob::new(name, &mob_type, &blocks, &models))
+        .collect::<Result<Vec<_>>>()?;
+
     info!("Data successfully loaded");
     Ok(Data {
         blocks,
@@ -188,9 +371,15 @@ pub fn load_data(data_directory: PathBuf) -> Result<Data> {
         models,
         items,
         item_meshes,
+        recipes,
+        loot_tables,
+        mobs,
     })
 }
 
+/// Height of the top face of a fluid source block
+const FLUID_SOURCE_HEIGHT: f32 = 0.875;
+
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct TextureRect {
     pub x: f32,
@@ -259,16 +448,56 @@ fn load_textures(
     ))
 }
 
-/// Load all <name>.ron files from a given folder and parse them into type `T`.
+/// Load all <name>.ron files from a given folder and parse them into type `T`, skipping the files that fail to parse.
 fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) -> Vec<(String, T)> {
+    read_ron_files::<T>(directory)
+        .into_iter()
+        .filter_map(
+            |(file_stem, file_path, buffer)| match ron::de::from_str(&buffer) {
+                Ok(parsed_file) => Some((file_stem, parsed_file)),
+                Err(e) => {
+                    log::error!(
+                        "Failed to parse RON file {}: {}, skipping...",
+                        file_path.display(),
+                        e
+                    );
+                    None
+                }
+            },
+        )
+        .collect()
+}
+
+/// Load all <name>.ron files from a given folder and parse them into type `T`. Fail if any of the files doesn't parse.
+fn try_load_files_from_folder<T: serde::de::DeserializeOwned>(
+    directory: PathBuf,
+) -> Result<Vec<(String, T)>> {
+    read_ron_files::<T>(directory)
+        .into_iter()
+        .map(|(file_stem, file_path, buffer)| {
+            let parsed_file = ron::de::from_str(&buffer)
+                .context(format!("Failed to parse RON file {}", file_path.display()))?;
+            Ok((file_stem, parsed_file))
+        })
+        .collect()
+}
+
+/// Read all <name>.ron files from a given folder that contain objects of type `T`.
+/// Return the name, the path and the contents of each file.
+fn read_ron_files<T>(directory: PathBuf) -> Vec<(String, PathBuf, String)> {
     let mut result = Vec::new();
     info!(
         "Loading objects of type {} from directory {}",
         std::any::type_name::<T>(),
         directory.display(),
     );
-    for dir_entry in fs::read_dir(directory).expect("Failed to read from directory") {
-        let dir_entry = dir_entry.expect("Failed to read directory entry");
+    // Sort the files so that the ids of the objects are the same on every machine
+    let mut dir_entries = fs::read_dir(directory)
+        .expect("Failed to read from directory")
+        .map(|dir_entry| dir_entry.expect("Failed to read directory entry"))
+        .collect::<Vec<_>>();
+    dir_entries.sort_by_key(|dir_entry| dir_entry.path());
+    for dir_entry in dir_entries {
         if dir_entry
             .file_type()
             .expect("Failed to get file type")
@@ -295,21 +524,7 @@ fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) ->
                             .to_str()
                             .unwrap()
                             .to_owned();
-
-                        let parsed_file = {
-                            if ext == "ron" {
-                                match ron::de::from_str(&buffer) {
-                                    Ok(x) => x,
-                                    Err(e) => {
-                                        log::error!("Failed to parse RON: {}, skipping...", e);
-                                        continue;
-                                    }
-                                }
-                            } else {
-                                unreachable!("No parser for file format");
-                            }
-                        };
-                        result.push((file_stem, parsed_file));
+                        result.push((file_stem, file_path, buffer));
                     } else {
                         log::warn!("Unsupported file extension {:?}, skipping...", ext);
                         // TODO: display instead of debug
@@ -320,3 +535,25 @@ fn load_files_from_folder<T: serde::de::DeserializeOwned>(directory: PathBuf) ->
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_block_item_has_a_model() {
+        let data = load_data("../data".into()).unwrap();
+        for mesh in data.item_meshes.iter() {
+            if let ItemMesh::Block { block } = mesh {
+                let name = &data.blocks.get_value_by_id(*block as u32).unwrap().name;
+                assert!(
+                    data.models
+                        .get_id_by_name(&format!("block:{}", name))
+                        .is_some(),
+                    "The item of block {} has no model",
+                    name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::block::MeshCuboid;
+use crate::data::vox::VoxelModel;
+use crate::data::{TextureRect, MAX_TEXTURE_SIZE};
+use image::{ImageBuffer, Rgba};
+
+/// Return the number of voxels along each side of the model of a block textured with `texture`
+fn model_size(texture: &TextureRect) -> usize {
+    ((texture.width * MAX_TEXTURE_SIZE as f32).round() as usize).max(1)
+}
+
+/// Return pixel (u, v) of a texture, where v goes up, for a model with `size` voxels along each side.
+/// Textures may have a different size than the model.
+fn texture_pixel(
+    texture: &TextureRect,
+    size: usize,
+    u: usize,
+    v: usize,
+    atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> Rgba<u8> {
+    let x = (texture.x * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let y = (texture.y * MAX_TEXTURE_SIZE as f32).round() as u32;
+    let width = (texture.width * MAX_TEXTURE_SIZE as f32).round() as usize;
+    let height = (texture.height * MAX_TEXTURE_SIZE as f32).round() as usize;
+    let u = (u * width / size) as u32;
+    let v = ((size - 1 - v) * height / size) as u32;
+    *atlas.get_pixel(x + u, y + v)
+}
+
+/// Return the color of a pixel, as it is stored in the voxels
+fn voxel_color(rgba: Rgba<u8>) -> u32 {
+    // AGBR
+    ((rgba[2] as u32) << 16) + ((rgba[1] as u32) << 8) + rgba[0] as u32
+}
+
+/// Generate the model of a full cube. Every voxel of the surface takes the color of a pixel of the corresponding face.
+/// The faces are in the same order as in the block definitions: x, -x, y, -y, z, -z.
+pub fn generate_block_model(
+    textures: &[TextureRect; 6],
+    atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> VoxelModel {
+    let cube = MeshCuboid {
+        min: [0.0; 3],
+        max: [1.0; 3],
+        textures: *textures,
+    };
+    generate_cuboids_model(&[cube], atlas)
+}
+
+/// Generate the model of a block made of cuboids, like the slabs and the stairs.
+/// Every voxel of the surface of a cuboid takes the color of the pixel of the face texture at the same position in the block.
+pub fn generate_cuboids_model(
+    cuboids: &[MeshCuboid],
+    atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> VoxelModel {
+    let size = cuboids
+        .first()
+        .map_or(1, |cuboid| model_size(&cuboid.textures[0]));
+    // The first and the last voxel of every cuboid, along each axis
+    let bounds = cuboids
+        .iter()
+        .map(|cuboid| {
+            let first = |axis: usize| (cuboid.min[axis] * size as f32).round() as usize;
+            let last = |axis: usize| ((cuboid.max[axis] * size as f32).round() as usize).max(1) - 1;
+            ([first(0), first(1), first(2)], [last(0), last(1), last(2)])
+        })
+        .collect::<Vec<_>>();
+
+    let mut full = Vec::with_capacity(size * size * size);
+    let mut voxels = Vec::with_capacity(size * size * size);
+    for i in 0..size {
+        for j in 0..size {
+            for k in 0..size {
+                let cuboid = bounds.iter().position(|(first, last)| {
+                    (first[0]..=last[0]).contains(&i)
+                        && (first[1]..=last[1]).contains(&j)
+                        && (first[2]..=last[2]).contains(&k)
+                });
+                let (first, last, textures) = match cuboid {
+                    Some(c) => (bounds[c].0, bounds[c].1, &cuboids[c].textures),
+                    None => {
+                        full.push(false);
+                        voxels.push(0);
+                        continue;
+                    }
+                };
+                let color = |face: usize, u: usize, v: usize| {
+                    voxel_color(texture_pixel(&textures[face], size, u, v, atlas))
+                };
+                full.push(true);
+                // The top and bottom faces take precedence over the sides
+                voxels.push(if j == last[1] {
+                    color(2, i, k)
+                } else if j == first[1] {
+                    color(3, i, k)
+                } else if i == last[0] {
+                    color(0, k, j)
+                } else if i == first[0] {
+                    color(1, k, j)
+                } else if k == last[2] {
+                    color(4, i, j)
+                } else if k == first[2] {
+                    color(5, i, j)
+                } else {
+                    // Inside the cuboid, never visible
+                    0
+                });
+            }
+        }
+    }
+
+    VoxelModel {
+        size_x: size,
+        size_y: size,
+        size_z: size,
+        voxels,
+        full,
+    }
+}
+
+/// Generate the model of a plant: two diagonal planes with the texture of the plant, like the mesh of the plants
+pub fn generate_cross_model(
+    texture: &TextureRect,
+    atlas: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+) -> VoxelModel {
+    let size = model_size(texture);
+    let mut full = Vec::with_capacity(size * size * size);
+    let mut voxels = Vec::with_capacity(size * size * size);
+    for i in 0..size {
+        for j in 0..size {
+            for k in 0..size {
+                let rgba = texture_pixel(texture, size, i, j, atlas);
+                // Not transparent
+                if (k == i || k == size - 1 - i) && rgba[3] == 255 {
+                    full.push(true);
+                    voxels.push(voxel_color(rgba));
+                } else {
+                    full.push(false);
+                    voxels.push(0);
+                }
+            }
+        }
+    }
+
+    VoxelModel {
+        size_x: size,
+        size_y: size,
+        size_z: size,
+        voxels,
+        full,
+    }
+}
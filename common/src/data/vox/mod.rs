@@ -2,6 +2,7 @@ use std::fs::File;
 use std::io::Read;
 use std::str::from_utf8;
 
+pub mod block;
 pub mod item;
 
 const DEFAULT_PALETTE: [u32; 256] = [
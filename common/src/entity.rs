@@ -0,0 +1,258 @@
+//! Entities: mobs, items, falling blocks, projectiles and anything else that moves in the world.
+//!
+//! An entity is only an id. Its data is split into components, each kept in its own `ComponentStorage`.
+//! The physics components are part of the `PhysicsState`, so they are stepped by both the server and
+//! the client, and replicated with every physics update. The other components, like the model, don't change
+//! often and are sent by the server when the entity spawns.
+use crate::physics::falling_block::FallingBlock;
+use crate::physics::item_entity::ItemEntity;
+use crate::physics::player::{aabb_intersects_world, move_aabb_check_collision};
+use crate::physics::{BlockContainer, GRAVITY_ACCELERATION, MAX_FALL_SPEED};
+use nalgebra::{Isometry3, Point3, Vector3};
+use ncollide3d::bounding_volume::AABB;
+use std::collections::BTreeMap;
+
+/// Maximum distance between an entity and the ground for the entity to be on the ground
+const GROUND_DISTANCE: f64 = 0.01;
+
+/// Some unique entity id. Ids are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(u64);
+
+/// One component of the entities that have it, by entity id.
+/// The components are ordered by id to keep the simulation deterministic.
+#[derive(Debug, Clone)]
+pub struct ComponentStorage<T> {
+    components: BTreeMap<EntityId, T>,
+}
+
+impl<T> Default for ComponentStorage<T> {
+    fn default() -> Self {
+        Self {
+            components: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> ComponentStorage<T> {
+    /// Add the component to some entity, replacing the previous one
+    pub fn insert(&mut self, id: EntityId, component: T) {
+        self.components.insert(id, component);
+    }
+
+    /// Remove the component of some entity, and return it
+    pub fn remove(&mut self, id: EntityId) -> Option<T> {
+        self.components.remove(&id)
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        self.components.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        self.components.get_mut(&id)
+    }
+
+    /// Iterate over the entities that have the component
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.components
+            .iter()
+            .map(|(&id, component)| (id, component))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.components
+            .iter_mut()
+            .map(|(&id, component)| (id, component))
+    }
+}
+
+/// The model of an entity, drawn by the client
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntityModel {
+    /// Name of the model in the model registry
+    pub model: String,
+    /// Scale of the model
+    pub scale: f32,
+}
+
+/// The entities and their physics components
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    /// The id of the next entity
+    next_id: u64,
+    /// The position of the bottom center of every entity
+    pub positions: ComponentStorage<Point3<f64>>,
+    /// The velocity of the entities that move
+    pub velocities: ComponentStorage<Vector3<f64>>,
+    /// The size of the aabb of the entities that fall and collide with the world
+    pub colliders: ComponentStorage<Vector3<f64>>,
+    /// The direction the entities are facing, in degrees. A yaw of 0 faces -z, like the players.
+    pub yaws: ComponentStorage<f64>,
+    /// The items of the entities that are items lying in the world
+    pub items: ComponentStorage<ItemEntity>,
+    /// The block of the entities that are falling blocks
+    pub falling_blocks: ComponentStorage<FallingBlock>,
+}
+
+impl Entities {
+    /// Create an entity at `position`, without any other component
+    pub fn spawn(&mut self, position: Point3<f64>) -> EntityId {
+        let id = EntityId(self.next_id);
+        self.next_id += 1;
+        self.positions.insert(id, position);
+        id
+    }
+
+    /// Remove an entity and all of its components. Return false if it didn't exist.
+    pub fn despawn(&mut self, id: EntityId) -> bool {
+        self.velocities.remove(id);
+        self.colliders.remove(id);
+        self.yaws.remove(id);
+        self.items.remove(id);
+        self.falling_blocks.remove(id);
+        self.positions.remove(id).is_some()
+    }
+
+    /// Return true if the entity exists
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.positions.get(id).is_some()
+    }
+
+    /// Iterate over the ids of every entity
+    pub fn ids(&self) -> impl Iterator<Item = EntityId> + '_ {
+        self.positions.iter().map(|(id, _)| id)
+    }
+
+    /// Return the aabb of an entity, if it has a collider
+    pub fn aabb(&self, id: EntityId) -> Option<AABB<f64>> {
+        let position = self.positions.get(id)?;
+        let size = self.colliders.get(id)?;
+        let half_size = Vector3::new(size.x / 2.0, 0.0, size.z / 2.0);
+        Some(AABB::new(
+            position - half_size,
+            position + half_size + Vector3::new(0.0, size.y, 0.0),
+        ))
+    }
+
+    /// Return true if the entity has a collider and stands on something
+    pub fn is_on_ground<BC: BlockContainer>(&self, id: EntityId, world: &BC) -> bool {
+        match self.aabb(id) {
+            Some(aabb) => {
+                let lower_aabb = aabb.transform_by(&Isometry3::new(
+                    Vector3::new(0.0, -GROUND_DISTANCE, 0.0),
+                    Vector3::zeros(),
+                ));
+                !aabb_intersects_world(world, &aabb) && aabb_intersects_world(world, &lower_aabb)
+            }
+            None => false,
+        }
+    }
+
+    /// Move the entities according to their velocity. The entities with a collider fall and don't go through blocks.
+    pub fn step<BC: BlockContainer>(&mut self, seconds_delta: f64, world: &BC) {
+        let ids = self.velocities.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        for id in ids {
+            let movement = match self.aabb(id) {
+                Some(mut aabb) => {
+                    let velocity = self.velocities.get_mut(id).unwrap();
+                    velocity.y =
+                        (velocity.y - GRAVITY_ACCELERATION * seconds_delta).max(-MAX_FALL_SPEED);
+                    let expected_movement = *velocity * seconds_delta;
+                    let movement = move_aabb_check_collision(&mut aabb, world, expected_movement);
+                    for i in 0..3 {
+                        if (movement[i] - expected_movement[i]).abs() > 1e-9 {
+                            // The entity hit something
+                            velocity[i] = 0.0;
+                        }
+                    }
+                    movement
+                }
+                None => self.velocities.get(id).unwrap() * seconds_delta,
+            };
+            if let Some(position) = self.positions.get_mut(id) {
+                *position += movement;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::inventory::ItemStack;
+    use crate::physics::test_world::TestWorld;
+
+    const SECONDS_DELTA: f64 = 1.0 / 60.0;
+
+    #[test]
+    fn test_despawned_entities_lose_their_components() {
+        let mut entities = Entities::default();
+        let a = entities.spawn(Point3::origin());
+        let b = entities.spawn(Point3::new(1.0, 0.0, 0.0));
+        entities.velocities.insert(a, Vector3::zeros());
+        entities.colliders.insert(a, Vector3::repeat(1.0));
+        entities.yaws.insert(a, 90.0);
+        entities.items.insert(
+            a,
+            ItemEntity {
+                stack: ItemStack { item: 1, count: 1 },
+                age: 0.0,
+            },
+        );
+        entities.falling_blocks.insert(
+            a,
+            FallingBlock {
+                block: 1,
+                fall_duration: 0.0,
+            },
+        );
+        assert!(entities.despawn(a));
+        assert!(!entities.despawn(a));
+        assert!(!entities.contains(a));
+        assert!(entities.velocities.get(a).is_none());
+        assert!(entities.colliders.get(a).is_none());
+        assert!(entities.yaws.get(a).is_none());
+        assert!(entities.items.get(a).is_none());
+        assert!(entities.falling_blocks.get(a).is_none());
+        assert_eq!(entities.ids().collect::<Vec<_>>(), vec![b]);
+        // Ids are not reused
+        let c = entities.spawn(Point3::origin());
+        assert!(c != a && c != b);
+    }
+
+    #[test]
+    fn test_entities_with_a_collider_fall() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, 0).into());
+        let mut entities = Entities::default();
+        let falling = entities.spawn(Point3::new(0.5, 5.0, 0.5));
+        entities.velocities.insert(falling, Vector3::zeros());
+        entities
+            .colliders
+            .insert(falling, Vector3::new(0.6, 1.8, 0.6));
+        let flying = entities.spawn(Point3::new(5.5, 5.0, 0.5));
+        entities
+            .velocities
+            .insert(flying, Vector3::new(1.0, 0.0, 0.0));
+        let fixed = entities.spawn(Point3::new(-5.5, 5.0, 0.5));
+        for _ in 0..120 {
+            entities.step(SECONDS_DELTA, &world);
+        }
+        // Lands on the block
+        let position = entities.positions.get(falling).unwrap();
+        assert!((1.0..1.01).contains(&position.y));
+        assert_eq!(entities.velocities.get(falling), Some(&Vector3::zeros()));
+        assert!(entities.is_on_ground(falling, &world));
+        assert!(!entities.is_on_ground(flying, &world));
+        // Entities without a collider just follow their velocity
+        let position = entities.positions.get(flying).unwrap();
+        assert!((position.x - 7.5).abs() < 1e-6);
+        assert!((position.y - 5.0).abs() < 1e-6);
+        // Entities without a velocity don't move
+        assert_eq!(
+            entities.positions.get(fixed),
+            Some(&Point3::new(-5.5, 5.0, 0.5))
+        );
+    }
+}
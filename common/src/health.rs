@@ -0,0 +1,210 @@
+//! Health of the players, and the damage they take.
+//!
+//! The server owns the health of every player and sends it to the client whenever it changes.
+use crate::physics::GRAVITY_ACCELERATION;
+use std::fmt;
+
+/// Maximum health of the players
+pub const MAX_HEALTH: u32 = 20;
+/// How many seconds the players can stay with their head in a fluid before they start drowning
+pub const MAX_AIR: f64 = 10.0;
+/// Players can fall this many blocks without taking damage. They take one damage per block after that.
+const SAFE_FALL_HEIGHT: f64 = 3.0;
+/// Seconds between two damages when the head of the player is inside a block
+const SUFFOCATION_INTERVAL: f64 = 0.5;
+/// Seconds between two damages when the player has no air left
+const DROWNING_INTERVAL: f64 = 1.0;
+/// How fast the air refills when the head of the player is out of the fluid, in seconds of air per second
+const AIR_REFILL_SPEED: f64 = 5.0;
+/// The players start regenerating when they didn't take damage for this many seconds
+const REGENERATION_DELAY: f64 = 10.0;
+/// Seconds between two regenerated health points
+const REGENERATION_INTERVAL: f64 = 2.0;
+
+/// What damaged a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DamageSource {
+    Fall,
+    Suffocation,
+    Drowning,
+}
+
+impl fmt::Display for DamageSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cause = match self {
+            DamageSource::Fall => "fell from a high place",
+            DamageSource::Suffocation => "suffocated in a wall",
+            DamageSource::Drowning => "drowned",
+        };
+        write!(f, "{}", cause)
+    }
+}
+
+/// Return the damage taken by a player that lands at `landing_speed`
+pub fn fall_damage(landing_speed: f64) -> u32 {
+    let fall_height = landing_speed * landing_speed / (2.0 * GRAVITY_ACCELERATION);
+    (fall_height - SAFE_FALL_HEIGHT).round().max(0.0) as u32
+}
+
+/// The health of a player
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Health {
+    /// The current health, 0 if the player is dead
+    pub health: u32,
+    pub max_health: u32,
+    /// How many more seconds the player can stay with its head in a fluid before drowning
+    pub air: f64,
+    /// Seconds since the player last took damage
+    time_since_damage: f64,
+    /// Seconds until the next suffocation damage
+    suffocation_timer: f64,
+    /// Seconds until the next drowning damage
+    drowning_timer: f64,
+    /// Seconds until the next regenerated health point
+    regeneration_timer: f64,
+}
+
+impl Health {
+    /// Create the full health of a player
+    pub fn new(max_health: u32) -> Self {
+        Self {
+            health: max_health,
+            max_health,
+            air: MAX_AIR,
+            time_since_damage: 0.0,
+            suffocation_timer: 0.0,
+            drowning_timer: 0.0,
+            regeneration_timer: REGENERATION_INTERVAL,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+
+    /// Remove `amount` health points
+    pub fn damage(&mut self, amount: u32) {
+        if amount > 0 {
+            self.health = self.health.saturating_sub(amount);
+            self.time_since_damage = 0.0;
+            self.regeneration_timer = REGENERATION_INTERVAL;
+        }
+    }
+
+    /// Update the air and the health of a player whose head may be inside a block or a fluid.
+    /// Return what damaged the player, if something did.
+    pub fn tick(
+        &mut self,
+        seconds_delta: f64,
+        head_in_block: bool,
+        head_in_fluid: bool,
+    ) -> Option<DamageSource> {
+        let mut damage_source = None;
+        self.time_since_damage += seconds_delta;
+
+        if head_in_block {
+            self.suffocation_timer -= seconds_delta;
+            if self.suffocation_timer <= 0.0 {
+                self.suffocation_timer += SUFFOCATION_INTERVAL;
+                self.damage(1);
+                damage_source = Some(DamageSource::Suffocation);
+            }
+        } else {
+            self.suffocation_timer = 0.0;
+        }
+
+        if head_in_fluid {
+            self.air = (self.air - seconds_delta).max(0.0);
+            if self.air <= 0.0 {
+                self.drowning_timer -= seconds_delta;
+                if self.drowning_timer <= 0.0 {
+                    self.drowning_timer += DROWNING_INTERVAL;
+                    self.damage(1);
+                    damage_source = Some(DamageSource::Drowning);
+                }
+            }
+        } else {
+            self.air = (self.air + AIR_REFILL_SPEED * seconds_delta).min(MAX_AIR);
+            self.drowning_timer = 0.0;
+        }
+
+        if self.time_since_damage >= REGENERATION_DELAY && self.health < self.max_health {
+            self.regeneration_timer -= seconds_delta;
+            if self.regeneration_timer <= 0.0 {
+                self.regeneration_timer += REGENERATION_INTERVAL;
+                self.health += 1;
+            }
+        }
+
+        damage_source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECONDS_DELTA: f64 = 0.05;
+
+    fn tick(health: &mut Health, seconds: f64, head_in_block: bool, head_in_fluid: bool) {
+        for _ in 0..(seconds / SECONDS_DELTA).round() as usize {
+            health.tick(SECONDS_DELTA, head_in_block, head_in_fluid);
+        }
+    }
+
+    #[test]
+    fn test_high_falls_hurt() {
+        let speed_after_falling = |height: f64| (2.0 * GRAVITY_ACCELERATION * height).sqrt();
+        assert_eq!(fall_damage(0.0), 0);
+        assert_eq!(fall_damage(speed_after_falling(1.2)), 0);
+        assert_eq!(fall_damage(speed_after_falling(3.0)), 0);
+        assert_eq!(fall_damage(speed_after_falling(4.0)), 1);
+        assert_eq!(fall_damage(speed_after_falling(10.0)), 7);
+    }
+
+    #[test]
+    fn test_players_suffocate_in_blocks() {
+        let mut health = Health::new(MAX_HEALTH);
+        assert_eq!(
+            health.tick(SECONDS_DELTA, true, false),
+            Some(DamageSource::Suffocation)
+        );
+        assert_eq!(health.health, MAX_HEALTH - 1);
+        tick(&mut health, 1.0, true, false);
+        assert_eq!(health.health, MAX_HEALTH - 3);
+        tick(&mut health, 1.0, false, false);
+        assert_eq!(health.health, MAX_HEALTH - 3);
+    }
+
+    #[test]
+    fn test_players_drown_when_they_run_out_of_air() {
+        let mut health = Health::new(MAX_HEALTH);
+        tick(&mut health, MAX_AIR - 1.0, false, true);
+        assert_eq!(health.health, MAX_HEALTH);
+        assert!((health.air - 1.0).abs() < 1e-6);
+        tick(&mut health, 2.5, false, true);
+        assert!(health.air <= 0.0);
+        assert_eq!(health.health, MAX_HEALTH - 2);
+        // Breathing refills the air
+        tick(&mut health, 2.0, false, false);
+        assert!((health.air - MAX_AIR).abs() < 1e-6);
+        assert_eq!(health.health, MAX_HEALTH - 2);
+    }
+
+    #[test]
+    fn test_players_regenerate_and_die() {
+        let mut health = Health::new(MAX_HEALTH);
+        health.damage(5);
+        tick(&mut health, REGENERATION_DELAY - 1.0, false, false);
+        assert_eq!(health.health, MAX_HEALTH - 5);
+        tick(&mut health, 1.0 + 3.5 * REGENERATION_INTERVAL, false, false);
+        assert_eq!(health.health, MAX_HEALTH - 2);
+        tick(&mut health, 10.0 * REGENERATION_INTERVAL, false, false);
+        assert_eq!(health.health, MAX_HEALTH);
+
+        assert!(!health.is_dead());
+        health.damage(MAX_HEALTH + 10);
+        assert_eq!(health.health, 0);
+        assert!(health.is_dead());
+    }
+}
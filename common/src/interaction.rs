@@ -0,0 +1,320 @@
+//! Block interactions of the players: mining, placing and picking blocks.
+//!
+//! The client sends the block it is looking at with every interaction. The server checks it against its own
+//! simulation of the player before changing anything, and reports the rejected interactions to the client
+//! using the id of the interaction.
+use crate::block::{BlockId, FluidState};
+use crate::physics::player::{aabbs_overlap, ray_aabb_intersection, PhysicsPlayer};
+use crate::physics::{BlockContainer, BoundingVolume, AABB};
+use crate::world::BlockPos;
+use nalgebra::{Point3, Vector3};
+use std::fmt;
+
+/// Default maximum distance between the camera of a player and the blocks it interacts with
+pub const DEFAULT_REACH: f64 = 6.0;
+
+/// How far from the look ray of the server simulation the target of an interaction can be.
+/// The server simulation lags behind the client a bit, so the ray doesn't always hit the block the client saw.
+pub const TARGET_TOLERANCE: f64 = 0.3;
+
+/// The id of an interaction, chosen by the client
+pub type InteractionId = u32;
+
+/// A block interaction, as sent by the client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockInteraction {
+    pub id: InteractionId,
+    pub yaw: f64,
+    pub pitch: f64,
+    /// The block the client is looking at, and the face it points at.
+    /// The server only trusts the block: it computes the face itself.
+    pub target: (BlockPos, usize),
+}
+
+/// Why the server rejected an interaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InteractionError {
+    /// The game mode of the player doesn't let it interact with the world
+    NotAllowed,
+    /// The player doesn't point at any block within reach
+    OutOfReach,
+    /// The player points at another block than the one the client saw
+    TargetMismatch,
+    /// The placed block would intersect a player
+    IntersectsPlayer,
+    /// The placed block would intersect an entity or a falling block
+    IntersectsEntity,
+    /// The block would replace another block that is neither air nor a fluid
+    Occupied,
+    /// The block is in a chunk that is not loaded
+    ChunkNotLoaded,
+    /// The selected item doesn't place a block
+    NoBlockToPlace,
+    /// The block was not mined for long enough
+    NotMinedLongEnough,
+}
+
+impl fmt::Display for InteractionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            InteractionError::NotAllowed => "not allowed in this game mode",
+            InteractionError::OutOfReach => "the block is out of reach",
+            InteractionError::TargetMismatch => "the block is not in sight",
+            InteractionError::IntersectsPlayer => "a player is in the way",
+            InteractionError::IntersectsEntity => "an entity is in the way",
+            InteractionError::Occupied => "another block is in the way",
+            InteractionError::ChunkNotLoaded => "the chunk is not loaded",
+            InteractionError::NoBlockToPlace => "no block to place",
+            InteractionError::NotMinedLongEnough => "the block was not mined for long enough",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+/// Return the direction a player with some yaw and pitch looks at, in degrees
+pub fn look_direction(yaw: f64, pitch: f64) -> Vector3<f64> {
+    let y = yaw.to_radians();
+    let p = pitch.to_radians();
+    Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos())
+}
+
+/// Find the block pointed by `player` within `reach`, and check that it's the target of the interaction.
+/// Another target is accepted if the ray passes within `TARGET_TOLERANCE` of it, and if it is at most
+/// `TARGET_TOLERANCE` farther than the block the ray hits first, so that nothing is reached through the walls.
+/// Return the target and the face the ray of the server enters it through, whatever face the client sent.
+pub fn check_target<BC: BlockContainer>(
+    player: &PhysicsPlayer,
+    interaction: &BlockInteraction,
+    reach: f64,
+    world: &BC,
+) -> Result<(BlockPos, usize), InteractionError> {
+    let dir = look_direction(interaction.yaw, interaction.pitch);
+    let origin = player.get_camera_position();
+    let (target, _face) = interaction.target;
+    let (max_distance, error) = match player.get_pointed_at(dir, reach, world) {
+        Some((block, face)) if block == target => return Ok((block, face)),
+        Some((block, _)) => {
+            let hit = first_hit(&origin, &dir, &world.selection_boxes(block), 0.0);
+            let distance = hit.map_or(reach, |(t, _)| t + TARGET_TOLERANCE);
+            (distance.min(reach), InteractionError::TargetMismatch)
+        }
+        None => (reach, InteractionError::OutOfReach),
+    };
+    let target_boxes = world.selection_boxes(target);
+    match first_hit(&origin, &dir, &target_boxes, TARGET_TOLERANCE) {
+        Some((t, face)) if t <= max_distance => Ok((target, face)),
+        _ => Err(error),
+    }
+}
+
+/// Return the distance along a ray to the closest of some boxes loosened by `margin`,
+/// and the face the ray enters it through
+fn first_hit(
+    origin: &Point3<f64>,
+    dir: &Vector3<f64>,
+    boxes: &[AABB<f64>],
+    margin: f64,
+) -> Option<(f64, usize)> {
+    boxes
+        .iter()
+        .filter_map(|aabb| ray_aabb_intersection(origin, dir, &aabb.loosened(margin)))
+        .min_by(|(t1, _), (t2, _)| t1.partial_cmp(t2).unwrap())
+}
+
+/// Check that a block can be placed where `block` is: only air and fluids can be replaced
+pub fn check_replaceable(
+    block: BlockId,
+    fluid: Option<FluidState>,
+) -> Result<(), InteractionError> {
+    if block == 0 || fluid.is_some() {
+        Ok(())
+    } else {
+        Err(InteractionError::Occupied)
+    }
+}
+
+/// Check that a block with some collision boxes can be placed without intersecting the players
+/// and the aabbs of the entities. Spectators go through blocks, so they are ignored.
+pub fn check_placement<'a>(
+    collision_boxes: &[AABB<f64>],
+    players: impl IntoIterator<Item = &'a PhysicsPlayer>,
+    entity_aabbs: impl IntoIterator<Item = AABB<f64>>,
+) -> Result<(), InteractionError> {
+    let intersects = |other: &AABB<f64>| {
+        collision_boxes
+            .iter()
+            .any(|aabb| aabbs_overlap(aabb, other))
+    };
+    let intersects_player = players
+        .into_iter()
+        .filter(|player| player.game_mode.has_collisions())
+        .any(|player| intersects(&player.aabb));
+    if intersects_player {
+        return Err(InteractionError::IntersectsPlayer);
+    }
+    if entity_aabbs.into_iter().any(|aabb| intersects(&aabb)) {
+        return Err(InteractionError::IntersectsEntity);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::inventory::ItemStack;
+    use crate::physics::simulation::PhysicsState;
+    use crate::physics::test_world::TestWorld;
+    use crate::player::GameMode;
+
+    /// A world with a single block at (0, 0, -3)
+    fn one_block() -> TestWorld {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, -3).into());
+        world
+    }
+
+    /// A player whose camera is at (0.5, 0.5, 0.5)
+    fn player() -> PhysicsPlayer {
+        PhysicsPlayer::from_coords(Point3::new(0.5, -1.1, 0.5))
+    }
+
+    fn interaction(yaw: f64, target: (i64, i64, i64)) -> BlockInteraction {
+        BlockInteraction {
+            id: 0,
+            yaw,
+            pitch: 0.0,
+            target: (target.into(), 4),
+        }
+    }
+
+    #[test]
+    fn test_server_checks_the_target() {
+        let player = player();
+        assert_eq!(
+            check_target(&player, &interaction(0.0, (0, 0, -3)), 6.0, &one_block()),
+            Ok(((0, 0, -3).into(), 4))
+        );
+        assert_eq!(
+            check_target(&player, &interaction(0.0, (0, 0, -3)), 2.0, &one_block()),
+            Err(InteractionError::OutOfReach)
+        );
+        assert_eq!(
+            check_target(&player, &interaction(0.0, (0, 1, -3)), 6.0, &one_block()),
+            Err(InteractionError::TargetMismatch)
+        );
+        // Looking the other way
+        assert_eq!(
+            check_target(&player, &interaction(180.0, (0, 0, -3)), 6.0, &one_block()),
+            Err(InteractionError::OutOfReach)
+        );
+    }
+
+    #[test]
+    fn test_server_accepts_targets_close_to_the_ray() {
+        let player = player();
+        // The ray passes 0.1 next to the block
+        assert_eq!(
+            check_target(&player, &interaction(13.5, (0, 0, -3)), 6.0, &one_block()),
+            Ok(((0, 0, -3).into(), 4))
+        );
+        assert_eq!(
+            check_target(&player, &interaction(13.5, (0, 0, -3)), 2.0, &one_block()),
+            Err(InteractionError::OutOfReach)
+        );
+        // The ray passes too far from the block
+        assert_eq!(
+            check_target(&player, &interaction(30.0, (0, 0, -3)), 6.0, &one_block()),
+            Err(InteractionError::OutOfReach)
+        );
+    }
+
+    #[test]
+    fn test_targets_are_not_reached_through_walls() {
+        let player = player();
+        let mut world = one_block();
+        world.solid.insert((0, 0, -4).into());
+        assert_eq!(
+            check_target(&player, &interaction(0.0, (0, 0, -4)), 6.0, &world),
+            Err(InteractionError::TargetMismatch)
+        );
+        // The ray hits the block next to the target first, but the target is close enough
+        let mut world = one_block();
+        world.solid.insert((-1, 0, -3).into());
+        assert_eq!(
+            check_target(&player, &interaction(13.5, (0, 0, -3)), 6.0, &world),
+            Ok(((0, 0, -3).into(), 4))
+        );
+    }
+
+    #[test]
+    fn test_server_chooses_the_face() {
+        let player = player();
+        let mut world = one_block();
+        world.solid.insert((0, 0, -4).into());
+        // The -z face points into the solid block behind the target
+        for &yaw in [0.0, 13.5].iter() {
+            let interaction = BlockInteraction {
+                target: ((0, 0, -3).into(), 5),
+                ..interaction(yaw, (0, 0, -3))
+            };
+            let (target, face) = check_target(&player, &interaction, 6.0, &world).unwrap();
+            assert_eq!(face, 4);
+            assert_eq!(target.adjacent(face), (0, 0, -2).into());
+        }
+        // Blocks only replace air and fluids
+        let water = FluidState {
+            source: 1,
+            level: 0,
+            falling: false,
+        };
+        assert_eq!(check_replaceable(0, None), Ok(()));
+        assert_eq!(check_replaceable(3, Some(water)), Ok(()));
+        assert_eq!(check_replaceable(1, None), Err(InteractionError::Occupied));
+    }
+
+    #[test]
+    fn test_blocks_are_not_placed_inside_players() {
+        let player = player();
+        let feet = BlockPos::from((0, -2, 0)).aabb();
+        let below = BlockPos::from((0, -3, 0)).aabb();
+        let next = BlockPos::from((1, -1, 0)).aabb();
+        assert_eq!(
+            check_placement(&[feet], vec![&player], vec![]),
+            Err(InteractionError::IntersectsPlayer)
+        );
+        assert_eq!(check_placement(&[below], vec![&player], vec![]), Ok(()));
+        assert_eq!(check_placement(&[next], vec![&player], vec![]), Ok(()));
+        // Blocks without collisions and spectators don't matter
+        assert_eq!(check_placement(&[], vec![&player], vec![]), Ok(()));
+        let spectator = PhysicsPlayer {
+            game_mode: GameMode::Spectator,
+            ..player
+        };
+        assert_eq!(check_placement(&[feet], vec![&spectator], vec![]), Ok(()));
+    }
+
+    #[test]
+    fn test_blocks_are_not_placed_inside_entities() {
+        let mut state = PhysicsState::default();
+        let falling = BlockPos::from((0, 5, 0));
+        state.spawn_falling_block(1, falling);
+        let mob = state.entities.spawn(Point3::new(3.5, 0.0, 0.5));
+        state
+            .entities
+            .colliders
+            .insert(mob, Vector3::new(0.8, 1.5, 0.8));
+        let item = BlockPos::from((6, 0, 0));
+        state.spawn_item(ItemStack { item: 0, count: 1 }, Point3::new(6.5, 0.2, 0.5));
+
+        let check =
+            |pos: BlockPos| check_placement(&[pos.aabb()], vec![], state.solid_entity_aabbs());
+        assert_eq!(check(falling), Err(InteractionError::IntersectsEntity));
+        assert_eq!(
+            check((3, 1, 0).into()),
+            Err(InteractionError::IntersectsEntity)
+        );
+        assert_eq!(check((3, 2, 0).into()), Ok(()));
+        // Blocks can be placed over the items
+        assert_eq!(check(item), Ok(()));
+    }
+}
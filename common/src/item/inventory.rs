@@ -0,0 +1,197 @@
+//! Item stacks and inventories.
+//!
+//! Inventories are owned by the server, which applies every change requested by the players
+//! and sends the new contents back to them.
+use super::ItemId;
+use serde::{Deserialize, Serialize};
+
+/// Maximum number of items in a stack
+pub const MAX_STACK_SIZE: u32 = 64;
+/// Number of slots of the player inventory. The first slots are the hotbar.
+pub const PLAYER_INVENTORY_SIZE: usize = 36;
+/// Number of slots of the hotbar
+pub const HOTBAR_SIZE: usize = 9;
+
+/// Some number of identical items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub item: ItemId,
+    pub count: u32,
+}
+
+/// A fixed number of slots that can each hold an item stack
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Inventory {
+    slots: Vec<Option<ItemStack>>,
+}
+
+impl Inventory {
+    /// Create an empty inventory
+    pub fn new(size: usize) -> Self {
+        Self {
+            slots: vec![None; size],
+        }
+    }
+
+    /// Number of slots
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// The contents of every slot
+    pub fn slots(&self) -> &[Option<ItemStack>] {
+        &self.slots
+    }
+
+    /// Return the stack in some slot, or `None` if the slot is empty or doesn't exist
+    pub fn get(&self, slot: usize) -> Option<ItemStack> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    /// Replace the contents of some slot. Empty stacks are removed.
+    pub fn set(&mut self, slot: usize, stack: Option<ItemStack>) {
+        self.slots[slot] = stack.filter(|stack| stack.count > 0);
+    }
+
+    /// Return the first slot that holds some item
+    pub fn find(&self, item: ItemId) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| slot.map_or(false, |stack| stack.item == item))
+    }
+
+    /// Remove one item from the stack in some slot, and return the item
+    pub fn remove_one(&mut self, slot: usize) -> Option<ItemId> {
+        let mut stack = self.get(slot)?;
+        stack.count -= 1;
+        self.set(slot, Some(stack));
+        Some(stack.item)
+    }
+
+    /// Add a stack to the inventory, filling the existing stacks of the same item first.
+    /// Return what didn't fit.
+    pub fn insert(&mut self, mut stack: ItemStack) -> Option<ItemStack> {
+        for slot in self.slots.iter_mut().flatten() {
+            if slot.item == stack.item {
+                let moved = stack.count.min(MAX_STACK_SIZE.saturating_sub(slot.count));
+                slot.count += moved;
+                stack.count -= moved;
+            }
+        }
+        for slot in self.slots.iter_mut() {
+            if stack.count == 0 {
+                break;
+            }
+            if slot.is_none() {
+                let moved = stack.count.min(MAX_STACK_SIZE);
+                *slot = Some(ItemStack {
+                    item: stack.item,
+                    count: moved,
+                });
+                stack.count -= moved;
+            }
+        }
+        Some(stack).filter(|stack| stack.count > 0)
+    }
+
+    /// Remove and return every stack
+    pub fn take_all(&mut self) -> Vec<ItemStack> {
+        self.slots.iter_mut().filter_map(Option::take).collect()
+    }
+}
+
+/// One of the inventories a player can interact with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InventoryKind {
+    /// The inventory of the player
+    Player,
+    /// The crafting grid of the player
+    CraftingGrid,
+    /// The container the player opened
+    Container,
+}
+
+/// A slot of one of the inventories a player can interact with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotRef {
+    pub inventory: InventoryKind,
+    pub slot: usize,
+}
+
+/// Move stack `from` onto stack `to`, and return the new contents of both slots.
+/// Stacks of the same item are merged as much as possible, other stacks are swapped.
+pub fn move_stack(
+    from: Option<ItemStack>,
+    to: Option<ItemStack>,
+) -> (Option<ItemStack>, Option<ItemStack>) {
+    match (from, to) {
+        (Some(mut from), Some(mut to)) if from.item == to.item => {
+            let moved = from.count.min(MAX_STACK_SIZE.saturating_sub(to.count));
+            from.count -= moved;
+            to.count += moved;
+            (Some(from).filter(|stack| stack.count > 0), Some(to))
+        }
+        (from, to) => (to, from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(item: ItemId, count: u32) -> ItemStack {
+        ItemStack { item, count }
+    }
+
+    #[test]
+    fn test_insert_fills_existing_stacks_first() {
+        let mut inventory = Inventory::new(3);
+        inventory.set(2, Some(stack(1, 60)));
+        assert_eq!(inventory.insert(stack(1, 10)), None);
+        assert_eq!(inventory.get(2), Some(stack(1, 64)));
+        assert_eq!(inventory.get(0), Some(stack(1, 6)));
+        assert_eq!(inventory.insert(stack(2, 200)), Some(stack(2, 136)));
+        assert_eq!(inventory.get(1), Some(stack(2, 64)));
+    }
+
+    #[test]
+    fn test_move_stack_merges_or_swaps() {
+        assert_eq!(
+            move_stack(Some(stack(1, 10)), None),
+            (None, Some(stack(1, 10)))
+        );
+        assert_eq!(
+            move_stack(Some(stack(1, 10)), Some(stack(1, 60))),
+            (Some(stack(1, 6)), Some(stack(1, 64)))
+        );
+        assert_eq!(
+            move_stack(Some(stack(1, 10)), Some(stack(1, 5))),
+            (None, Some(stack(1, 15)))
+        );
+        assert_eq!(
+            move_stack(Some(stack(1, 10)), Some(stack(2, 5))),
+            (Some(stack(2, 5)), Some(stack(1, 10)))
+        );
+    }
+
+    #[test]
+    fn test_remove_one_empties_the_slot() {
+        let mut inventory = Inventory::new(2);
+        inventory.set(1, Some(stack(3, 2)));
+        assert_eq!(inventory.find(3), Some(1));
+        assert_eq!(inventory.remove_one(1), Some(3));
+        assert_eq!(inventory.remove_one(1), Some(3));
+        assert_eq!(inventory.get(1), None);
+        assert_eq!(inventory.remove_one(1), None);
+        assert_eq!(inventory.find(3), None);
+    }
+
+    #[test]
+    fn test_take_all_empties_the_inventory() {
+        let mut inventory = Inventory::new(4);
+        inventory.set(1, Some(stack(1, 3)));
+        inventory.set(3, Some(stack(2, 0)));
+        assert_eq!(inventory.take_all(), vec![stack(1, 3)]);
+        assert_eq!(inventory, Inventory::new(4));
+    }
+}
@@ -1,5 +1,9 @@
+use crate::block::{mining::MiningTool, BlockId};
 use serde::Deserialize;
 
+pub mod inventory;
+pub mod recipe;
+
 pub type ItemId = u32;
 
 /// The type of an item. It contains the behavior and the texture of the item.
@@ -7,7 +11,44 @@ pub type ItemId = u32;
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename = "Item")]
 pub enum ItemType {
-    NormalItem { texture: String },
+    NormalItem {
+        texture: String,
+    },
+    /// A tool, needed to get the drops of some blocks
+    Tool {
+        texture: String,
+        tool: ToolKind,
+        /// How many times faster the tool mines the blocks it is effective on
+        #[serde(default = "default_tool_speed")]
+        speed: f64,
+    },
+    /// An item that places a block. One is generated for every block, with the name of the block.
+    #[serde(skip_deserializing)]
+    Block {
+        block: BlockId,
+    },
+}
+
+fn default_tool_speed() -> f64 {
+    2.0
+}
+
+impl ItemType {
+    /// Return the tool to mine blocks with, if this item is one
+    pub fn mining_tool(&self) -> Option<MiningTool> {
+        match *self {
+            Self::Tool { tool, speed, .. } => Some(MiningTool { kind: tool, speed }),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of a tool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
 }
 
 /// The mesh of an item
@@ -22,6 +63,8 @@ pub enum ItemMesh {
         /// Center of the mesh, relative to the cube at position (0, 0, 0), before scaling
         mesh_center: (f32, f32, f32),
     },
+    /// Drawn like the block that the item places
+    Block { block: BlockId },
 }
 
 /// A general item in-memory representation
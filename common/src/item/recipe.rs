@@ -0,0 +1,348 @@
+//! Crafting recipes.
+//!
+//! Recipes are declared in `data/recipes`. Shaped recipes must be arranged like their pattern in the crafting grid,
+//! possibly mirrored, and shapeless recipes only need their ingredients to be somewhere in the grid.
+//! The items are referred to by name, and every block has an item with the same name.
+use super::inventory::{Inventory, ItemStack, MAX_STACK_SIZE};
+use super::{Item, ItemId};
+use crate::registry::Registry;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Number of rows and columns of the crafting grid
+pub const CRAFTING_GRID_SIZE: usize = 3;
+/// Number of slots of the crafting grid. The slots are stored row by row, starting with the top row.
+pub const CRAFTING_GRID_SLOTS: usize = CRAFTING_GRID_SIZE * CRAFTING_GRID_SIZE;
+
+/// A recipe. This is the data provided by the creator of the recipe.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Recipe")]
+pub enum RecipeType {
+    /// Each row of the pattern is a row of the crafting grid. Spaces are empty slots,
+    /// and the other characters are replaced by their item in `key`.
+    Shaped {
+        pattern: Vec<String>,
+        key: HashMap<char, String>,
+        result: String,
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+    Shapeless {
+        ingredients: Vec<String>,
+        result: String,
+        #[serde(default = "default_count")]
+        count: u32,
+    },
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+/// What a recipe needs in the crafting grid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ingredients {
+    /// The items of a `width` x `height` rectangle, row by row
+    Shaped {
+        width: usize,
+        height: usize,
+        slots: Vec<Option<ItemId>>,
+    },
+    /// The items, sorted by id
+    Shapeless(Vec<ItemId>),
+}
+
+/// A recipe, with the items resolved
+#[derive(Debug, Clone)]
+pub struct Recipe {
+    pub name: String,
+    pub ingredients: Ingredients,
+    pub result: ItemStack,
+}
+
+impl Recipe {
+    /// Resolve and validate a recipe loaded from the data files
+    pub fn new(name: String, recipe_type: &RecipeType, items: &Registry<Item>) -> Result<Self> {
+        let item = |item_name: &String| -> Result<ItemId> {
+            items.get_id_by_name(item_name).context(format!(
+                "unknown item or block {} in recipe {}",
+                item_name, name
+            ))
+        };
+        let (ingredients, result, count) = match recipe_type {
+            RecipeType::Shaped {
+                pattern,
+                key,
+                result,
+                count,
+            } => {
+                // Remove the empty rows and columns around the pattern
+                let rows = pattern
+                    .iter()
+                    .map(|row| row.chars().collect::<Vec<_>>())
+                    .collect::<Vec<_>>();
+                let is_full = |&(i, j): &(usize, usize)| rows[j][i] != ' ';
+                let cells = (0..rows.len())
+                    .flat_map(|j| (0..rows[j].len()).map(move |i| (i, j)))
+                    .filter(is_full)
+                    .collect::<Vec<_>>();
+                anyhow::ensure!(!cells.is_empty(), "recipe {} has an empty pattern", name);
+                let min_x = cells.iter().map(|c| c.0).min().unwrap();
+                let max_x = cells.iter().map(|c| c.0).max().unwrap();
+                let min_y = cells.iter().map(|c| c.1).min().unwrap();
+                let max_y = cells.iter().map(|c| c.1).max().unwrap();
+                let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+                anyhow::ensure!(
+                    width <= CRAFTING_GRID_SIZE && height <= CRAFTING_GRID_SIZE,
+                    "the pattern of recipe {} doesn't fit in the crafting grid",
+                    name
+                );
+                let mut slots = Vec::with_capacity(width * height);
+                for row in &rows[min_y..=max_y] {
+                    for i in min_x..=max_x {
+                        slots.push(match row.get(i) {
+                            None | Some(' ') => None,
+                            Some(c) => Some(item(key.get(c).context(format!(
+                                "character '{}' of recipe {} is not in the key",
+                                c, name
+                            ))?)?),
+                        });
+                    }
+                }
+                let ingredients = Ingredients::Shaped {
+                    width,
+                    height,
+                    slots,
+                };
+                (ingredients, result, *count)
+            }
+            RecipeType::Shapeless {
+                ingredients,
+                result,
+                count,
+            } => {
+                anyhow::ensure!(
+                    !ingredients.is_empty() && ingredients.len() <= CRAFTING_GRID_SLOTS,
+                    "recipe {} must have between 1 and {} ingredients",
+                    name,
+                    CRAFTING_GRID_SLOTS
+                );
+                let mut ingredients = ingredients.iter().map(item).collect::<Result<Vec<_>>>()?;
+                ingredients.sort_unstable();
+                (Ingredients::Shapeless(ingredients), result, *count)
+            }
+        };
+        anyhow::ensure!(
+            (1..=MAX_STACK_SIZE).contains(&count),
+            "recipe {} must craft between 1 and {} items",
+            name,
+            MAX_STACK_SIZE
+        );
+        let result = ItemStack {
+            item: item(result)?,
+            count,
+        };
+        Ok(Self {
+            name,
+            ingredients,
+            result,
+        })
+    }
+
+    /// Return true if the items in the crafting grid are those of the recipe
+    pub fn matches(&self, grid: &Inventory) -> bool {
+        let item = |i: usize, j: usize| grid.get(j * CRAFTING_GRID_SIZE + i).map(|s| s.item);
+        let cells = (0..CRAFTING_GRID_SIZE)
+            .flat_map(|j| (0..CRAFTING_GRID_SIZE).map(move |i| (i, j)))
+            .filter(|&(i, j)| item(i, j).is_some())
+            .collect::<Vec<_>>();
+        if cells.is_empty() {
+            return false;
+        }
+        match &self.ingredients {
+            Ingredients::Shaped {
+                width,
+                height,
+                slots,
+            } => {
+                let min_x = cells.iter().map(|c| c.0).min().unwrap();
+                let max_x = cells.iter().map(|c| c.0).max().unwrap();
+                let min_y = cells.iter().map(|c| c.1).min().unwrap();
+                let max_y = cells.iter().map(|c| c.1).max().unwrap();
+                if (max_x - min_x + 1, max_y - min_y + 1) != (*width, *height) {
+                    return false;
+                }
+                let matches_pattern = |mirrored: bool| {
+                    (0..*height).all(|j| {
+                        (0..*width).all(|i| {
+                            let pattern_i = if mirrored { width - 1 - i } else { i };
+                            item(min_x + i, min_y + j) == slots[j * width + pattern_i]
+                        })
+                    })
+                };
+                matches_pattern(false) || matches_pattern(true)
+            }
+            Ingredients::Shapeless(ingredients) => {
+                let mut items = cells
+                    .iter()
+                    .map(|&(i, j)| item(i, j).unwrap())
+                    .collect::<Vec<_>>();
+                items.sort_unstable();
+                &items == ingredients
+            }
+        }
+    }
+}
+
+/// Return the first recipe that matches the crafting grid
+pub fn find_recipe<'a>(recipes: &'a [Recipe], grid: &Inventory) -> Option<&'a Recipe> {
+    recipes.iter().find(|recipe| recipe.matches(grid))
+}
+
+/// Craft the recipe that matches the crafting grid, consuming one item from every slot of the grid.
+/// Return the crafted items.
+pub fn craft(recipes: &[Recipe], grid: &mut Inventory) -> Option<ItemStack> {
+    let result = find_recipe(recipes, grid)?.result;
+    for slot in 0..grid.size() {
+        grid.remove_one(slot);
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::ItemType;
+
+    fn items() -> Registry<Item> {
+        let mut items = Registry::default();
+        for name in &["wood", "stone", "chest", "stone_stairs", "sign"] {
+            items
+                .register(
+                    name.to_string(),
+                    Item {
+                        name: name.to_string(),
+                        ty: ItemType::NormalItem {
+                            texture: name.to_string(),
+                        },
+                    },
+                )
+                .unwrap();
+        }
+        items
+    }
+
+    fn id(name: &str) -> ItemId {
+        items().get_id_by_name(&name.to_owned()).unwrap()
+    }
+
+    fn shaped(pattern: &[&str], result: &str, count: u32) -> Result<Recipe> {
+        let mut key = HashMap::new();
+        key.insert('w', "wood".to_owned());
+        key.insert('s', "stone".to_owned());
+        let recipe_type = RecipeType::Shaped {
+            pattern: pattern.iter().map(|row| row.to_string()).collect(),
+            key,
+            result: result.to_owned(),
+            count,
+        };
+        Recipe::new("test".to_owned(), &recipe_type, &items())
+    }
+
+    /// Create a crafting grid from rows of `w`, `s` and spaces, with `count` items per stack
+    fn grid(rows: &[&str], count: u32) -> Inventory {
+        let mut grid = Inventory::new(CRAFTING_GRID_SLOTS);
+        for (j, row) in rows.iter().enumerate() {
+            for (i, c) in row.chars().enumerate() {
+                let item = match c {
+                    'w' => id("wood"),
+                    's' => id("stone"),
+                    _ => continue,
+                };
+                grid.set(j * CRAFTING_GRID_SIZE + i, Some(ItemStack { item, count }));
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn test_shaped_recipe_matches_anywhere() {
+        let recipe = shaped(&["ww", "ww"], "chest", 1).unwrap();
+        assert!(recipe.matches(&grid(&["ww", "ww"], 1)));
+        assert!(recipe.matches(&grid(&["", " ww", " ww"], 1)));
+        assert!(!recipe.matches(&grid(&["ww", "w"], 1)));
+        assert!(!recipe.matches(&grid(&["ww", "ws"], 1)));
+        assert!(!recipe.matches(&grid(&["ww", "ww", "w"], 1)));
+        assert!(!recipe.matches(&grid(&[], 1)));
+    }
+
+    #[test]
+    fn test_shaped_recipe_matches_mirrored() {
+        let recipe = shaped(&["s  ", "ss ", "sss"], "stone_stairs", 4).unwrap();
+        assert!(recipe.matches(&grid(&["s", "ss", "sss"], 1)));
+        assert!(recipe.matches(&grid(&["  s", " ss", "sss"], 1)));
+        // Upside down is not the same recipe
+        assert!(!recipe.matches(&grid(&["sss", "ss", "s"], 1)));
+    }
+
+    #[test]
+    fn test_pattern_is_trimmed() {
+        let recipe = shaped(&["   ", " w ", " w "], "sign", 1).unwrap();
+        assert_eq!(
+            recipe.ingredients,
+            Ingredients::Shaped {
+                width: 1,
+                height: 2,
+                slots: vec![Some(id("wood")), Some(id("wood"))],
+            }
+        );
+        assert!(recipe.matches(&grid(&["  w", "  w"], 1)));
+    }
+
+    #[test]
+    fn test_shapeless_recipe_ignores_positions() {
+        let recipe_type = RecipeType::Shapeless {
+            ingredients: vec!["stone".to_owned(), "wood".to_owned()],
+            result: "sign".to_owned(),
+            count: 1,
+        };
+        let recipe = Recipe::new("test".to_owned(), &recipe_type, &items()).unwrap();
+        assert!(recipe.matches(&grid(&["w", "", "  s"], 1)));
+        assert!(recipe.matches(&grid(&["sw"], 1)));
+        assert!(!recipe.matches(&grid(&["sww"], 1)));
+        assert!(!recipe.matches(&grid(&["w"], 1)));
+    }
+
+    #[test]
+    fn test_crafting_consumes_one_item_per_slot() {
+        let recipes = vec![shaped(&["ww", "ww"], "chest", 2).unwrap()];
+        let mut crafting_grid = grid(&["ww", "ww"], 2);
+        crafting_grid.set(
+            1,
+            Some(ItemStack {
+                item: id("wood"),
+                count: 1,
+            }),
+        );
+        let chests = Some(ItemStack {
+            item: id("chest"),
+            count: 2,
+        });
+        assert_eq!(craft(&recipes, &mut crafting_grid), chests);
+        assert_eq!(crafting_grid.get(0).unwrap().count, 1);
+        assert_eq!(crafting_grid.get(1), None);
+        // The grid doesn't match the recipe anymore
+        assert_eq!(craft(&recipes, &mut crafting_grid), None);
+        assert_eq!(crafting_grid.get(0).unwrap().count, 1);
+    }
+
+    #[test]
+    fn test_invalid_recipes_are_rejected() {
+        assert!(shaped(&["wx"], "chest", 1).is_err());
+        assert!(shaped(&["ww"], "diamond", 1).is_err());
+        assert!(shaped(&["wwww"], "chest", 1).is_err());
+        assert!(shaped(&["   "], "chest", 1).is_err());
+        assert!(shaped(&["w"], "chest", 0).is_err());
+    }
+}
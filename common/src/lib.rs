@@ -1,11 +1,17 @@
 pub mod block;
+pub mod clock;
 pub mod collections;
 pub mod data;
 pub mod debug;
+pub mod entity;
+pub mod health;
+pub mod interaction;
 pub mod item;
+pub mod mob;
 pub mod network;
 pub mod physics;
 pub mod player;
+pub mod random;
 pub mod registry;
 pub mod time;
 pub mod worker;
@@ -0,0 +1,208 @@
+//! The creatures that live in the world, as declared in the mob files.
+//!
+//! This module only contains the data of the mobs. Their behavior is simulated by the server.
+use crate::block::{Block, BlockId};
+use crate::data::vox::VoxelModel;
+use crate::registry::Registry;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// What a mob does when it is not walking somewhere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum MobBehavior {
+    /// Walk to random places nearby
+    Wander,
+    /// Walk to the closest player, or wander if there is no player nearby
+    Follow,
+    /// Run away from the closest player, or wander if there is no player nearby
+    Flee,
+}
+
+/// Where a mob can spawn, as declared in the mob files
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnConditions {
+    /// The blocks the mob can spawn on
+    pub blocks: Vec<String>,
+    /// The minimum light level where the mob spawns
+    #[serde(default)]
+    pub min_light: u8,
+    /// The maximum light level where the mob spawns
+    #[serde(default = "default_max_light")]
+    pub max_light: u8,
+}
+
+fn default_max_light() -> u8 {
+    15
+}
+
+fn default_width() -> f64 {
+    0.6
+}
+
+/// A mob, as declared in the mob files
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename = "Mob")]
+pub struct MobType {
+    /// The name of the model in the model registry
+    pub model: String,
+    /// The height of the mob, the model is scaled to match it
+    pub height: f64,
+    /// The width of the aabb of the mob
+    #[serde(default = "default_width")]
+    pub width: f64,
+    /// The walking speed of the mob, in blocks per second
+    pub speed: f64,
+    pub behavior: MobBehavior,
+    pub spawn: SpawnConditions,
+}
+
+/// Where a mob can spawn, with the blocks resolved
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnRule {
+    /// The base ids of the blocks the mob can spawn on
+    pub blocks: Vec<BlockId>,
+    pub min_light: u8,
+    pub max_light: u8,
+}
+
+impl SpawnRule {
+    /// Return true if the mob can spawn on block `ground`, given as a base id, with light level `light`
+    pub fn allows(&self, ground: BlockId, light: u8) -> bool {
+        self.blocks.contains(&ground) && (self.min_light..=self.max_light).contains(&light)
+    }
+}
+
+/// A mob, with its model and spawn conditions resolved
+#[derive(Debug, Clone)]
+pub struct Mob {
+    pub name: String,
+    pub model: String,
+    /// The scale of the model
+    pub scale: f32,
+    pub height: f64,
+    pub width: f64,
+    pub speed: f64,
+    pub behavior: MobBehavior,
+    pub spawn: SpawnRule,
+}
+
+impl Mob {
+    /// Resolve and validate mob `name`
+    pub fn new(
+        name: String,
+        mob_type: &MobType,
+        blocks: &Registry<Block>,
+        models: &Registry<VoxelModel>,
+    ) -> Result<Self> {
+        let model = models
+            .get_id_by_name(&mob_type.model)
+            .and_then(|id| models.get_value_by_id(id))
+            .context(format!("unknown model {} for mob {}", mob_type.model, name))?;
+        anyhow::ensure!(
+            mob_type.height > 0.0 && mob_type.width > 0.0 && mob_type.width < 1.0,
+            "mob {} must have a positive height and a width between 0 and 1",
+            name
+        );
+        anyhow::ensure!(
+            mob_type.spawn.min_light <= mob_type.spawn.max_light,
+            "mob {} has min_light > max_light",
+            name
+        );
+        let spawn_blocks = mob_type
+            .spawn
+            .blocks
+            .iter()
+            .map(|block| {
+                blocks
+                    .get_id_by_name(block)
+                    .map(|id| id as BlockId)
+                    .context(format!("unknown block {} for mob {}", block, name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            scale: (mob_type.height / model.size_y as f64) as f32,
+            model: mob_type.model.clone(),
+            height: mob_type.height,
+            width: mob_type.width,
+            speed: mob_type.speed,
+            behavior: mob_type.behavior,
+            spawn: SpawnRule {
+                blocks: spawn_blocks,
+                min_light: mob_type.spawn.min_light,
+                max_light: mob_type.spawn.max_light,
+            },
+            name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{state::BlockState, BlockType};
+
+    fn registries() -> (Registry<Block>, Registry<VoxelModel>) {
+        let mut blocks = Registry::default();
+        for (id, name) in ["air", "grass"].iter().enumerate() {
+            let block = Block {
+                name: name.to_string(),
+                block_type: BlockType::Air,
+                fluid: None,
+                state: BlockState::default(),
+                base: id as BlockId,
+            };
+            blocks.register(name.to_string(), block).unwrap();
+        }
+        let mut models = Registry::default();
+        let model = VoxelModel {
+            size_x: 10,
+            size_y: 20,
+            size_z: 10,
+            voxels: Vec::new(),
+            full: Vec::new(),
+        };
+        models.register("pig".to_owned(), model).unwrap();
+        (blocks, models)
+    }
+
+    fn mob_type(model: &str, block: &str) -> MobType {
+        MobType {
+            model: model.to_owned(),
+            height: 0.9,
+            width: 0.6,
+            speed: 2.0,
+            behavior: MobBehavior::Wander,
+            spawn: SpawnConditions {
+                blocks: vec![block.to_owned()],
+                min_light: 8,
+                max_light: 15,
+            },
+        }
+    }
+
+    #[test]
+    fn test_mobs_are_resolved() {
+        let (blocks, models) = registries();
+        let mob = Mob::new(
+            "pig".to_owned(),
+            &mob_type("pig", "grass"),
+            &blocks,
+            &models,
+        )
+        .unwrap();
+        assert_eq!(mob.spawn.blocks, vec![1]);
+        assert!((mob.scale - 0.045).abs() < 1e-6);
+        assert!(mob.spawn.allows(1, 15));
+        assert!(!mob.spawn.allows(1, 7));
+        assert!(!mob.spawn.allows(0, 15));
+
+        assert!(Mob::new(
+            "pig".to_owned(),
+            &mob_type("cow", "grass"),
+            &blocks,
+            &models
+        )
+        .is_err());
+        assert!(Mob::new("pig".to_owned(), &mob_type("pig", "sand"), &blocks, &models).is_err());
+    }
+}
@@ -1,11 +1,16 @@
 use crate::{
+    block::entity::BlockEntity,
+    clock::WorldTime,
     data::Data,
+    entity::{EntityId, EntityModel},
+    health::{DamageSource, Health},
+    interaction::{BlockInteraction, InteractionError, InteractionId},
+    item::inventory::{Inventory, ItemStack, SlotRef},
     physics::simulation::ServerState,
     player::PlayerId,
     player::{PlayerInput, RenderDistance},
-    world::{Chunk, LightChunk},
+    world::{BlockPos, Chunk, LightChunk},
 };
-use nalgebra::Vector3;
 use std::sync::Arc;
 
 /// A message sent to the server by the client
@@ -15,12 +20,30 @@ pub enum ToServer {
     SetRenderDistance(RenderDistance),
     /// Update the player's input
     UpdateInput(PlayerInput),
-    /// Break a block (player pos, yaw, pitch)
-    BreakBlock(Vector3<f64>, f64, f64),
-    /// Select a block
-    SelectBlock(Vector3<f64>, f64, f64),
-    /// Place a block
-    PlaceBlock(Vector3<f64>, f64, f64),
+    /// Set the name of the player, displayed to the other players
+    SetPlayerName(String),
+    /// Start mining the pointed block. Creative players break it instantly.
+    StartMining(BlockInteraction),
+    /// Stop mining the block without breaking it
+    AbortMining,
+    /// Break the block that is being mined, if it was mined for long enough
+    FinishMining(BlockInteraction),
+    /// Select the item of the pointed block in the hotbar
+    PickBlock(BlockInteraction),
+    /// Select a slot of the hotbar
+    SelectHotbarSlot(usize),
+    /// Place the block of the selected hotbar slot against the pointed face
+    PlaceBlock(BlockInteraction),
+    /// Ask for the block entity at some position
+    RequestBlockEntity(BlockPos),
+    /// Move a stack between two slots of the player inventory or of the open container
+    MoveStack(SlotRef, SlotRef),
+    /// Close the open container
+    CloseContainer,
+    /// Craft the recipe that matches the crafting grid, and put the result in the player inventory
+    Craft,
+    /// Execute a command, for example `/time set noon`
+    Command(String),
     /// Tell the server to shutdown
     StopServer,
 }
@@ -37,4 +60,36 @@ pub enum ToClient {
     UpdatePhysics(ServerState),
     /// Set the id of a player
     CurrentId(PlayerId),
+    /// The name of some player
+    PlayerName(PlayerId, String),
+    /// Update the world time
+    UpdateTime(WorldTime),
+    /// The block entity at some position, if there is one
+    BlockEntity(BlockPos, Option<BlockEntity>),
+    /// The contents of the player inventory
+    PlayerInventory(Inventory),
+    /// The health of the player
+    UpdateHealth(Health),
+    /// The player died and respawned
+    Died(DamageSource),
+    /// Select a slot of the hotbar
+    SelectHotbarSlot(usize),
+    /// The maximum distance between the camera of the player and the blocks it interacts with
+    SetReach(f64),
+    /// The server accepted a block interaction of the player, and will send the modified chunks
+    InteractionAccepted(InteractionId),
+    /// The server rejected a block interaction of the player
+    InteractionRejected(InteractionId, InteractionError),
+    /// The contents of the crafting grid of the player, and the result of the recipe it matches
+    CraftingGrid(Inventory, Option<ItemStack>),
+    /// Open the container at some position, or update its contents if it is already open
+    OpenContainer(BlockPos, Inventory),
+    /// Close the open container, for example because it was broken
+    CloseContainer,
+    /// Feedback for a command sent by the player
+    CommandFeedback(String),
+    /// An entity spawned, or already existed when the player joined. Its physics components are part of the physics updates.
+    SpawnEntity(EntityId, EntityModel),
+    /// An entity was removed
+    DespawnEntity(EntityId),
 }
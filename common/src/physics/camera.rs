@@ -2,9 +2,9 @@
 //!
 //! A `Camera` defines how a player's entity reacts to that player's inputs.
 
-use super::BlockContainer;
+use super::{BlockContainer, GRAVITY_ACCELERATION};
 use crate::{debug::send_debug_info, physics::player::PhysicsPlayer, player::PlayerInput};
-use nalgebra::Vector3;
+use nalgebra::{Isometry3, Vector3};
 
 // Unit vector in the `angle` direction
 fn movement_direction(yaw: f64, angle: f64) -> Vector3<f64> {
@@ -86,6 +86,44 @@ trait PlayerCamera {
 
 pub struct FlyingCamera;
 
+// The velocity of a flying player according to its controls, ignoring the collisions
+fn flying_velocity(input: PlayerInput, acceleration: f64, max_speed: f64) -> Vector3<f64> {
+    // Calculate the intended acceleration based on controls.
+    let mut force = Vector3::zeros();
+    if input.key_move_forward {
+        force += movement_direction(input.yaw, 0.0);
+    }
+    if input.key_move_left {
+        force += movement_direction(input.yaw, 90.0);
+    }
+    if input.key_move_backward {
+        force += movement_direction(input.yaw, 180.0);
+    }
+    if input.key_move_right {
+        force += movement_direction(input.yaw, 270.0);
+    }
+    force *= acceleration;
+
+    if input.key_move_up {
+        force.y += max_speed;
+    }
+    if input.key_move_down {
+        force.y -= max_speed;
+    }
+
+    /*const STIFFNESS: f64 = 10.0;
+    const MASS: f64 = 1.0;
+    const DAMPENING: f64 = 0.2;
+    let spring_force = -STIFFNESS/MASS - (DAMPENING/MASS) * player.velocity;*/
+
+    let mut expected_movement = force;
+
+    if expected_movement.norm() > max_speed {
+        expected_movement *= max_speed / expected_movement.norm();
+    }
+    expected_movement
+}
+
 impl PlayerCamera for FlyingCamera {
     const ACCELERATION: f64 = 25.0;
     const MAX_SPEED: f64 = 30.0;
@@ -96,45 +134,30 @@ impl PlayerCamera for FlyingCamera {
         seconds_delta: f64,
         world: &BC,
     ) {
-        // We're flying, so reset Y velocity to zero.
-        player.velocity.y = 0.0;
-
-        // Calculate the intended acceleration based on controls.
-        let mut force = Vector3::zeros();
-        if input.key_move_forward {
-            force += movement_direction(input.yaw, 0.0);
-        }
-        if input.key_move_left {
-            force += movement_direction(input.yaw, 90.0);
-        }
-        if input.key_move_backward {
-            force += movement_direction(input.yaw, 180.0);
-        }
-        if input.key_move_right {
-            force += movement_direction(input.yaw, 270.0);
-        }
-        force *= Self::ACCELERATION;
-
-        if input.key_move_up {
-            force.y += Self::MAX_SPEED as f64;
-        }
-        if input.key_move_down {
-            force.y -= Self::MAX_SPEED as f64;
-        }
-
-        /*const STIFFNESS: f64 = 10.0;
-        const MASS: f64 = 1.0;
-        const DAMPENING: f64 = 0.2;
-        let spring_force = -STIFFNESS/MASS - (DAMPENING/MASS) * player.velocity;*/
+        let expected_movement = flying_velocity(input, Self::ACCELERATION, Self::MAX_SPEED);
+        player.velocity =
+            player.move_check_collision(world, expected_movement * seconds_delta) / seconds_delta;
+    }
+}
 
-        let mut expected_movement = force;
+/// Flying through the blocks
+pub struct SpectatorCamera;
 
-        if expected_movement.norm() > Self::MAX_SPEED {
-            expected_movement *= Self::MAX_SPEED / expected_movement.norm();
-        }
+impl PlayerCamera for SpectatorCamera {
+    const ACCELERATION: f64 = 25.0;
+    const MAX_SPEED: f64 = 30.0;
 
-        player.velocity =
-            player.move_check_collision(world, expected_movement * seconds_delta) / seconds_delta;
+    fn compute_movement<BC: BlockContainer>(
+        player: &mut PhysicsPlayer,
+        input: PlayerInput,
+        seconds_delta: f64,
+        _world: &BC,
+    ) {
+        player.velocity = flying_velocity(input, Self::ACCELERATION, Self::MAX_SPEED);
+        let movement = player.velocity * seconds_delta;
+        player.aabb = player
+            .aabb
+            .transform_by(&Isometry3::new(movement, Vector3::zeros()));
     }
 }
 
@@ -152,7 +175,6 @@ impl PlayerCamera for WalkingCamera {
     ) {
         // Not flying
         const JUMP_SPEED: f64 = 8.0;
-        const GRAVITY_ACCELERATION: f64 = 25.0;
         const HORIZONTAL_SPEED: f64 = 7.0;
         player.velocity.x = 0.0;
         player.velocity.z = 0.0;
@@ -170,7 +192,8 @@ impl PlayerCamera for WalkingCamera {
             horizontal_velocity += movement_direction(input.yaw, 270.0);
         }
         let horizontal_velocity = normalize_or_zero(horizontal_velocity) * HORIZONTAL_SPEED;
-        if player.is_on_ground(world) {
+        let on_ground = player.is_on_ground(world);
+        if on_ground {
             player.velocity.y = if input.key_move_up { JUMP_SPEED } else { 0.0 };
         } else {
             player.velocity.y -= GRAVITY_ACCELERATION * seconds_delta;
@@ -179,11 +202,83 @@ impl PlayerCamera for WalkingCamera {
             }
         };
         let expected_movement = (player.velocity + horizontal_velocity) * seconds_delta;
-        player.move_check_collision(world, expected_movement);
+        let movement = player.move_check_collision(world, expected_movement);
+        if !on_ground && expected_movement.y < 0.0 && movement.y - expected_movement.y > 1e-9 {
+            // The player landed, the server will apply the fall damage
+            player.landing_speed = player.landing_speed.max(-player.velocity.y);
+        }
     }
 }
 
-/// The default camera. It doesn't let you go inside blocks unless you are already inside blocks.
+pub struct SwimmingCamera;
+
+impl PlayerCamera for SwimmingCamera {
+    const ACCELERATION: f64 = 16.0;
+    const MAX_SPEED: f64 = 8.0;
+
+    fn compute_movement<BC: BlockContainer>(
+        player: &mut PhysicsPlayer,
+        input: PlayerInput,
+        seconds_delta: f64,
+        world: &BC,
+    ) {
+        // Reduced gravity because of the buoyancy
+        const FLUID_GRAVITY_ACCELERATION: f64 = 6.0;
+        // The drag slows the player down proportionally to its velocity
+        const DRAG: f64 = 4.0;
+        // Vertical speed when jumping out of the fluid
+        const EXIT_JUMP_SPEED: f64 = 8.0;
+
+        let mut direction = Vector3::zeros();
+        if input.key_move_forward {
+            direction += movement_direction(input.yaw, 0.0);
+        }
+        if input.key_move_left {
+            direction += movement_direction(input.yaw, 90.0);
+        }
+        if input.key_move_backward {
+            direction += movement_direction(input.yaw, 180.0);
+        }
+        if input.key_move_right {
+            direction += movement_direction(input.yaw, 270.0);
+        }
+        let mut acceleration = normalize_or_zero(direction) * Self::ACCELERATION;
+        if input.key_move_up {
+            acceleration.y += Self::ACCELERATION;
+        }
+        if input.key_move_down {
+            acceleration.y -= Self::ACCELERATION;
+        }
+        acceleration.y -= FLUID_GRAVITY_ACCELERATION;
+        acceleration -= player.velocity * DRAG;
+
+        player.velocity += acceleration * seconds_delta;
+        if player.velocity.norm() > Self::MAX_SPEED {
+            player.velocity *= Self::MAX_SPEED / player.velocity.norm();
+        }
+
+        let expected_movement = player.velocity * seconds_delta;
+        let movement = player.move_check_collision(world, expected_movement);
+        // Stop the movement along the axes where the player hit a block
+        let mut blocked_horizontally = false;
+        for i in 0..3 {
+            if (movement[i] - expected_movement[i]).abs() > 1e-9 {
+                player.velocity[i] = 0.0;
+                if i != 1 {
+                    blocked_horizontally = true;
+                }
+            }
+        }
+
+        // Jump out of the fluid when swimming up against a block at the surface
+        if input.key_move_up && blocked_horizontally && !player.is_head_in_fluid(world) {
+            player.velocity.y = EXIT_JUMP_SPEED;
+        }
+    }
+}
+
+/// The default camera. It doesn't let you go inside blocks unless you are already inside blocks or a spectator.
+/// The players can only fly if their game mode allows it.
 // TODO: use better integrator (RK4 ?)
 pub fn default_camera<BC: BlockContainer>(
     player: &mut PhysicsPlayer,
@@ -192,12 +287,16 @@ pub fn default_camera<BC: BlockContainer>(
     world: &BC,
 ) {
     // Compute the expected movement of the player, i.e. assuming there are no collisions.
-    if input.flying || player.intersect_world(world) {
+    let game_mode = player.game_mode;
+    if !game_mode.has_collisions() {
+        SpectatorCamera::compute_movement(player, input, seconds_delta, world);
+    } else if (input.flying && game_mode.can_fly()) || player.intersect_world(world) {
         FlyingCamera::compute_movement(player, input, seconds_delta, world);
+    } else if player.is_in_fluid(world) {
+        SwimmingCamera::compute_movement(player, input, seconds_delta, world);
     } else {
         WalkingCamera::compute_movement(player, input, seconds_delta, world);
     }
-    // TODO: add a noclip camera mode
     send_debug_info(
         "Physics",
         "ontheground",
@@ -210,3 +309,230 @@ pub fn default_camera<BC: BlockContainer>(
         format!("velocity: {:.2} {:.2} {:.2}", vx, vy, vz),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::test_world::TestWorld;
+    use crate::physics::AABB;
+    use crate::player::GameMode;
+    use nalgebra::Point3;
+
+    const SECONDS_DELTA: f64 = 1.0 / 60.0;
+
+    /// A pool of fluid from y = 0 to y = 9, with a solid floor
+    fn pool() -> TestWorld {
+        let mut world = TestWorld::default();
+        for x in -10..10 {
+            for z in -10..10 {
+                world.solid.insert((x, -1, z).into());
+                for y in 0..10 {
+                    world.fluid.insert((x, y, z).into());
+                }
+            }
+        }
+        world
+    }
+
+    /// A pool of fluid from y = 0 to y = 2 for z >= -1, next to a bank of solid blocks from y = 0 to y = 3 for z <= -2.
+    /// The bank is one block higher than the surface of the fluid.
+    fn pool_with_bank() -> TestWorld {
+        let mut world = TestWorld::default();
+        for x in -3..4 {
+            for z in -8..4 {
+                world.solid.insert((x, -1, z).into());
+                for y in 0..4 {
+                    if z <= -2 {
+                        world.solid.insert((x, y, z).into());
+                    } else if y < 3 {
+                        world.fluid.insert((x, y, z).into());
+                    }
+                }
+            }
+        }
+        world
+    }
+
+    /// A player whose feet are at the given position
+    fn player_at(x: f64, y: f64, z: f64) -> PhysicsPlayer {
+        PhysicsPlayer {
+            aabb: AABB::new(
+                Point3::new(x - 0.4, y, z - 0.4),
+                Point3::new(x + 0.4, y + 1.8, z + 0.4),
+            ),
+            velocity: Vector3::zeros(),
+            landing_speed: 0.0,
+            game_mode: GameMode::Survival,
+        }
+    }
+
+    fn walking_input() -> PlayerInput {
+        PlayerInput {
+            flying: false,
+            ..PlayerInput::default()
+        }
+    }
+
+    fn simulate(player: &mut PhysicsPlayer, input: PlayerInput, steps: usize, world: &TestWorld) {
+        for _ in 0..steps {
+            default_camera(player, input, SECONDS_DELTA, world);
+        }
+    }
+
+    #[test]
+    fn test_swimming_sinks_slowly() {
+        let world = pool();
+        let mut player = player_at(0.5, 5.0, 0.5);
+        simulate(&mut player, walking_input(), 30, &world);
+
+        assert!(player.is_in_fluid(&world));
+        assert!(player.velocity.y < 0.0);
+        // Falling in the air for half a second would be much faster
+        assert!(player.velocity.y > -2.0);
+        assert!(player.position().y > 4.0);
+    }
+
+    #[test]
+    fn test_swimming_up() {
+        let world = pool();
+        let mut player = player_at(0.5, 2.0, 0.5);
+        let input = PlayerInput {
+            key_move_up: true,
+            ..walking_input()
+        };
+        simulate(&mut player, input, 60, &world);
+
+        assert!(player.velocity.y > 0.0);
+        assert!(player.position().y > 3.5);
+    }
+
+    #[test]
+    fn test_swimming_drag_limits_speed() {
+        let world = pool();
+        let mut player = player_at(0.5, 2.0, 0.5);
+        let input = PlayerInput {
+            key_move_forward: true,
+            ..walking_input()
+        };
+        simulate(&mut player, input, 120, &world);
+
+        // Yaw 0 is towards -z
+        let horizontal_speed = Vector3::new(player.velocity.x, 0.0, player.velocity.z).norm();
+        assert!(player.velocity.z < 0.0);
+        assert!(horizontal_speed > 3.0);
+        assert!(horizontal_speed < 5.0);
+    }
+
+    #[test]
+    fn test_exit_jump() {
+        let world = pool_with_bank();
+        let mut player = player_at(0.5, 1.0, 0.5);
+        let input = PlayerInput {
+            key_move_forward: true,
+            key_move_up: true,
+            ..walking_input()
+        };
+        simulate(&mut player, input, 60, &world);
+        simulate(&mut player, walking_input(), 60, &world);
+
+        // The player is standing on the bank
+        assert!(!player.is_in_fluid(&world));
+        assert!(player.is_on_ground(&world));
+        assert!(player.position().y > 3.99);
+        assert!(player.aabb.mins.z < -2.0);
+    }
+
+    #[test]
+    fn test_landing_speed() {
+        let mut world = TestWorld::default();
+        for x in -2..3 {
+            for z in -2..3 {
+                world.solid.insert((x, -1, z).into());
+            }
+        }
+        let mut player = player_at(0.5, 10.0, 0.5);
+        simulate(&mut player, walking_input(), 120, &world);
+
+        assert!(player.is_on_ground(&world));
+        let fall_speed = (2.0 * GRAVITY_ACCELERATION * 10.0f64).sqrt();
+        assert!((player.landing_speed - fall_speed).abs() < 1.0);
+        // Landing in a fluid doesn't count
+        let world = pool();
+        let mut player = player_at(0.5, 15.0, 0.5);
+        simulate(&mut player, walking_input(), 120, &world);
+        assert!(player.landing_speed < 1e-9);
+    }
+
+    #[test]
+    fn test_game_modes() {
+        let mut world = TestWorld::default();
+        for x in -2..3 {
+            for z in -2..3 {
+                world.solid.insert((x, -1, z).into());
+            }
+        }
+        for y in 0..3 {
+            world.solid.insert((0, y, -1).into());
+        }
+        let flying_input = PlayerInput {
+            flying: true,
+            ..PlayerInput::default()
+        };
+        // Survival players can't fly
+        let mut player = player_at(0.5, 5.0, 0.5);
+        simulate(&mut player, flying_input, 120, &world);
+        assert!(player.is_on_ground(&world));
+        // Creative players can
+        let mut player = player_at(0.5, 5.0, 0.5);
+        player.game_mode = GameMode::Creative;
+        simulate(&mut player, flying_input, 120, &world);
+        assert!((player.position().y - 5.0).abs() < 1e-6);
+        // Spectators go through blocks
+        let forward = PlayerInput {
+            key_move_forward: true,
+            ..flying_input
+        };
+        player.game_mode = GameMode::Spectator;
+        player.aabb = player_at(0.5, 0.0, 0.5).aabb;
+        simulate(&mut player, forward, 30, &world);
+        assert!(player.position().z < -1.0);
+        assert!((player.position().y - 0.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_swimming_terminal_sink_speed() {
+        let world = pool();
+        let mut player = player_at(0.5, 8.0, 0.5);
+        simulate(&mut player, walking_input(), 180, &world);
+
+        // The drag balances the reduced gravity: 6 / 4 = 1.5 blocks per second
+        assert!(player.is_in_fluid(&world));
+        assert!((player.velocity.y + 1.5).abs() < 1e-3);
+        assert!(player.velocity.x.abs() < 1e-9 && player.velocity.z.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exit_jump_launches_out_of_fluid() {
+        let world = pool_with_bank();
+        let mut player = player_at(0.5, 1.0, 0.5);
+        let input = PlayerInput {
+            key_move_forward: true,
+            key_move_up: true,
+            ..walking_input()
+        };
+        let mut launched = false;
+        let mut left_fluid_upwards = false;
+        for _ in 0..60 {
+            default_camera(&mut player, input, SECONDS_DELTA, &world);
+            launched |= player.velocity.y > 7.0;
+            left_fluid_upwards |= !player.is_in_fluid(&world)
+                && player.position().y >= 3.0
+                && player.velocity.y > 0.0;
+        }
+
+        // Swimming up is much slower than the exit jump
+        assert!(launched);
+        // The surface of the fluid is at y = 3
+        assert!(left_fluid_upwards);
+    }
+}
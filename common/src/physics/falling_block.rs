@@ -0,0 +1,163 @@
+use nalgebra::{Point3, Vector3};
+
+use super::BlockContainer;
+use crate::block::BlockId;
+use crate::entity::{Entities, EntityId};
+use crate::world::BlockPos;
+
+/// Falling blocks are a bit smaller than a block so they don't rub against the blocks next to them
+const FALLING_BLOCK_SIDE: f64 = 0.98;
+/// Falling blocks that don't land after this many seconds are removed, for example if they fell out of the loaded chunks
+const MAX_FALL_DURATION: f64 = 60.0;
+
+/// A block that is falling because nothing supports it. It becomes a normal block again when it lands.
+/// The entities with this component also have a position, a velocity and a collider, so they fall like the other entities.
+#[derive(Debug, Clone)]
+pub struct FallingBlock {
+    /// The falling block
+    pub block: BlockId,
+    /// For how many seconds the block has been falling
+    pub fall_duration: f64,
+}
+
+/// Start the fall of a block from block position `pos`
+pub fn spawn_falling_block(entities: &mut Entities, block: BlockId, pos: BlockPos) -> EntityId {
+    let id = entities.spawn(Point3::new(
+        pos.px as f64 + 0.5,
+        pos.py as f64 + (1.0 - FALLING_BLOCK_SIDE) / 2.0,
+        pos.pz as f64 + 0.5,
+    ));
+    entities.velocities.insert(id, Vector3::zeros());
+    entities
+        .colliders
+        .insert(id, Vector3::repeat(FALLING_BLOCK_SIDE));
+    entities.falling_blocks.insert(
+        id,
+        FallingBlock {
+            block,
+            fall_duration: 0.0,
+        },
+    );
+    id
+}
+
+/// Remove the falling blocks that landed or that fell for too long.
+/// Return the blocks that landed, that must be placed in the world, with their position.
+/// The falling blocks must already have been moved with the other entities.
+pub fn step_falling_blocks<BC: BlockContainer>(
+    entities: &mut Entities,
+    seconds_delta: f64,
+    world: &BC,
+) -> Vec<(BlockPos, BlockId)> {
+    let mut landed_blocks = Vec::new();
+    let ids = entities
+        .falling_blocks
+        .iter()
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in ids {
+        let falling_block = entities.falling_blocks.get_mut(id).unwrap();
+        falling_block.fall_duration += seconds_delta;
+        let block = falling_block.block;
+        if falling_block.fall_duration > MAX_FALL_DURATION {
+            entities.despawn(id);
+        } else if entities.is_on_ground(id, world) {
+            let pos = BlockPos::from(entities.aabb(id).unwrap().center());
+            entities.despawn(id);
+            landed_blocks.push((pos, block));
+        }
+    }
+    landed_blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::test_world::TestWorld;
+
+    const SECONDS_DELTA: f64 = 1.0 / 60.0;
+
+    /// Let a block fall at `pos` until it lands, and return where and after how many steps it landed
+    fn fall(world: &TestWorld, pos: BlockPos) -> (BlockPos, usize) {
+        let mut entities = Entities::default();
+        spawn_falling_block(&mut entities, 1, pos);
+        for step in 0..600 {
+            entities.step(SECONDS_DELTA, world);
+            let landed_blocks = step_falling_blocks(&mut entities, SECONDS_DELTA, world);
+            if let Some(&(pos, _)) = landed_blocks.first() {
+                return (pos, step);
+            }
+            assert!(entities.ids().next().is_some(), "The block expired");
+        }
+        panic!("The block didn't land");
+    }
+
+    #[test]
+    fn test_block_lands_on_ground() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, 0).into());
+        let (landed_pos, _) = fall(&world, (0, 10, 0).into());
+        assert_eq!(landed_pos, (0, 1, 0).into());
+    }
+
+    #[test]
+    fn test_falling_is_deterministic() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, 0).into());
+        assert_eq!(
+            fall(&world, (0, 20, 0).into()),
+            fall(&world, (0, 20, 0).into())
+        );
+    }
+
+    #[test]
+    fn test_block_falls_through_holes() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, -5, 0).into());
+        world.solid.insert((1, 0, 0).into());
+        world.solid.insert((-1, 0, 0).into());
+        world.solid.insert((0, 0, 1).into());
+        world.solid.insert((0, 0, -1).into());
+        let (landed_pos, _) = fall(&world, (0, 3, 0).into());
+        assert_eq!(landed_pos, (0, -4, 0).into());
+    }
+
+    #[test]
+    fn test_simulation_returns_landed_blocks() {
+        use crate::physics::simulation::{Input, PhysicsState};
+        use std::time::Duration;
+
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, 0).into());
+        world.solid.insert((5, 0, 0).into());
+        let mut state = PhysicsState::default();
+        state.spawn_falling_block(1, (0, 10, 0).into());
+        state.spawn_falling_block(2, (5, 3, 0).into());
+        let mut landed_blocks = Vec::new();
+        for _ in 0..600 {
+            landed_blocks.extend(state.step_simulation(
+                &Input::default(),
+                Duration::from_secs_f64(SECONDS_DELTA),
+                &world,
+            ));
+        }
+        assert_eq!(
+            landed_blocks,
+            vec![((5, 1, 0).into(), 2), ((0, 1, 0).into(), 1)]
+        );
+        assert!(state.entities.falling_blocks.iter().next().is_none());
+        assert!(state.entities.ids().next().is_none());
+    }
+
+    #[test]
+    fn test_block_expires() {
+        let world = TestWorld::default();
+        let mut entities = Entities::default();
+        spawn_falling_block(&mut entities, 1, (0, 0, 0).into());
+        for _ in 0..(MAX_FALL_DURATION / SECONDS_DELTA) as usize + 10 {
+            entities.step(SECONDS_DELTA, &world);
+            assert!(step_falling_blocks(&mut entities, SECONDS_DELTA, &world).is_empty());
+        }
+        assert!(entities.ids().next().is_none());
+    }
+}
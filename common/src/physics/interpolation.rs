@@ -0,0 +1,258 @@
+//! Interpolation of the other players between the states sent by the server.
+//!
+//! The client predicts its own player by replaying its inputs, but it doesn't know the future inputs of the
+//! other players. Instead, they are displayed slightly in the past, between the two server snapshots that
+//! surround the render time. When the snapshots are late, the last movement of the players is extrapolated
+//! for a limited time, and then they stop until the next snapshot arrives.
+use crate::physics::player::PhysicsPlayer;
+use crate::player::PlayerId;
+use nalgebra::{Isometry3, Point3, Vector3};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// How long in the past the other players are displayed by default
+pub const DEFAULT_INTERPOLATION_DELAY: Duration = Duration::from_millis(100);
+/// How long the movement of the other players is extrapolated by default when the snapshots are late
+pub const DEFAULT_EXTRAPOLATION_LIMIT: Duration = Duration::from_millis(250);
+
+/// How the other players are displayed between the snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterpolationSettings {
+    /// How long in the past the other players are displayed. It should be longer than the time between two
+    /// snapshots, otherwise the players are extrapolated most of the time.
+    pub delay: Duration,
+    /// The maximum duration the movement of a player is extrapolated for
+    pub extrapolation_limit: Duration,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self {
+            delay: DEFAULT_INTERPOLATION_DELAY,
+            extrapolation_limit: DEFAULT_EXTRAPOLATION_LIMIT,
+        }
+    }
+}
+
+/// Another player as it is displayed: its physics and its yaw
+pub type RemotePlayer = (PhysicsPlayer, f64);
+
+/// The other players at some server time
+#[derive(Debug, Clone)]
+struct Snapshot {
+    time: Instant,
+    players: HashMap<PlayerId, RemotePlayer>,
+}
+
+/// The last snapshots of the other players received from the server
+#[derive(Debug, Clone)]
+pub struct SnapshotBuffer {
+    settings: InterpolationSettings,
+    /// The snapshots, from the oldest to the newest
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl SnapshotBuffer {
+    pub fn new(settings: InterpolationSettings) -> Self {
+        Self {
+            settings,
+            snapshots: VecDeque::new(),
+        }
+    }
+
+    /// Add the snapshot of the server at `time`. Snapshots that arrive out of order are ignored.
+    pub fn push(&mut self, time: Instant, players: HashMap<PlayerId, RemotePlayer>) {
+        if let Some(newest) = self.snapshots.back() {
+            if time <= newest.time {
+                return;
+            }
+        }
+        self.snapshots.push_back(Snapshot { time, players });
+    }
+
+    /// Return the other players as they must be displayed at `now`, and drop the snapshots that are not needed anymore.
+    /// A player is displayed from the time of the first snapshot it is in, until the time of the first snapshot it
+    /// is not in anymore.
+    pub fn sample(&mut self, now: Instant) -> HashMap<PlayerId, RemotePlayer> {
+        let render_time = match now.checked_sub(self.settings.delay) {
+            Some(render_time) => render_time,
+            None => return self.oldest_players(),
+        };
+        // Keep the last snapshot before the render time, and the one before it for the extrapolation
+        while self.snapshots.len() > 2 && self.snapshots[2].time <= render_time {
+            self.snapshots.pop_front();
+        }
+
+        let next = self.snapshots.iter().position(|s| s.time > render_time);
+        match next {
+            // No snapshot is old enough yet, wait at the oldest one
+            Some(0) => self.oldest_players(),
+            Some(i) => interpolate(&self.snapshots[i - 1], &self.snapshots[i], render_time),
+            None => match self.snapshots.len() {
+                0 | 1 => self.oldest_players(),
+                n => extrapolate(
+                    &self.snapshots[n - 2],
+                    &self.snapshots[n - 1],
+                    render_time,
+                    self.settings.extrapolation_limit,
+                ),
+            },
+        }
+    }
+
+    fn oldest_players(&self) -> HashMap<PlayerId, RemotePlayer> {
+        self.snapshots
+            .front()
+            .map(|snapshot| snapshot.players.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Display the players of `from` between `from` and `to`
+fn interpolate(from: &Snapshot, to: &Snapshot, time: Instant) -> HashMap<PlayerId, RemotePlayer> {
+    let t = (time - from.time).as_secs_f64() / (to.time - from.time).as_secs_f64();
+    from.players
+        .iter()
+        .map(|(&id, (player, yaw))| {
+            let remote_player = match to.players.get(&id) {
+                Some((next_player, next_yaw)) => {
+                    let center = player.aabb.center();
+                    let next_center = next_player.aabb.center();
+                    (
+                        move_to(next_player, center + (next_center - center) * t),
+                        interpolate_angle(*yaw, *next_yaw, t),
+                    )
+                }
+                // The player left before the next snapshot
+                None => (player.clone(), *yaw),
+            };
+            (id, remote_player)
+        })
+        .collect()
+}
+
+/// Continue the movement of the players between `previous` and `last` after `last`, for at most `limit`
+fn extrapolate(
+    previous: &Snapshot,
+    last: &Snapshot,
+    time: Instant,
+    limit: Duration,
+) -> HashMap<PlayerId, RemotePlayer> {
+    let elapsed = (time - last.time).min(limit).as_secs_f64();
+    let span = (last.time - previous.time).as_secs_f64();
+    last.players
+        .iter()
+        .map(|(&id, (player, yaw))| {
+            let center = player.aabb.center();
+            let velocity = previous
+                .players
+                .get(&id)
+                .map(|(previous_player, _)| (center - previous_player.aabb.center()) / span)
+                .unwrap_or_else(Vector3::zeros);
+            (id, (move_to(player, center + velocity * elapsed), *yaw))
+        })
+        .collect()
+}
+
+/// Return a copy of `player` with its aabb centered on `center`
+fn move_to(player: &PhysicsPlayer, center: Point3<f64>) -> PhysicsPlayer {
+    let delta = center - player.aabb.center();
+    PhysicsPlayer {
+        aabb: player
+            .aabb
+            .transform_by(&Isometry3::new(delta, Vector3::zeros())),
+        ..player.clone()
+    }
+}
+
+/// Interpolate between two angles in degrees, going the shortest way around
+fn interpolate_angle(from: f64, to: f64, t: f64) -> f64 {
+    let difference = (to - from + 180.0).rem_euclid(360.0) - 180.0;
+    from + difference * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: InterpolationSettings = InterpolationSettings {
+        delay: Duration::from_millis(100),
+        extrapolation_limit: Duration::from_millis(100),
+    };
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// A snapshot where each player `id` is at `(x, 0, 0)` with some yaw
+    fn players(players: &[(PlayerId, f64, f64)]) -> HashMap<PlayerId, RemotePlayer> {
+        players
+            .iter()
+            .map(|&(id, x, yaw)| {
+                let player = PhysicsPlayer::from_coords(Point3::new(x, 0.0, 0.0));
+                (id, (player, yaw))
+            })
+            .collect()
+    }
+
+    fn x(players: &HashMap<PlayerId, RemotePlayer>, id: PlayerId) -> f64 {
+        players[&id].0.position().x
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_players_move_smoothly_between_snapshots() {
+        let start = Instant::now();
+        let mut buffer = SnapshotBuffer::new(SETTINGS);
+        for i in 0..3 {
+            let yaw = 350.0 + 20.0 * i as f64;
+            buffer.push(start + ms(50 * i), players(&[(0, i as f64, yaw)]));
+        }
+        // Before the render time reaches the first snapshot
+        assert_close(x(&buffer.sample(start + ms(50)), 0), 0.0);
+        let sampled = buffer.sample(start + ms(125));
+        assert_close(x(&sampled, 0), 0.5);
+        // The yaw goes from 350 to 370 = 10 degrees through 360
+        assert_close(sampled[&0].1.rem_euclid(360.0), 0.0);
+        assert_close(x(&buffer.sample(start + ms(175)), 0), 1.5);
+        assert_close(x(&buffer.sample(start + ms(200)), 0), 2.0);
+    }
+
+    #[test]
+    fn test_late_snapshots_are_extrapolated_for_a_limited_time() {
+        let start = Instant::now();
+        let mut buffer = SnapshotBuffer::new(SETTINGS);
+        // Moving at 20 blocks per second
+        buffer.push(start, players(&[(0, 0.0, 0.0)]));
+        buffer.push(start + ms(50), players(&[(0, 1.0, 0.0)]));
+        assert_close(x(&buffer.sample(start + ms(170)), 0), 1.4);
+        assert_close(x(&buffer.sample(start + ms(250)), 0), 3.0);
+        assert_close(x(&buffer.sample(start + ms(1000)), 0), 3.0);
+        // The next snapshot brings the player back to its real position
+        buffer.push(start + ms(1000), players(&[(0, 2.0, 0.0)]));
+        assert_close(x(&buffer.sample(start + ms(1100)), 0), 2.0);
+        assert!(buffer.snapshots.len() <= 2);
+    }
+
+    #[test]
+    fn test_players_appear_and_disappear_at_the_time_of_the_snapshots() {
+        let start = Instant::now();
+        let mut buffer = SnapshotBuffer::new(SETTINGS);
+        buffer.push(start, players(&[(0, 0.0, 0.0)]));
+        buffer.push(start + ms(50), players(&[(1, 5.0, 0.0)]));
+        // Out of order snapshots are ignored
+        buffer.push(start + ms(25), players(&[(0, 9.0, 0.0), (1, 9.0, 0.0)]));
+
+        let sampled = buffer.sample(start + ms(125));
+        assert_close(x(&sampled, 0), 0.0);
+        assert!(!sampled.contains_key(&1));
+        let sampled = buffer.sample(start + ms(150));
+        assert!(!sampled.contains_key(&0));
+        assert_close(x(&sampled, 1), 5.0);
+        // A player that was only in one snapshot is not extrapolated
+        assert_close(x(&buffer.sample(start + ms(200)), 1), 5.0);
+    }
+}
@@ -0,0 +1,246 @@
+use nalgebra::{Point3, Vector3};
+use ncollide3d::bounding_volume::BoundingVolume;
+use std::collections::HashMap;
+
+use super::player::PhysicsPlayer;
+use super::BlockContainer;
+use crate::entity::{Entities, EntityId};
+use crate::item::inventory::{ItemStack, MAX_STACK_SIZE};
+use crate::player::PlayerId;
+
+/// Side of the aabb of an item
+const ITEM_SIDE: f64 = 0.25;
+/// Vertical speed of the items when they are dropped
+const SPAWN_SPEED: f64 = 4.0;
+/// How fast the items stop sliding when they are on the ground, in 1/s
+const GROUND_FRICTION: f64 = 10.0;
+/// Items can be picked up when they are this close to the aabb of a player
+const PICKUP_DISTANCE: f64 = 1.0;
+/// Items can't be picked up during this many seconds after they were dropped, so they can be seen falling
+const PICKUP_DELAY: f64 = 0.5;
+/// Maximum distance between the centers of two identical items for them to merge
+const MERGE_DISTANCE: f64 = 0.5;
+/// Items are removed after lying in the world for this many seconds
+pub const DESPAWN_DELAY: f64 = 300.0;
+
+/// A stack of items lying in the world. The entities with this component also have
+/// a position, a velocity and a collider, so they fall like the other entities.
+#[derive(Debug, Clone)]
+pub struct ItemEntity {
+    /// The items
+    pub stack: ItemStack,
+    /// For how many seconds the items have been in the world
+    pub age: f64,
+}
+
+impl ItemEntity {
+    /// Return true if `other` has the same items, and fits in the same stack
+    fn can_stack_with(&self, other: &ItemEntity) -> bool {
+        self.stack.item == other.stack.item
+            && self.stack.count + other.stack.count <= MAX_STACK_SIZE
+    }
+
+    /// Merge `other` into these items. The merged items despawn as late as the most recent of the two.
+    fn merge(&mut self, other: ItemEntity) {
+        self.stack.count += other.stack.count;
+        self.age = self.age.min(other.age);
+    }
+}
+
+/// Drop some items centered at `position`, with a small upward velocity
+pub fn spawn_item(entities: &mut Entities, stack: ItemStack, position: Point3<f64>) -> EntityId {
+    let id = entities.spawn(position - Vector3::new(0.0, ITEM_SIDE / 2.0, 0.0));
+    entities
+        .velocities
+        .insert(id, Vector3::new(0.0, SPAWN_SPEED, 0.0));
+    entities.colliders.insert(id, Vector3::repeat(ITEM_SIDE));
+    entities.items.insert(id, ItemEntity { stack, age: 0.0 });
+    id
+}
+
+/// Age the items, remove the old ones, and slow down the ones that lie on the ground.
+/// The items must already have been moved with the other entities.
+pub fn step_items<BC: BlockContainer>(entities: &mut Entities, seconds_delta: f64, world: &BC) {
+    let ids = entities.items.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    for id in ids {
+        let item = entities.items.get_mut(id).unwrap();
+        item.age += seconds_delta;
+        if item.age > DESPAWN_DELAY {
+            entities.despawn(id);
+        } else if entities.is_on_ground(id, world) {
+            if let Some(velocity) = entities.velocities.get_mut(id) {
+                let friction = (1.0 - GROUND_FRICTION * seconds_delta).max(0.0);
+                velocity.x *= friction;
+                velocity.z *= friction;
+            }
+        }
+    }
+}
+
+/// Merge the close identical items. The oldest items absorb the newer ones.
+pub fn merge_items(entities: &mut Entities) {
+    let ids = entities.items.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    for (i, &id) in ids.iter().enumerate() {
+        for &other_id in ids[i + 1..].iter() {
+            let can_merge = match (
+                entities.items.get(id),
+                entities.items.get(other_id),
+                entities.aabb(id),
+                entities.aabb(other_id),
+            ) {
+                (Some(item), Some(other), Some(aabb), Some(other_aabb)) => {
+                    item.can_stack_with(other)
+                        && (aabb.center() - other_aabb.center()).norm() <= MERGE_DISTANCE
+                }
+                _ => false,
+            };
+            if can_merge {
+                let other = entities.items.remove(other_id).unwrap();
+                entities.despawn(other_id);
+                entities.items.get_mut(id).unwrap().merge(other);
+            }
+        }
+    }
+}
+
+/// Give the items that are close to a player to the closest player. Spectators don't pick up items.
+/// `pick_up` adds the items to the inventory of the player and returns what didn't fit.
+pub fn pick_up_items(
+    entities: &mut Entities,
+    players: &HashMap<PlayerId, PhysicsPlayer>,
+    mut pick_up: impl FnMut(PlayerId, ItemStack) -> Option<ItemStack>,
+) {
+    let ids = entities.items.iter().map(|(id, _)| id).collect::<Vec<_>>();
+    for id in ids {
+        let (stack, aabb) = match (entities.items.get(id), entities.aabb(id)) {
+            (Some(item), Some(aabb)) if item.age >= PICKUP_DELAY => (item.stack, aabb),
+            _ => continue,
+        };
+        let closest_player = players
+            .iter()
+            .filter(|(_, player)| player.game_mode.interacts_with_world())
+            .filter(|(_, player)| player.aabb.loosened(PICKUP_DISTANCE).intersects(&aabb))
+            .map(|(&player_id, player)| (player_id, (player.aabb.center() - aabb.center()).norm()))
+            .min_by(|a, b| a.1.partial_cmp(&b.1).expect("Distances are not NaN"))
+            .map(|(player_id, _)| player_id);
+        if let Some(player) = closest_player {
+            match pick_up(player, stack) {
+                Some(leftover) => entities.items.get_mut(id).unwrap().stack = leftover,
+                None => {
+                    entities.despawn(id);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::simulation::{Input, PhysicsState};
+    use crate::physics::test_world::TestWorld;
+    use std::time::Duration;
+
+    const SECONDS_DELTA: f64 = 1.0 / 60.0;
+
+    fn stack(item: u32, count: u32) -> ItemStack {
+        ItemStack { item, count }
+    }
+
+    fn step(state: &mut PhysicsState, world: &TestWorld, steps: usize) {
+        for _ in 0..steps {
+            state.step_simulation(
+                &Input::default(),
+                Duration::from_secs_f64(SECONDS_DELTA),
+                world,
+            );
+        }
+    }
+
+    fn stacks(state: &PhysicsState) -> Vec<ItemStack> {
+        state
+            .entities
+            .items
+            .iter()
+            .map(|(_, item)| item.stack)
+            .collect()
+    }
+
+    #[test]
+    fn test_items_fall_on_the_ground() {
+        let world = TestWorld::floor(5);
+        let mut state = PhysicsState::default();
+        let item = state.spawn_item(stack(1, 1), Point3::new(0.5, 5.5, 0.5));
+        step(&mut state, &world, 300);
+        let position = state.entities.positions.get(item).unwrap();
+        assert!((1.0..1.01).contains(&position.y));
+        assert_eq!(state.entities.velocities.get(item), Some(&Vector3::zeros()));
+    }
+
+    #[test]
+    fn test_close_identical_items_merge() {
+        let world = TestWorld::floor(5);
+        let mut state = PhysicsState::default();
+        state.spawn_item(stack(1, 10), Point3::new(0.5, 1.5, 0.5));
+        state.spawn_item(stack(1, 20), Point3::new(0.7, 1.5, 0.5));
+        state.spawn_item(stack(2, 5), Point3::new(0.5, 1.5, 0.7));
+        state.spawn_item(stack(1, 60), Point3::new(0.5, 1.5, 0.3));
+        state.spawn_item(stack(1, 3), Point3::new(3.5, 1.5, 0.5));
+        step(&mut state, &world, 1);
+        let mut stacks = stacks(&state);
+        stacks.sort_by_key(|stack| (stack.item, stack.count));
+        assert_eq!(
+            stacks,
+            vec![stack(1, 3), stack(1, 30), stack(1, 60), stack(2, 5)]
+        );
+        // The merged items are removed with all their components
+        assert_eq!(state.entities.ids().count(), 4);
+    }
+
+    #[test]
+    fn test_items_despawn() {
+        let world = TestWorld::floor(5);
+        let mut state = PhysicsState::default();
+        state.spawn_item(stack(1, 1), Point3::new(0.5, 1.5, 0.5));
+        step(
+            &mut state,
+            &world,
+            (DESPAWN_DELAY / SECONDS_DELTA) as usize - 10,
+        );
+        assert_eq!(stacks(&state).len(), 1);
+        step(&mut state, &world, 20);
+        assert!(stacks(&state).is_empty());
+        assert_eq!(state.entities.ids().count(), 0);
+    }
+
+    #[test]
+    fn test_items_are_picked_up_by_close_players() {
+        let world = TestWorld::floor(5);
+        let mut state = PhysicsState::default();
+        state.spawn_item(stack(1, 50), Point3::new(0.5, 1.5, 0.5));
+        state.spawn_item(stack(2, 5), Point3::new(4.5, 1.5, 4.5));
+        let player = PhysicsPlayer::from_coords(Point3::new(1.0, 1.0, 1.0));
+        state.players.insert(PlayerId(1), player.clone());
+        // Too early
+        let mut picked_up = Vec::new();
+        state.pick_up_items(|_, stack| {
+            picked_up.push(stack);
+            None
+        });
+        assert!(picked_up.is_empty());
+        // Stepping the simulation removes the players without input
+        step(&mut state, &world, 60);
+        state.players.insert(PlayerId(1), player);
+        // The player only has room for 30 items
+        state.pick_up_items(|player, stack| {
+            assert_eq!(player, PlayerId(1));
+            picked_up.push(stack);
+            Some(ItemStack {
+                count: stack.count - 30,
+                ..stack
+            })
+        });
+        assert_eq!(picked_up, vec![stack(1, 50)]);
+        assert_eq!(stacks(&state), vec![stack(1, 20), stack(2, 5)]);
+    }
+}
@@ -1,12 +1,39 @@
+use crate::block::FluidState;
 use crate::world::BlockPos;
 pub use ncollide3d::bounding_volume::{BoundingVolume, AABB};
 
 pub mod camera;
+pub mod falling_block;
+pub mod interpolation;
+pub mod item_entity;
 pub mod player;
 pub mod simulation;
+#[cfg(any(test, feature = "test-world"))]
+pub mod test_world;
+
+/// Downward acceleration of the players, the entities, the items and the falling blocks, in blocks per second squared.
+/// The fall damage of the players is computed from it.
+pub const GRAVITY_ACCELERATION: f64 = 25.0;
+/// Maximum downward speed of the entities that fall, like the items and the falling blocks, in blocks per second
+pub const MAX_FALL_SPEED: f64 = 40.0;
 
 /// A "block container", i.e. either the client's World or the server's World.
 /// This trait allows the physics simulation to work transparently with both World structs.
 pub trait BlockContainer {
+    /// Return true if the block at `pos` stops the movement of the entities
     fn is_block_full(&self, pos: BlockPos) -> bool;
+    /// Return the boxes of the block at `pos` that stop the movement of the entities, in world coordinates
+    fn collision_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        if self.is_block_full(pos) {
+            vec![pos.aabb()]
+        } else {
+            Vec::new()
+        }
+    }
+    /// Return the boxes of the block at `pos` that can be pointed at, in world coordinates
+    fn selection_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        self.collision_boxes(pos)
+    }
+    /// Return the fluid state of the block at `pos`, if it's a fluid
+    fn get_fluid_state(&self, pos: BlockPos) -> Option<FluidState>;
 }
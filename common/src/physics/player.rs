@@ -2,6 +2,7 @@ use nalgebra::{Isometry3, Point3, Vector3};
 use ncollide3d::bounding_volume::AABB;
 
 use super::BlockContainer;
+use crate::player::GameMode;
 use crate::world::BlockPos;
 
 const PLAYER_SIDE: f64 = 0.8;
@@ -9,14 +10,20 @@ const PLAYER_HEIGHT: f64 = 1.8;
 const POSITION_OFFSET: [f64; 3] = [0.0, PLAYER_HEIGHT / 2.0, 0.0];
 const CAMERA_OFFSET: [f64; 3] = [0.0, 1.6, 0.0];
 
-fn aabb_intersects_world<BC: BlockContainer>(world: &BC, aabb: &AABB<f64>) -> bool {
+/// Return true if the two boxes overlap. Boxes that only touch don't overlap.
+pub(crate) fn aabbs_overlap(a: &AABB<f64>, b: &AABB<f64>) -> bool {
+    (0..3).all(|i| a.mins[i] < b.maxs[i] && b.mins[i] < a.maxs[i])
+}
+
+pub(crate) fn aabb_intersects_world<BC: BlockContainer>(world: &BC, aabb: &AABB<f64>) -> bool {
     let mins = aabb.mins.map(|c| c.floor() as i64);
     let maxs = aabb.maxs.map(|c| c.ceil() as i64);
 
     for i in mins.x..maxs.x {
         for j in mins.y..maxs.y {
             for k in mins.z..maxs.z {
-                if world.is_block_full((i, j, k).into()) {
+                let collision_boxes = world.collision_boxes((i, j, k).into());
+                if collision_boxes.iter().any(|b| aabbs_overlap(b, aabb)) {
                     return true;
                 }
             }
@@ -25,6 +32,110 @@ fn aabb_intersects_world<BC: BlockContainer>(world: &BC, aabb: &AABB<f64>) -> bo
     return false;
 }
 
+/// Intersect a ray with a box. Return the distance along the ray where it enters the box and the face (x/-x/y/-y/z/-z)
+/// it enters through. If the origin of the ray is inside the box, the distance is 0 and the face is the one the ray exits through.
+pub(crate) fn ray_aabb_intersection(
+    origin: &Point3<f64>,
+    dir: &Vector3<f64>,
+    aabb: &AABB<f64>,
+) -> Option<(f64, usize)> {
+    let mut t_enter = 0.0;
+    let mut t_exit = f64::INFINITY;
+    let mut enter_face = None;
+    let mut exit_face = 0;
+    for axis in 0..3 {
+        if dir[axis].abs() < 1e-9 {
+            if origin[axis] < aabb.mins[axis] || origin[axis] > aabb.maxs[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t_mins = (aabb.mins[axis] - origin[axis]) / dir[axis];
+        let t_maxs = (aabb.maxs[axis] - origin[axis]) / dir[axis];
+        // The faces are x, -x, y, -y, z, -z
+        let (t_near, near_face, t_far, far_face) = if t_mins < t_maxs {
+            (t_mins, 2 * axis + 1, t_maxs, 2 * axis)
+        } else {
+            (t_maxs, 2 * axis, t_mins, 2 * axis + 1)
+        };
+        if t_near > t_enter {
+            t_enter = t_near;
+            enter_face = Some(near_face);
+        }
+        if t_far < t_exit {
+            t_exit = t_far;
+            exit_face = far_face;
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+    }
+    Some((t_enter, enter_face.unwrap_or(exit_face)))
+}
+
+/// Try to move the box in the world and stop the movement if it goes trough a block
+/// Return the actual deplacement
+pub(crate) fn move_aabb_check_collision<BC: BlockContainer>(
+    aabb: &mut AABB<f64>,
+    world: &BC,
+    delta: Vector3<f64>,
+) -> Vector3<f64> {
+    if aabb_intersects_world(world, aabb) {
+        *aabb = aabb.transform_by(&Isometry3::new(delta, Vector3::zeros()));
+        return delta;
+    }
+
+    // How many blocks are we moving?
+    let step = delta.zip_map(&aabb.extents(), |d, s| (d.abs() / s).ceil() as u32);
+    let dd = delta.zip_map(&step, |d, s| d / (s as f64));
+
+    let old_pos = *aabb;
+
+    // Loop the X, Y, and Z dimension.
+    for r in 0..3 {
+        let mut dimension_delta = Vector3::zeros();
+        dimension_delta[r] = dd[r];
+        let mut new_pos = *aabb;
+
+        for _ in 0..step[r] {
+            let mut should_break = false;
+            new_pos = new_pos.transform_by(&Isometry3::new(dimension_delta, Vector3::zeros()));
+            if aabb_intersects_world(world, &new_pos) {
+                new_pos = new_pos.transform_by(&Isometry3::new(-dimension_delta, Vector3::zeros()));
+
+                let mut min_d = 0.0;
+                let mut max_d = dd[r].abs();
+
+                while max_d - min_d > 0.001 {
+                    // binary search the max delta
+                    let med = (min_d + max_d) / 2.0;
+                    let mut delta_d = Vector3::zeros();
+                    delta_d[r] = med * dd[r].signum();
+                    let pot_pos = new_pos.transform_by(&Isometry3::new(delta_d, Vector3::zeros()));
+                    if aabb_intersects_world(world, &pot_pos) {
+                        max_d = med;
+                    } else {
+                        min_d = med;
+                    }
+                }
+
+                let mut delta_d = Vector3::zeros();
+                delta_d[r] = dd[r].signum() * min_d / 2.0;
+                new_pos = new_pos.transform_by(&Isometry3::new(delta_d, Vector3::zeros()));
+                should_break = true
+            }
+
+            *aabb = new_pos;
+
+            if should_break {
+                break;
+            }
+        }
+    }
+
+    aabb.mins - old_pos.mins
+}
+
 /// The physics representation of a player
 #[derive(Debug, Clone)]
 pub struct PhysicsPlayer {
@@ -32,6 +143,10 @@ pub struct PhysicsPlayer {
     pub aabb: AABB<f64>,
     /// The current velocity of the player
     pub velocity: Vector3<f64>,
+    /// The highest vertical speed at which the player landed since the server last applied the fall damage
+    pub landing_speed: f64,
+    /// The game mode of the player, which decides whether it can fly and whether it collides with the blocks
+    pub game_mode: GameMode,
 }
 
 impl PhysicsPlayer {
@@ -42,6 +157,8 @@ impl PhysicsPlayer {
                 Vector3::new(PLAYER_SIDE, PLAYER_HEIGHT, PLAYER_SIDE),
             ),
             velocity: Vector3::zeros(),
+            landing_speed: 0.0,
+            game_mode: GameMode::default(),
         }
     }
 
@@ -52,64 +169,7 @@ impl PhysicsPlayer {
         world: &BC,
         delta: Vector3<f64>,
     ) -> Vector3<f64> {
-        if self.intersect_world(world) {
-            self.aabb = self
-                .aabb
-                .transform_by(&Isometry3::new(delta, Vector3::zeros()));
-            return delta;
-        }
-
-        // How many blocks are we moving?
-        let step = delta.zip_map(&self.aabb.extents(), |d, s| (d.abs() / s).ceil() as u32);
-        let dd = delta.zip_map(&step, |d, s| d / (s as f64));
-
-        let old_pos = self.aabb;
-
-        // Loop the X, Y, and Z dimension.
-        for r in 0..3 {
-            let mut dimension_delta = Vector3::zeros();
-            dimension_delta[r] = dd[r];
-            let mut new_pos = self.aabb;
-
-            for _ in 0..step[r] {
-                let mut should_break = false;
-                new_pos = new_pos.transform_by(&Isometry3::new(dimension_delta, Vector3::zeros()));
-                if aabb_intersects_world(world, &new_pos) {
-                    new_pos =
-                        new_pos.transform_by(&Isometry3::new(-dimension_delta, Vector3::zeros()));
-
-                    let mut min_d = 0.0;
-                    let mut max_d = dd[r].abs();
-
-                    while max_d - min_d > 0.001 {
-                        // binary search the max delta
-                        let med = (min_d + max_d) / 2.0;
-                        let mut delta_d = Vector3::zeros();
-                        delta_d[r] = med * dd[r].signum();
-                        let pot_pos =
-                            new_pos.transform_by(&Isometry3::new(delta_d, Vector3::zeros()));
-                        if aabb_intersects_world(world, &pot_pos) {
-                            max_d = med;
-                        } else {
-                            min_d = med;
-                        }
-                    }
-
-                    let mut delta_d = Vector3::zeros();
-                    delta_d[r] = dd[r].signum() * min_d / 2.0;
-                    new_pos = new_pos.transform_by(&Isometry3::new(delta_d, Vector3::zeros()));
-                    should_break = true
-                }
-
-                self.aabb = new_pos;
-
-                if should_break {
-                    break;
-                }
-            }
-        }
-
-        self.aabb.mins - old_pos.mins
+        move_aabb_check_collision(&mut self.aabb, world, delta)
     }
 
     /// Check if player is on ground in world.
@@ -122,6 +182,35 @@ impl PhysicsPlayer {
         !self.intersect_world(world) && would_intersect_down
     }
 
+    /// Check if the player is touching some fluid.
+    pub fn is_in_fluid<BC: BlockContainer>(&self, world: &BC) -> bool {
+        let mins = self.aabb.mins.map(|c| c.floor() as i64);
+        let maxs = self.aabb.maxs.map(|c| c.ceil() as i64);
+
+        for i in mins.x..maxs.x {
+            for j in mins.y..maxs.y {
+                for k in mins.z..maxs.z {
+                    if world.get_fluid_state((i, j, k).into()).is_some() {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Check if the camera of the player is inside some fluid.
+    pub fn is_head_in_fluid<BC: BlockContainer>(&self, world: &BC) -> bool {
+        world
+            .get_fluid_state(BlockPos::from(self.get_camera_position()))
+            .is_some()
+    }
+
+    /// Check if the camera of the player is inside a block.
+    pub fn is_head_in_block<BC: BlockContainer>(&self, world: &BC) -> bool {
+        world.is_block_full(BlockPos::from(self.get_camera_position()))
+    }
+
     /// Check if player is intersecting with the world.
     pub fn intersect_world<BC: BlockContainer>(&self, world: &BC) -> bool {
         return aabb_intersects_world(world, &self.aabb);
@@ -138,66 +227,60 @@ impl PhysicsPlayer {
     }
 
     /// Ray trace to find the pointed block. Return the position of the block and the face (x/-x/y/-y/z/-z)
-    // TODO: use block registry
     pub fn get_pointed_at<BC: BlockContainer>(
         &self,
         dir: Vector3<f64>,
-        mut max_dist: f64,
+        max_dist: f64,
         world: &BC,
     ) -> Option<(BlockPos, usize)> {
         let dir = dir.normalize();
-        let mut pos = self.get_camera_position();
-
-        // Check current block first
-        let was_inside = world.is_block_full(BlockPos::from(pos));
-        let dirs = [
-            Vector3::new(-1.0, 0.0, 0.0),
-            Vector3::new(1.0, 0.0, 0.0),
-            Vector3::new(0.0, -1.0, 0.0),
-            Vector3::new(0.0, 1.0, 0.0),
-            Vector3::new(0.0, 0.0, -1.0),
-            Vector3::new(0.0, 0.0, 1.0),
+        let origin = self.get_camera_position();
+
+        // Walk through the blocks along the ray, until one of their selection boxes is hit
+        let mut block = [
+            origin.x.floor() as i64,
+            origin.y.floor() as i64,
+            origin.z.floor() as i64,
         ];
-        loop {
-            let targets = [
-                pos.x.floor(),
-                pos.x.ceil(),
-                pos.y.floor(),
-                pos.y.ceil(),
-                pos.z.floor(),
-                pos.z.ceil(),
-            ];
-
-            let mut curr_min = 1e9;
-            let mut face = 0;
-
-            for i in 0..6 {
-                let effective_movement = dir.dot(&dirs[i]);
-                if effective_movement > 1e-6 {
-                    let dir_offset = (targets[i].abs() - pos.coords.dot(&dirs[i]).abs()).abs();
-                    let dist = dir_offset / effective_movement;
-                    if curr_min > dist {
-                        curr_min = dist;
-                        face = i;
-                    }
-                }
+        let mut step = [0; 3];
+        // Distance along the ray to the next block boundary, on each axis
+        let mut t_next = [f64::INFINITY; 3];
+        // Distance along the ray between two block boundaries, on each axis
+        let mut t_delta = [f64::INFINITY; 3];
+        for axis in 0..3 {
+            if dir[axis] > 1e-9 {
+                step[axis] = 1;
+                t_next[axis] = (block[axis] as f64 + 1.0 - origin[axis]) / dir[axis];
+                t_delta[axis] = 1.0 / dir[axis];
+            } else if dir[axis] < -1e-9 {
+                step[axis] = -1;
+                t_next[axis] = (block[axis] as f64 - origin[axis]) / dir[axis];
+                t_delta[axis] = -1.0 / dir[axis];
             }
+        }
 
-            if was_inside {
-                return Some((BlockPos::from(pos), face ^ 1));
+        loop {
+            let block_pos = BlockPos::from((block[0], block[1], block[2]));
+            let hit = world
+                .selection_boxes(block_pos)
+                .iter()
+                .filter_map(|aabb| ray_aabb_intersection(&origin, &dir, aabb))
+                .min_by(|(t1, _), (t2, _)| t1.partial_cmp(t2).unwrap());
+            if let Some((t, face)) = hit {
+                if t <= max_dist {
+                    return Some((block_pos, face));
+                }
             }
 
-            if curr_min > max_dist {
+            // Move to the next block
+            let axis = (0..3)
+                .min_by(|&i, &j| t_next[i].partial_cmp(&t_next[j]).unwrap())
+                .unwrap();
+            if t_next[axis] > max_dist {
                 return None;
-            } else {
-                curr_min += 1e-5;
-                max_dist -= curr_min;
-                pos += curr_min * dir;
-                let block_pos = BlockPos::from(pos);
-                if world.is_block_full(block_pos) {
-                    return Some((block_pos, face));
-                }
             }
+            block[axis] += step[axis];
+            t_next[axis] += t_delta[axis];
         }
     }
 }
@@ -210,6 +293,58 @@ impl Default for PhysicsPlayer {
                 Vector3::new(PLAYER_SIDE / 2.0, PLAYER_HEIGHT / 2.0, PLAYER_SIDE / 2.0),
             ),
             velocity: Vector3::zeros(),
+            landing_speed: 0.0,
+            game_mode: GameMode::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::test_world::TestWorld;
+
+    /// A player whose camera is at the center of block (0, 0, 0)
+    fn player() -> PhysicsPlayer {
+        PhysicsPlayer::from_coords(Point3::new(0.5, 0.5, 0.5) - Vector3::from(CAMERA_OFFSET))
+    }
+
+    #[test]
+    fn test_intersects_partial_blocks() {
+        let mut world = TestWorld::default();
+        world.slabs.insert((0, 0, 0).into());
+        let above_slab = AABB::new(Point3::new(0.2, 0.5, 0.2), Point3::new(0.8, 2.0, 0.8));
+        assert!(!aabb_intersects_world(&world, &above_slab));
+        let in_slab = AABB::new(Point3::new(0.2, 0.4, 0.2), Point3::new(0.8, 2.0, 0.8));
+        assert!(aabb_intersects_world(&world, &in_slab));
+    }
+
+    #[test]
+    fn test_points_at_full_block() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, -3).into());
+        let pointed_at = player().get_pointed_at(Vector3::new(0.0, 0.0, -1.0), 10.0, &world);
+        assert_eq!(pointed_at, Some(((0, 0, -3).into(), 4)));
+    }
+
+    #[test]
+    fn test_points_at_slab() {
+        let mut world = TestWorld::default();
+        world.slabs.insert((0, -1, 0).into());
+        let down = Vector3::new(0.0, -1.0, 0.0);
+        // The top of the slab is 1 block away from the camera
+        assert_eq!(
+            player().get_pointed_at(down, 1.1, &world),
+            Some(((0, -1, 0).into(), 2))
+        );
+        assert_eq!(player().get_pointed_at(down, 0.9, &world), None);
+    }
+
+    #[test]
+    fn test_points_at_block_around_camera() {
+        let mut world = TestWorld::default();
+        world.solid.insert((0, 0, 0).into());
+        let pointed_at = player().get_pointed_at(Vector3::new(1.0, 0.0, 0.0), 10.0, &world);
+        assert_eq!(pointed_at, Some(((0, 0, 0).into(), 0)));
+    }
+}
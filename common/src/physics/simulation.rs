@@ -1,10 +1,17 @@
 use crate::{
+    block::BlockId,
+    entity::{Entities, EntityId},
+    item::inventory::ItemStack,
     physics::camera::default_camera,
+    physics::falling_block::{self, step_falling_blocks},
+    physics::interpolation::{InterpolationSettings, RemotePlayer, SnapshotBuffer},
+    physics::item_entity::{self, merge_items, step_items},
     physics::player::PhysicsPlayer,
-    physics::BlockContainer,
-    player::{PlayerId, PlayerInput},
+    physics::{BlockContainer, AABB},
+    player::{GameMode, PlayerId, PlayerInput},
+    world::BlockPos,
 };
-use nalgebra::Point3;
+use nalgebra::{Point3, Vector3};
 use std::{
     collections::HashMap,
     time::{Duration, Instant},
@@ -20,12 +27,20 @@ pub struct Input {
 #[derive(Debug, Clone, Default)]
 pub struct PhysicsState {
     pub players: HashMap<PlayerId, PhysicsPlayer>,
+    /// The entities and their physics components, including the falling blocks and the items
+    pub entities: Entities,
 }
 
 impl PhysicsState {
     /// Step the full physics simulation.
-    /// For now, it just moves all connected players.
-    pub fn step_simulation<BC: BlockContainer>(&mut self, input: &Input, dt: Duration, world: &BC) {
+    /// It moves all connected players and the entities.
+    /// Return the blocks that landed and must be placed in the world.
+    pub fn step_simulation<BC: BlockContainer>(
+        &mut self,
+        input: &Input,
+        dt: Duration,
+        world: &BC,
+    ) -> Vec<(BlockPos, BlockId)> {
         let seconds_delta = dt.as_secs_f64();
         for (&id, input) in input.player_inputs.iter() {
             default_camera(self.get_player_mut(id), *input, seconds_delta, world);
@@ -33,12 +48,44 @@ impl PhysicsState {
         // Remove players that don't exist anymore
         self.players
             .retain(|id, _| input.player_inputs.contains_key(id));
+
+        self.entities.step(seconds_delta, world);
+        let landed_blocks = step_falling_blocks(&mut self.entities, seconds_delta, world);
+        step_items(&mut self.entities, seconds_delta, world);
+        merge_items(&mut self.entities);
+        landed_blocks
+    }
+
+    /// Start simulating a falling block
+    pub fn spawn_falling_block(&mut self, block: BlockId, pos: BlockPos) -> EntityId {
+        falling_block::spawn_falling_block(&mut self.entities, block, pos)
+    }
+
+    /// Drop some items centered at `position`
+    pub fn spawn_item(&mut self, stack: ItemStack, position: Point3<f64>) -> EntityId {
+        item_entity::spawn_item(&mut self.entities, stack, position)
+    }
+
+    /// Give the items that are close to a player to the closest player. Spectators don't pick up items.
+    /// `pick_up` adds the items to the inventory of the player and returns what didn't fit.
+    pub fn pick_up_items(&mut self, pick_up: impl FnMut(PlayerId, ItemStack) -> Option<ItemStack>) {
+        item_entity::pick_up_items(&mut self.entities, &self.players, pick_up);
     }
 
     pub fn get_player(&mut self, id: PlayerId) -> &PhysicsPlayer {
         self.players.entry(id).or_insert(Default::default())
     }
 
+    /// Return the aabbs of the entities with a collider, like the falling blocks,
+    /// that blocks can't be placed into. The items are left out: they are small
+    /// and lie on the ground, so blocks can be placed over them.
+    pub fn solid_entity_aabbs(&self) -> impl Iterator<Item = AABB<f64>> + '_ {
+        self.entities
+            .ids()
+            .filter(move |&id| self.entities.items.get(id).is_none())
+            .filter_map(move |id| self.entities.aabb(id))
+    }
+
     pub fn get_player_mut(&mut self, id: PlayerId) -> &mut PhysicsPlayer {
         self.players.entry(id).or_insert(Default::default())
     }
@@ -52,7 +99,8 @@ pub struct ServerState {
     pub input: Input,
 }
 
-/// The client's physics simulation
+/// The client's physics simulation.
+/// The client player is predicted from its inputs, and the other players are interpolated between the server states.
 pub struct ClientPhysicsSimulation {
     /// Previous client inputs
     client_inputs: Vec<(Instant, PlayerInput)>,
@@ -64,22 +112,46 @@ pub struct ClientPhysicsSimulation {
     needs_recomputing: bool,
     /// Id of the current player
     player_id: PlayerId,
+    /// The last states of the other players received from the server
+    remote_snapshots: SnapshotBuffer,
+    /// The other players, as they are displayed
+    remote_players: HashMap<PlayerId, RemotePlayer>,
 }
 
 impl ClientPhysicsSimulation {
     /// Create a new simulation from some `ServerState` and the client's id
-    pub fn new(server_state: ServerState, player_id: PlayerId) -> Self {
+    pub fn new(
+        server_state: ServerState,
+        player_id: PlayerId,
+        interpolation: InterpolationSettings,
+    ) -> Self {
         Self {
             client_inputs: Vec::new(),
             last_server_state: server_state.clone(),
             current_state: server_state.physics_state,
             needs_recomputing: false,
             player_id,
+            remote_snapshots: SnapshotBuffer::new(interpolation),
+            remote_players: HashMap::new(),
         }
     }
 
     /// Process a server update
     pub fn receive_server_update(&mut self, state: ServerState) {
+        let player_id = self.player_id;
+        let inputs = &state.input.player_inputs;
+        let remote_players = state
+            .physics_state
+            .players
+            .iter()
+            .filter(|&(&id, _)| id != player_id)
+            .map(|(&id, player)| {
+                let yaw = inputs.get(&id).map_or(0.0, |input| input.yaw);
+                (id, (player.clone(), yaw))
+            })
+            .collect();
+        self.remote_snapshots
+            .push(state.server_time, remote_players);
         // Save state
         self.last_server_state = state;
         // Drop inputs anterior to this server state
@@ -99,6 +171,23 @@ impl ClientPhysicsSimulation {
             .get_camera_position()
     }
 
+    /// Get the other players with their yaw, interpolated between the last server states
+    pub fn get_other_players(&self) -> impl Iterator<Item = (PlayerId, &PhysicsPlayer, f64)> {
+        self.remote_players
+            .iter()
+            .map(|(&id, (player, yaw))| (id, player, *yaw))
+    }
+
+    /// Get the entities, including the falling blocks and the items
+    pub fn get_entities(&self) -> &Entities {
+        &self.current_state.entities
+    }
+
+    /// Get the aabbs that blocks can't be placed into, apart from the players
+    pub fn get_solid_entity_aabbs(&self) -> impl Iterator<Item = AABB<f64>> + '_ {
+        self.current_state.solid_entity_aabbs()
+    }
+
     /// Get the client player
     pub fn get_player(&mut self) -> &PhysicsPlayer {
         self.current_state.get_player(self.player_id)
@@ -145,12 +234,14 @@ impl ClientPhysicsSimulation {
             .player_inputs
             .insert(self.player_id, input);
 
-        // Step local simulation
+        // Step local simulation. The blocks that land are placed by the server.
         self.current_state.step_simulation(
             &self.last_server_state.input,
             time - previous_instant,
             world,
         );
+
+        self.remote_players = self.remote_snapshots.sample(time);
     }
 }
 
@@ -185,14 +276,80 @@ impl ServerPhysicsSimulation {
         self.server_state.input.player_inputs.remove(&player_id);
     }
 
-    /// Step the simulation according to the current input and time
-    pub fn step_simulation<BC: BlockContainer>(&mut self, time: Instant, world: &BC) {
-        self.server_state.physics_state.step_simulation(
+    /// Move a player back to the world spawn, without any velocity
+    pub fn respawn_player(&mut self, player_id: PlayerId) {
+        let player = self.server_state.physics_state.get_player_mut(player_id);
+        *player = PhysicsPlayer {
+            game_mode: player.game_mode,
+            ..PhysicsPlayer::default()
+        };
+    }
+
+    /// Change the game mode of a player
+    pub fn set_game_mode(&mut self, player_id: PlayerId, game_mode: GameMode) {
+        self.server_state
+            .physics_state
+            .get_player_mut(player_id)
+            .game_mode = game_mode;
+    }
+
+    /// Return the speed at which a player landed since the last call, or 0 if it didn't land
+    pub fn take_landing_speed(&mut self, player_id: PlayerId) -> f64 {
+        let player = self.server_state.physics_state.get_player_mut(player_id);
+        std::mem::replace(&mut player.landing_speed, 0.0)
+    }
+
+    /// Start simulating a falling block
+    pub fn spawn_falling_block(&mut self, block: BlockId, pos: BlockPos) {
+        self.server_state
+            .physics_state
+            .spawn_falling_block(block, pos);
+    }
+
+    /// Drop some items centered at `position`
+    pub fn spawn_item(&mut self, stack: ItemStack, position: Point3<f64>) {
+        self.server_state.physics_state.spawn_item(stack, position);
+    }
+
+    /// Give the items that are close to a player to the closest player.
+    /// `pick_up` adds the items to the inventory of the player and returns what didn't fit.
+    pub fn pick_up_items(&mut self, pick_up: impl FnMut(PlayerId, ItemStack) -> Option<ItemStack>) {
+        self.server_state.physics_state.pick_up_items(pick_up);
+    }
+
+    /// Spawn an entity at `position` that falls and collides with the world, with an aabb of size `size`
+    pub fn spawn_entity(&mut self, position: Point3<f64>, size: Vector3<f64>) -> EntityId {
+        let entities = &mut self.server_state.physics_state.entities;
+        let id = entities.spawn(position);
+        entities.velocities.insert(id, Vector3::zeros());
+        entities.colliders.insert(id, size);
+        id
+    }
+
+    /// Remove an entity from the simulation
+    pub fn despawn_entity(&mut self, id: EntityId) -> bool {
+        self.server_state.physics_state.entities.despawn(id)
+    }
+
+    /// Get the entities of the simulation, to move them or change their components
+    pub fn get_entities_mut(&mut self) -> &mut Entities {
+        &mut self.server_state.physics_state.entities
+    }
+
+    /// Step the simulation according to the current input and time.
+    /// Return the blocks that landed and must be placed in the world.
+    pub fn step_simulation<BC: BlockContainer>(
+        &mut self,
+        time: Instant,
+        world: &BC,
+    ) -> Vec<(BlockPos, BlockId)> {
+        let landed_blocks = self.server_state.physics_state.step_simulation(
             &self.server_state.input,
             time - self.server_state.server_time,
             world,
         );
         self.server_state.server_time = time;
+        landed_blocks
     }
 
     /// Get a reference to the current state of the simulation
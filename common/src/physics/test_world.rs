@@ -0,0 +1,59 @@
+//! A small world to test the code that moves things around in the world
+use super::{BlockContainer, AABB};
+use crate::block::FluidState;
+use crate::world::BlockPos;
+use nalgebra::Vector3;
+use std::collections::HashSet;
+
+/// A world made of full blocks, bottom slabs and fluid sources. The other blocks are air.
+#[derive(Debug, Clone, Default)]
+pub struct TestWorld {
+    pub solid: HashSet<BlockPos>,
+    pub slabs: HashSet<BlockPos>,
+    pub fluid: HashSet<BlockPos>,
+}
+
+impl TestWorld {
+    /// A flat floor of full blocks at y = 0, from `-size` to `size` in x and z
+    pub fn floor(size: i64) -> Self {
+        let mut world = Self::default();
+        for x in -size..=size {
+            for z in -size..=size {
+                world.solid.insert((x, 0, z).into());
+            }
+        }
+        world
+    }
+}
+
+impl BlockContainer for TestWorld {
+    fn is_block_full(&self, pos: BlockPos) -> bool {
+        self.solid.contains(&pos)
+    }
+
+    fn collision_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        if self.solid.contains(&pos) {
+            vec![pos.aabb()]
+        } else if self.slabs.contains(&pos) {
+            let aabb = pos.aabb();
+            vec![AABB::new(
+                aabb.mins,
+                aabb.maxs - Vector3::new(0.0, 0.5, 0.0),
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn get_fluid_state(&self, pos: BlockPos) -> Option<FluidState> {
+        if self.fluid.contains(&pos) {
+            Some(FluidState {
+                source: 1,
+                level: 0,
+                falling: false,
+            })
+        } else {
+            None
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use crate::world::ChunkPos;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// The input of a player
 #[derive(Debug, Clone, Copy)]
@@ -31,6 +31,55 @@ impl Default for PlayerInput {
     }
 }
 
+/// What a player is allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Walk, mine blocks, use items and take damage
+    Survival,
+    /// Fly, break blocks instantly and use items without consuming them
+    Creative,
+    /// Fly through blocks without interacting with the world
+    Spectator,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Survival
+    }
+}
+
+impl GameMode {
+    /// Return true if the player can fly when it wants to
+    pub fn can_fly(self) -> bool {
+        self != GameMode::Survival
+    }
+
+    /// Return true if the player collides with the blocks
+    pub fn has_collisions(self) -> bool {
+        self != GameMode::Spectator
+    }
+
+    /// Return true if the player takes damage
+    pub fn takes_damage(self) -> bool {
+        self == GameMode::Survival
+    }
+
+    /// Return true if placing blocks consumes the items of the player
+    pub fn consumes_items(self) -> bool {
+        self == GameMode::Survival
+    }
+
+    /// Return true if the player breaks blocks instantly instead of mining them
+    pub fn breaks_blocks_instantly(self) -> bool {
+        self == GameMode::Creative
+    }
+
+    /// Return true if the player can break and place blocks, and pick up items
+    pub fn interacts_with_world(self) -> bool {
+        self != GameMode::Spectator
+    }
+}
+
 /// Some unique player id.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct PlayerId(pub(crate) u16);
@@ -0,0 +1,37 @@
+//! A small deterministic random number generator
+/// A xorshift64* random number generator. It is fast and deterministic, but not suitable for cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a new generator from a seed
+    pub fn new(seed: u64) -> Self {
+        // The state must never be 0, and similar seeds should give different sequences
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+        if state == 0 {
+            state = 1;
+        }
+        Self { state }
+    }
+
+    /// Generate a random `u64`
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Generate a random integer in `[0, n)`
+    pub fn gen_range(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "empty range");
+        self.next_u64() % n
+    }
+
+    /// Generate a random `f64` in `[0, 1)`
+    pub fn gen_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -1,7 +1,11 @@
 //! Generic worker, allowing a computation to be performed in a separate thread
 use crate::{debug::send_worker_perf, time::AverageTimeCounter};
 use crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
-use std::{marker::PhantomData, time::Instant};
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
 
 /// A type that takes inputs of type `Input` produces outputs of type `Output`.
 pub trait WorkerState<Input, Output> {
@@ -18,7 +22,10 @@ pub struct Worker<
     Output: Send + 'static,
     State: WorkerState<Input, Output> + Send + 'static,
 > {
-    to_worker: Sender<Input>,
+    /// The input queue of each thread
+    to_workers: Vec<Sender<Input>>,
+    /// The thread that receives the next input
+    next_worker: AtomicUsize,
     from_worker: Receiver<Output>,
     _phantom: PhantomData<State>,
 }
@@ -31,51 +38,89 @@ impl<
 {
     /// Start a new worker with the given state using the provided channel size. The name is used for debug printing.
     pub fn new(state: State, channel_size: usize, name: String) -> Self {
-        let (in_sender, in_receiver) = bounded::<Input>(channel_size);
-        let (out_sender, out_receiver) = bounded::<Output>(channel_size);
+        Self::new_pool(vec![state], channel_size, name)
+    }
+
+    /// Start a pool of worker threads, one for each of the given states, sharing the same output channel.
+    /// Every thread owns its state, so scratch buffers are never shared, and its input queue of `channel_size`.
+    /// The inputs are spread over the threads in turn. The outputs are not guaranteed to be in order.
+    ///
+    /// # Panics
+    /// Will panic if `states` is empty.
+    pub fn new_pool(states: Vec<State>, channel_size: usize, name: String) -> Self {
+        assert!(
+            !states.is_empty(),
+            "cannot start a worker pool without threads"
+        );
+        let thread_count = states.len();
+        let (out_sender, out_receiver) = bounded::<Output>(channel_size * thread_count);
 
-        std::thread::spawn(move || {
-            // TODO: debug timing
-            let mut state = state;
-            let mut timing = AverageTimeCounter::new();
-            while let Ok(input) = in_receiver.recv() {
-                // Compute
-                let t1 = Instant::now();
-                let output = state.compute(input);
-                let t2 = Instant::now();
-                timing.add_time(t2 - t1);
+        let mut to_workers = Vec::with_capacity(thread_count);
+        for (thread_index, state) in states.into_iter().enumerate() {
+            let (in_sender, in_receiver) = bounded::<Input>(channel_size);
+            to_workers.push(in_sender);
+            let out_sender = out_sender.clone();
+            let thread_name = if thread_count == 1 {
+                name.clone()
+            } else {
+                format!("{} #{}", name, thread_index)
+            };
 
-                // Send debug info
-                send_worker_perf(
-                    "Workers",
-                    &name,
-                    &name,
-                    timing.average_time_micros() as f32,
-                    timing.average_iter_per_sec(),
-                    0,
-                );
+            std::thread::spawn(move || {
+                let mut state = state;
+                let mut timing = AverageTimeCounter::new();
+                while let Ok(input) = in_receiver.recv() {
+                    // Compute
+                    let t1 = Instant::now();
+                    let output = state.compute(input);
+                    let t2 = Instant::now();
+                    timing.add_time(t2 - t1);
 
-                // Send result
-                match out_sender.send(output) {
-                    Ok(()) => (),
-                    Err(_) => break,
+                    // Send debug info
+                    send_worker_perf(
+                        "Workers",
+                        &thread_name,
+                        &thread_name,
+                        timing.average_time_micros() as f32,
+                        timing.average_iter_per_sec(),
+                        in_receiver.len(),
+                    );
+
+                    // Send result
+                    match out_sender.send(output) {
+                        Ok(()) => (),
+                        Err(_) => break,
+                    }
                 }
-            }
-        });
+            });
+        }
 
         Self {
-            to_worker: in_sender,
+            to_workers,
+            next_worker: AtomicUsize::new(0),
             from_worker: out_receiver,
             _phantom: PhantomData,
         }
     }
 
-    /// Try to enqueue a new input in the worker queue. Doesn't block. Will return the input if the queue is full.
-    pub fn enqueue(&self, input: Input) -> Result<(), Input> {
-        self.to_worker.try_send(input).map_err(|e| match e {
-            TrySendError::Full(input) => input,
-            TrySendError::Disconnected(_) => unreachable!("Worker channel disconnected"),
-        })
+    /// Try to enqueue a new input in the queue of the next thread that has room for it. Doesn't block.
+    /// Will return the input if the queues of all the threads are full.
+    pub fn enqueue(&self, mut input: Input) -> Result<(), Input> {
+        let thread_count = self.to_workers.len();
+        let first = self.next_worker.load(Ordering::Relaxed);
+        for i in 0..thread_count {
+            let index = (first + i) % thread_count;
+            match self.to_workers[index].try_send(input) {
+                Ok(()) => {
+                    self.next_worker
+                        .store((index + 1) % thread_count, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(TrySendError::Full(rejected)) => input = rejected,
+                Err(TrySendError::Disconnected(_)) => unreachable!("Worker channel disconnected"),
+            }
+        }
+        Err(input)
     }
 
     /// Try to get a new output from the worker. Doesn't block. Will return None if there is no available output.
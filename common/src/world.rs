@@ -3,9 +3,11 @@ use crate::{
     registry::Registry,
 };
 use nalgebra::Point3;
+use ncollide3d::bounding_volume::AABB;
+use serde::{Deserialize, Serialize};
 
 /// The position of a block in the world.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct BlockPos {
     pub px: i64,
     pub py: i64,
@@ -22,6 +24,35 @@ impl BlockPos {
         }
     }
 
+    /// Offset the current block position by some amount of blocks
+    pub fn offset(self, dx: i64, dy: i64, dz: i64) -> Self {
+        Self {
+            px: self.px + dx,
+            py: self.py + dy,
+            pz: self.pz + dz,
+        }
+    }
+
+    /// Return the position of the block next to some face of this block (x/-x/y/-y/z/-z)
+    pub fn adjacent(self, face: usize) -> Self {
+        const FACE_OFFSETS: [[i64; 3]; 6] = [
+            [1, 0, 0],
+            [-1, 0, 0],
+            [0, 1, 0],
+            [0, -1, 0],
+            [0, 0, 1],
+            [0, 0, -1],
+        ];
+        let [dx, dy, dz] = FACE_OFFSETS[face];
+        self.offset(dx, dy, dz)
+    }
+
+    /// Return the box occupied by the block at this position
+    pub fn aabb(self) -> AABB<f64> {
+        let mins = Point3::new(self.px as f64, self.py as f64, self.pz as f64);
+        AABB::new(mins, mins + nalgebra::Vector3::repeat(1.0))
+    }
+
     #[inline(always)]
     pub fn pos_in_containing_chunk(self) -> (u32, u32, u32) {
         (
@@ -69,7 +100,7 @@ pub trait WorldGenerator {
 pub const CHUNK_SIZE: u32 = 32;
 
 /// Position of a chunk in the world
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ChunkPos {
     pub px: i64,
     pub py: i64,
@@ -158,7 +189,7 @@ impl From<ChunkPos> for ChunkPosXZ {
 }
 
 /// An RLE-compressed chunk
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompressedChunk {
     pub pos: ChunkPos,
     pub data: Vec<(u16, BlockId)>,
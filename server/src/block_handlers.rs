@@ -0,0 +1,282 @@
+//! The behavior of the blocks of the base game.
+//!
+//! New behaviors, for example growing crops, are added by implementing `BlockHandler`
+//! and registering the handler for the corresponding blocks in `create_block_handlers`.
+use crate::fluid::FluidHandler;
+use crate::tick::{BlockHandler, TickContext};
+use std::sync::Arc;
+use voxel_rs_common::{
+    block::{entity::BlockEntity, Block, BlockId, BlockType},
+    item::inventory::Inventory,
+    registry::Registry,
+    world::BlockPos,
+};
+
+/// Minimum light level for grass to spread
+const GRASS_SPREAD_MIN_LIGHT: u8 = 9;
+/// Leaves that are further than this from a log decay
+const LEAVES_MAX_LOG_DISTANCE: i64 = 4;
+/// Maximum delay between the moment leaves are found without wood and the moment they decay
+const LEAVES_MAX_DECAY_DELAY: u64 = 100;
+/// Delay between the moment a block loses its support and the moment it starts falling
+const FALL_DELAY: u64 = 2;
+/// Number of slots of a chest
+const CHEST_SIZE: usize = 27;
+
+/// The handlers of every block, indexed by block id
+pub type BlockHandlers = Vec<Option<Arc<dyn BlockHandler>>>;
+
+/// Create the handlers of every block of the registry
+pub fn create_block_handlers(block_registry: &Registry<Block>) -> BlockHandlers {
+    let mut handlers: BlockHandlers = vec![None; block_registry.get_number_of_ids() as usize];
+    let id = |name: &str| {
+        block_registry
+            .get_id_by_name(&name.to_owned())
+            .map(|id| id as BlockId)
+    };
+    let opaque = (0..block_registry.get_number_of_ids())
+        .map(|id| {
+            block_registry
+                .get_value_by_id(id)
+                .expect("Block registry ids are contiguous")
+                .is_opaque()
+        })
+        .collect::<Vec<_>>();
+
+    let solid = (0..block_registry.get_number_of_ids())
+        .map(|id| block_registry.get_value_by_id(id).unwrap().is_solid())
+        .collect::<Vec<_>>();
+
+    let fluid_handler: Arc<dyn BlockHandler> = Arc::new(FluidHandler::new(block_registry));
+    let falling_block_handler: Arc<dyn BlockHandler> = Arc::new(FallingBlockHandler {
+        solid: solid.clone(),
+    });
+    let plant_handler: Arc<dyn BlockHandler> = Arc::new(PlantHandler { solid });
+    for block_id in 0..block_registry.get_number_of_ids() {
+        let block = block_registry.get_value_by_id(block_id).unwrap();
+        if block.fluid.is_some() {
+            handlers[block_id as usize] = Some(fluid_handler.clone());
+        }
+        if block.falls() {
+            handlers[block_id as usize] = Some(falling_block_handler.clone());
+        }
+        if let BlockType::Plant { .. } = block.block_type {
+            handlers[block_id as usize] = Some(plant_handler.clone());
+        }
+    }
+
+    if let (Some(grass), Some(dirt)) = (id("grass"), id("dirt")) {
+        handlers[grass as usize] = Some(Arc::new(GrassHandler {
+            grass,
+            dirt,
+            opaque,
+        }));
+    }
+    if let (Some(leaves), Some(wood)) = (id("leaves"), id("wood")) {
+        // Logs can be placed along any axis
+        let wood = (0..block_registry.get_number_of_ids())
+            .map(|id| block_registry.get_value_by_id(id).unwrap().base == wood)
+            .collect();
+        handlers[leaves as usize] = Some(Arc::new(LeavesHandler { wood }));
+    }
+    // Every state of signs and chests has a block entity
+    let sign_handler: Arc<dyn BlockHandler> = Arc::new(SignHandler);
+    let chest_handler: Arc<dyn BlockHandler> = Arc::new(ChestHandler);
+    for block_id in 0..block_registry.get_number_of_ids() {
+        let block = block_registry.get_value_by_id(block_id).unwrap();
+        let base = block_registry.get_value_by_id(block.base as u32).unwrap();
+        match &base.name[..] {
+            "sign" => handlers[block_id as usize] = Some(sign_handler.clone()),
+            "chest" => handlers[block_id as usize] = Some(chest_handler.clone()),
+            _ => (),
+        }
+    }
+
+    handlers
+}
+
+/// Grass spreads to the lit dirt blocks around it, and turns back into dirt when it is covered
+struct GrassHandler {
+    grass: BlockId,
+    dirt: BlockId,
+    /// Whether every block is opaque, indexed by block id
+    opaque: Vec<bool>,
+}
+
+impl GrassHandler {
+    fn is_covered(&self, ctx: &TickContext, pos: BlockPos) -> bool {
+        let above = BlockPos::from((pos.px, pos.py + 1, pos.pz));
+        self.opaque[ctx.get_block(above) as usize]
+    }
+}
+
+impl BlockHandler for GrassHandler {
+    fn neighbor_changed(
+        &self,
+        ctx: &mut TickContext,
+        pos: BlockPos,
+        _block: BlockId,
+        neighbor: BlockPos,
+    ) {
+        // Grass dies as soon as it is covered
+        if neighbor.py == pos.py + 1 && self.is_covered(ctx, pos) {
+            ctx.set_block(pos, self.dirt);
+        }
+    }
+
+    fn random_tick(&self, ctx: &mut TickContext, pos: BlockPos, _block: BlockId) {
+        if self.is_covered(ctx, pos) {
+            ctx.set_block(pos, self.dirt);
+            return;
+        }
+        // Try to spread to a random block in a 3x5x3 box
+        let rng = ctx.rng();
+        let target = BlockPos::from((
+            pos.px + rng.gen_range(3) as i64 - 1,
+            pos.py + rng.gen_range(5) as i64 - 3,
+            pos.pz + rng.gen_range(3) as i64 - 1,
+        ));
+        let above_target = BlockPos::from((target.px, target.py + 1, target.pz));
+        if ctx.get_block(target) == self.dirt
+            && !self.is_covered(ctx, target)
+            && ctx.world().get_light(above_target) >= GRASS_SPREAD_MIN_LIGHT
+        {
+            ctx.set_block(target, self.grass);
+        }
+    }
+}
+
+/// Leaves decay when there is no wood close to them
+struct LeavesHandler {
+    /// Whether every block is a state of wood, indexed by block id
+    wood: Vec<bool>,
+}
+
+impl LeavesHandler {
+    fn is_supported(&self, ctx: &TickContext, pos: BlockPos) -> bool {
+        let d = LEAVES_MAX_LOG_DISTANCE;
+        for i in -d..=d {
+            for j in -d..=d {
+                for k in -d..=d {
+                    let neighbor = BlockPos::from((pos.px + i, pos.py + j, pos.pz + k));
+                    // Leaves next to unloaded chunks are supported, the wood might be there
+                    if !ctx.world().is_block_loaded(neighbor)
+                        || self.wood[ctx.get_block(neighbor) as usize]
+                    {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+impl BlockHandler for LeavesHandler {
+    fn scheduled_tick(&self, ctx: &mut TickContext, pos: BlockPos, _block: BlockId) {
+        if !self.is_supported(ctx, pos) {
+            ctx.set_block(pos, 0);
+        }
+    }
+
+    fn random_tick(&self, ctx: &mut TickContext, pos: BlockPos, block: BlockId) {
+        // Don't remove all the leaves of a tree at once
+        if !self.is_supported(ctx, pos) {
+            let delay = 1 + ctx.rng().gen_range(LEAVES_MAX_DECAY_DELAY);
+            ctx.schedule_tick(pos, block, delay);
+        }
+    }
+}
+
+/// Blocks that fall when the block below them is not solid
+struct FallingBlockHandler {
+    /// Whether every block is solid, indexed by block id
+    solid: Vec<bool>,
+}
+
+impl FallingBlockHandler {
+    fn is_supported(&self, ctx: &TickContext, pos: BlockPos) -> bool {
+        let below = BlockPos::from((pos.px, pos.py - 1, pos.pz));
+        // Don't let blocks fall into unloaded chunks
+        !ctx.world().is_block_loaded(below) || self.solid[ctx.get_block(below) as usize]
+    }
+}
+
+impl BlockHandler for FallingBlockHandler {
+    fn scheduled_tick(&self, ctx: &mut TickContext, pos: BlockPos, block: BlockId) {
+        if !self.is_supported(ctx, pos) {
+            ctx.spawn_falling_block(pos, block);
+        }
+    }
+
+    fn placed(&self, ctx: &mut TickContext, pos: BlockPos, block: BlockId) {
+        ctx.schedule_tick(pos, block, FALL_DELAY);
+    }
+
+    fn neighbor_changed(
+        &self,
+        ctx: &mut TickContext,
+        pos: BlockPos,
+        block: BlockId,
+        neighbor: BlockPos,
+    ) {
+        if neighbor.py == pos.py - 1 {
+            ctx.schedule_tick(pos, block, FALL_DELAY);
+        }
+    }
+}
+
+/// Plants break when the block below them is not solid
+struct PlantHandler {
+    /// Whether every block is solid, indexed by block id
+    solid: Vec<bool>,
+}
+
+impl PlantHandler {
+    fn break_if_unsupported(&self, ctx: &mut TickContext, pos: BlockPos) {
+        let below = BlockPos::from((pos.px, pos.py - 1, pos.pz));
+        if ctx.world().is_block_loaded(below) && !self.solid[ctx.get_block(below) as usize] {
+            ctx.set_block(pos, 0);
+        }
+    }
+}
+
+impl BlockHandler for PlantHandler {
+    fn placed(&self, ctx: &mut TickContext, pos: BlockPos, _block: BlockId) {
+        self.break_if_unsupported(ctx, pos);
+    }
+
+    fn neighbor_changed(
+        &self,
+        ctx: &mut TickContext,
+        pos: BlockPos,
+        _block: BlockId,
+        neighbor: BlockPos,
+    ) {
+        if neighbor.py == pos.py - 1 {
+            self.break_if_unsupported(ctx, pos);
+        }
+    }
+}
+
+/// Signs hold some text
+struct SignHandler;
+
+impl BlockHandler for SignHandler {
+    fn create_block_entity(&self, _block: BlockId) -> Option<BlockEntity> {
+        Some(BlockEntity::Sign {
+            text: String::new(),
+        })
+    }
+}
+
+/// Chests store items
+struct ChestHandler;
+
+impl BlockHandler for ChestHandler {
+    fn create_block_entity(&self, _block: BlockId) -> Option<BlockEntity> {
+        Some(BlockEntity::Chest {
+            inventory: Inventory::new(CHEST_SIZE),
+        })
+    }
+}
@@ -0,0 +1,215 @@
+//! Parsing of the commands sent by the players
+use voxel_rs_common::clock::DEFAULT_DAY_LENGTH;
+use voxel_rs_common::player::GameMode;
+
+/// The longest day that can be set with `/time daylength`, in ticks
+const MAX_DAY_LENGTH: u64 = 100 * DEFAULT_DAY_LENGTH;
+
+/// A command sent by a player
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// `/time set <time>`: set the time of the current day
+    TimeSet(TimeOfDay),
+    /// `/time add <ticks>`: advance the clock
+    TimeAdd(u64),
+    /// `/time query`: display the current time
+    TimeQuery,
+    /// `/time daylength <ticks>`: change the length of a day
+    TimeDayLength(u64),
+    /// `/give <item> [count]`: add items to the inventory of the player
+    Give(String, u32),
+    /// `/summon <model>`: spawn an entity with some model at the position of the player
+    Summon(String),
+    /// `/despawn`: remove every entity
+    Despawn,
+    /// `/gamemode <survival|creative|spectator>`: change the game mode of the player
+    GameMode(GameMode),
+}
+
+impl Command {
+    /// Return whether only the operators can run this command. Everyone can query the time.
+    pub fn needs_operator(&self) -> bool {
+        !matches!(self, Self::TimeQuery)
+    }
+}
+
+/// A time of the day, either given in ticks or by name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOfDay {
+    Ticks(u64),
+    Sunrise,
+    Noon,
+    Sunset,
+    Midnight,
+}
+
+impl TimeOfDay {
+    /// Convert to a number of ticks since the beginning of the day
+    pub fn to_ticks(self, day_length: u64) -> u64 {
+        match self {
+            Self::Ticks(ticks) => ticks,
+            Self::Sunrise => 0,
+            Self::Noon => day_length / 4,
+            Self::Sunset => day_length / 2,
+            Self::Midnight => day_length * 3 / 4,
+        }
+    }
+}
+
+/// Parse a command. The leading `/` is optional.
+pub fn parse_command(command: &str) -> Result<Command, String> {
+    let command = command.trim();
+    let command = command.strip_prefix('/').unwrap_or(command);
+    let args: Vec<&str> = command.split_whitespace().collect();
+    match &args[..] {
+        ["time", "set", time] => Ok(Command::TimeSet(parse_time_of_day(time)?)),
+        ["time", "add", ticks] => Ok(Command::TimeAdd(parse_ticks(ticks)?)),
+        ["time", "query"] => Ok(Command::TimeQuery),
+        ["time", "daylength", ticks] => parse_day_length(ticks).map(Command::TimeDayLength),
+        ["time", ..] => Err(
+            "Usage: /time set <ticks|sunrise|day|noon|sunset|night|midnight>, /time add <ticks>, /time query, /time daylength <ticks>".to_owned(),
+        ),
+        ["give", item] => Ok(Command::Give(item.to_string(), 1)),
+        ["give", item, count] => Ok(Command::Give(item.to_string(), parse_count(count)?)),
+        ["give", ..] => Err("Usage: /give <item> [count]".to_owned()),
+        ["summon", model] => Ok(Command::Summon(model.to_string())),
+        ["summon", ..] => Err("Usage: /summon <model>".to_owned()),
+        ["despawn"] => Ok(Command::Despawn),
+        ["despawn", ..] => Err("Usage: /despawn".to_owned()),
+        ["gamemode", mode] => parse_game_mode(mode).map(Command::GameMode),
+        ["gamemode", ..] => Err("Usage: /gamemode <survival|creative|spectator>".to_owned()),
+        [] => Err("Empty command".to_owned()),
+        [name, ..] => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+fn parse_ticks(s: &str) -> Result<u64, String> {
+    s.parse()
+        .map_err(|_| format!("Expected a number of ticks, got {}", s))
+}
+
+fn parse_day_length(s: &str) -> Result<u64, String> {
+    match s.parse() {
+        Ok(ticks) if (1..=MAX_DAY_LENGTH).contains(&ticks) => Ok(ticks),
+        _ => Err(format!(
+            "Expected a day length between 1 and {} ticks, got {}",
+            MAX_DAY_LENGTH, s
+        )),
+    }
+}
+
+fn parse_count(s: &str) -> Result<u32, String> {
+    match s.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Expected a positive number of items, got {}", s)),
+    }
+}
+
+fn parse_time_of_day(s: &str) -> Result<TimeOfDay, String> {
+    match s {
+        "sunrise" | "day" => Ok(TimeOfDay::Sunrise),
+        "noon" => Ok(TimeOfDay::Noon),
+        "sunset" | "night" => Ok(TimeOfDay::Sunset),
+        "midnight" => Ok(TimeOfDay::Midnight),
+        _ => parse_ticks(s).map(TimeOfDay::Ticks),
+    }
+}
+
+fn parse_game_mode(s: &str) -> Result<GameMode, String> {
+    match s {
+        "survival" | "s" => Ok(GameMode::Survival),
+        "creative" | "c" => Ok(GameMode::Creative),
+        "spectator" | "sp" => Ok(GameMode::Spectator),
+        _ => Err(format!("Unknown game mode: {}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_commands() {
+        assert_eq!(
+            parse_command("/time set noon"),
+            Ok(Command::TimeSet(TimeOfDay::Noon))
+        );
+        assert_eq!(
+            parse_command("time set 1200"),
+            Ok(Command::TimeSet(TimeOfDay::Ticks(1200)))
+        );
+        assert_eq!(parse_command("/time add 50"), Ok(Command::TimeAdd(50)));
+        assert_eq!(parse_command(" /time query "), Ok(Command::TimeQuery));
+        assert_eq!(
+            parse_command("/time daylength 2400"),
+            Ok(Command::TimeDayLength(2400))
+        );
+        assert!(parse_command("/time daylength 0").is_err());
+        assert!(parse_command("/time daylength 18446744073709551615").is_err());
+        assert!(parse_command("/time set later").is_err());
+        assert!(parse_command("/weather clear").is_err());
+        assert!(parse_command("/").is_err());
+    }
+
+    #[test]
+    fn test_parse_give_command() {
+        assert_eq!(
+            parse_command("/give ingot_iron"),
+            Ok(Command::Give("ingot_iron".to_owned(), 1))
+        );
+        assert_eq!(
+            parse_command("/give ingot_iron 20"),
+            Ok(Command::Give("ingot_iron".to_owned(), 20))
+        );
+        assert!(parse_command("/give ingot_iron 0").is_err());
+        assert!(parse_command("/give").is_err());
+    }
+
+    #[test]
+    fn test_time_of_day_to_ticks() {
+        assert_eq!(TimeOfDay::Noon.to_ticks(24000), 6000);
+        assert_eq!(TimeOfDay::Midnight.to_ticks(24000), 18000);
+        assert_eq!(TimeOfDay::Ticks(42).to_ticks(24000), 42);
+    }
+
+    #[test]
+    fn test_parse_entity_commands() {
+        assert_eq!(
+            parse_command("/summon knight"),
+            Ok(Command::Summon("knight".to_owned()))
+        );
+        assert!(parse_command("/summon").is_err());
+        assert_eq!(parse_command("/despawn"), Ok(Command::Despawn));
+        assert!(parse_command("/despawn knight").is_err());
+    }
+
+    #[test]
+    fn test_parse_gamemode_command() {
+        assert_eq!(
+            parse_command("/gamemode creative"),
+            Ok(Command::GameMode(GameMode::Creative))
+        );
+        assert_eq!(
+            parse_command("/gamemode sp"),
+            Ok(Command::GameMode(GameMode::Spectator))
+        );
+        assert!(parse_command("/gamemode hardcore").is_err());
+        assert!(parse_command("/gamemode").is_err());
+    }
+
+    #[test]
+    fn test_commands_that_need_an_operator() {
+        assert!(!parse_command("/time query").unwrap().needs_operator());
+        for command in &[
+            "/time set noon",
+            "/time add 100",
+            "/time daylength 1000",
+            "/give stone",
+            "/summon knight",
+            "/despawn",
+            "/gamemode creative",
+        ] {
+            assert!(parse_command(command).unwrap().needs_operator());
+        }
+    }
+}
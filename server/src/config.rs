@@ -0,0 +1,62 @@
+//! Configuration of the server
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use voxel_rs_common::interaction::DEFAULT_REACH;
+use voxel_rs_common::player::GameMode;
+
+const CONFIG_FILENAME: &str = "server.ron";
+
+/// Settings of the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Number of threads used to compute the lighting of the chunks
+    pub light_threads: usize,
+    /// Number of random block ticks per loaded chunk and per game tick
+    pub random_ticks_per_chunk: u32,
+    /// Game mode of the players that join the server for the first time
+    pub default_game_mode: GameMode,
+    /// Maximum distance between the camera of a player and the blocks it interacts with
+    pub reach: f64,
+    /// Names of the players that can change their game mode. In singleplayer, the player always can.
+    pub operators: Vec<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            light_threads: 2,
+            random_ticks_per_chunk: 3,
+            default_game_mode: GameMode::Survival,
+            reach: DEFAULT_REACH,
+            operators: Vec::new(),
+        }
+    }
+}
+
+/// Load the server config from `folder_path`, or write the default config if it doesn't exist.
+pub fn load_config(folder_path: impl AsRef<Path>) -> Result<ServerConfig> {
+    let path = folder_path.as_ref().join(CONFIG_FILENAME);
+    if path.is_file() {
+        info!("Reading server config from {}", path.display());
+        let buffer = fs::read_to_string(&path)
+            .context(format!("Failed to read server config {}", path.display()))?;
+        ron::de::from_str(&buffer)
+            .context(format!("Failed to parse server config {}", path.display()))
+    } else {
+        info!(
+            "No server config at {}, using the default config",
+            path.display()
+        );
+        let config = ServerConfig::default();
+        fs::create_dir_all(folder_path.as_ref())?;
+        let string = ron::ser::to_string_pretty(&config, Default::default())
+            .context("Failed to serialize server config")?;
+        fs::write(&path, string)
+            .context(format!("Failed to write server config {}", path.display()))?;
+        Ok(config)
+    }
+}
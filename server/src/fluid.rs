@@ -0,0 +1,206 @@
+//! Simulation of the flowing fluids.
+//!
+//! Every time a fluid block or one of its neighbors changes, a tick is scheduled for the fluid block.
+//! When the tick runs, the state of the fluid block and of the blocks it can flow into is recomputed from their neighbors:
+//! * fluid falls into the block below it,
+//! * fluid that can't fall spreads horizontally, losing one level per block, up to the range of the fluid,
+//! * a block between two sources becomes a source if the fluid is renewable,
+//! * flowing fluid that is not fed anymore dries up.
+use crate::tick::{BlockHandler, TickContext};
+use crate::world::World;
+use std::collections::HashMap;
+use voxel_rs_common::{
+    block::{Block, BlockId, BlockType, FluidState},
+    registry::Registry,
+    world::BlockPos,
+};
+
+const NEIGHBORS: [[i64; 3]; 5] = [[1, 0, 0], [-1, 0, 0], [0, -1, 0], [0, 0, 1], [0, 0, -1]];
+const HORIZONTAL_NEIGHBORS: [[i64; 3]; 4] = [[1, 0, 0], [-1, 0, 0], [0, 0, 1], [0, 0, -1]];
+
+fn offset(pos: BlockPos, [dx, dy, dz]: [i64; 3]) -> BlockPos {
+    BlockPos::from((pos.px + dx, pos.py + dy, pos.pz + dz))
+}
+
+/// The properties of a fluid, shared by all of its states
+#[derive(Debug, Clone, Copy)]
+struct FluidProperties {
+    range: u8,
+    flow_delay: u64,
+    renewable: bool,
+}
+
+/// The block handler of every fluid block
+pub struct FluidHandler {
+    /// The fluid state of every block, indexed by block id
+    states: Vec<Option<FluidState>>,
+    /// Whether every block is solid, indexed by block id
+    solid: Vec<bool>,
+    /// The properties of every fluid, indexed by source block id
+    properties: HashMap<BlockId, FluidProperties>,
+}
+
+impl FluidHandler {
+    pub fn new(block_registry: &Registry<Block>) -> Self {
+        let mut states = Vec::new();
+        let mut solid = Vec::new();
+        let mut properties = HashMap::new();
+        for id in 0..block_registry.get_number_of_ids() {
+            let block = block_registry
+                .get_value_by_id(id)
+                .expect("Block registry ids are contiguous");
+            states.push(block.fluid);
+            solid.push(block.is_solid());
+            if let (
+                Some(fluid),
+                BlockType::Fluid {
+                    range,
+                    flow_delay,
+                    renewable,
+                    ..
+                },
+            ) = (block.fluid, &block.block_type)
+            {
+                properties.insert(
+                    fluid.source,
+                    FluidProperties {
+                        range: *range,
+                        flow_delay: (*flow_delay).max(1),
+                        renewable: *renewable,
+                    },
+                );
+            }
+        }
+        Self {
+            states,
+            solid,
+            properties,
+        }
+    }
+
+    /// Schedule the next flow update of the fluid block at `pos`
+    fn schedule_flow(&self, ctx: &mut TickContext, pos: BlockPos, block: BlockId) {
+        if let Some(state) = self.fluid_state(block) {
+            ctx.schedule_tick(pos, block, self.properties[&state.source].flow_delay);
+        }
+    }
+
+    fn fluid_state(&self, block: BlockId) -> Option<FluidState> {
+        self.states.get(block as usize).copied().flatten()
+    }
+
+    fn fluid_state_at(&self, pos: BlockPos, world: &World) -> Option<FluidState> {
+        self.fluid_state(world.get_block(pos))
+    }
+
+    fn is_solid_at(&self, pos: BlockPos, world: &World) -> bool {
+        self.solid
+            .get(world.get_block(pos) as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    /// Return true if the fluid at `pos` can spread horizontally, i.e. it can't fall
+    fn spreads_horizontally(&self, pos: BlockPos, source: BlockId, world: &World) -> bool {
+        let below = offset(pos, [0, -1, 0]);
+        self.is_solid_at(below, world)
+            || self
+                .fluid_state_at(below, world)
+                .map(|state| state.source == source && state.is_source())
+                .unwrap_or(false)
+    }
+
+    /// Compute the new block at `pos`, or `None` if it doesn't change
+    fn compute_update(&self, pos: BlockPos, world: &World) -> Option<BlockId> {
+        if !world.is_block_loaded(pos) {
+            return None;
+        }
+        let current = world.get_block(pos);
+        let current_state = self.fluid_state(current);
+        match current_state {
+            // Sources never change
+            Some(state) if state.is_source() => return None,
+            Some(_) => (),
+            // Only air can be replaced by a fluid
+            None if current != 0 => return None,
+            None => (),
+        }
+
+        // Find which fluid could be in this block
+        let above = self.fluid_state_at(offset(pos, [0, 1, 0]), world);
+        let horizontal = HORIZONTAL_NEIGHBORS
+            .iter()
+            .map(|&d| (offset(pos, d), self.fluid_state_at(offset(pos, d), world)))
+            .collect::<Vec<_>>();
+        let source = current_state
+            .or(above)
+            .or_else(|| horizontal.iter().find_map(|(_, state)| *state))?
+            .source;
+        let properties = self.properties[&source];
+
+        let desired = if above.map(|state| state.source) == Some(source) {
+            // Falling fluid
+            source + 1
+        } else {
+            let mut adjacent_sources = 0;
+            let mut min_level = None;
+            for &(neighbor, state) in horizontal.iter() {
+                if let Some(state) = state {
+                    if state.source != source {
+                        continue;
+                    }
+                    if state.is_source() {
+                        adjacent_sources += 1;
+                    }
+                    if self.spreads_horizontally(neighbor, source, world) {
+                        min_level = Some(min_level.map_or(state.level, |l: u8| l.min(state.level)));
+                    }
+                }
+            }
+            if properties.renewable
+                && adjacent_sources >= 2
+                && self.spreads_horizontally(pos, source, world)
+            {
+                source
+            } else {
+                match min_level {
+                    Some(level) if level < properties.range => source + 2 + level as BlockId,
+                    _ => 0,
+                }
+            }
+        };
+
+        if desired != current {
+            Some(desired)
+        } else {
+            None
+        }
+    }
+}
+
+impl BlockHandler for FluidHandler {
+    fn scheduled_tick(&self, ctx: &mut TickContext, pos: BlockPos, _block: BlockId) {
+        let world = ctx.world();
+        let updates = std::iter::once(pos)
+            .chain(NEIGHBORS.iter().map(|&d| offset(pos, d)))
+            .filter_map(|pos| self.compute_update(pos, world).map(|block| (pos, block)))
+            .collect::<Vec<_>>();
+        for (pos, block) in updates {
+            ctx.set_block(pos, block);
+        }
+    }
+
+    fn placed(&self, ctx: &mut TickContext, pos: BlockPos, block: BlockId) {
+        self.schedule_flow(ctx, pos, block);
+    }
+
+    fn neighbor_changed(
+        &self,
+        ctx: &mut TickContext,
+        pos: BlockPos,
+        block: BlockId,
+        _neighbor: BlockPos,
+    ) {
+        self.schedule_flow(ctx, pos, block);
+    }
+}
@@ -0,0 +1,191 @@
+//! Block interactions of the players.
+//!
+//! The interactions are checked against the server simulation of the player: the server looks for the
+//! pointed block from its own position of the player, within the configured reach, and rejects the
+//! interaction if it is not the block the client saw, unless the ray passes very close to that block.
+use crate::world::World;
+use crate::PlayerData;
+use std::time::Instant;
+use voxel_rs_common::{
+    block::{
+        entity::BlockEntity,
+        mining::{self, MiningTool},
+    },
+    data::Data,
+    interaction::{self, BlockInteraction, InteractionError},
+    item::ItemType,
+    network::{messages::ToClient, Server},
+    physics::{
+        simulation::{PhysicsState, ServerPhysicsSimulation},
+        BlockContainer,
+    },
+    player::PlayerId,
+    world::BlockPos,
+};
+
+/// What the server needs to validate the interactions of the players
+pub struct InteractionContext<'a> {
+    game_data: &'a Data,
+    /// The server simulation of the players and of the entities
+    physics_state: &'a PhysicsState,
+    reach: f64,
+}
+
+impl<'a> InteractionContext<'a> {
+    pub fn new(
+        game_data: &'a Data,
+        physics_simulation: &'a ServerPhysicsSimulation,
+        reach: f64,
+    ) -> Self {
+        Self {
+            game_data,
+            physics_state: &physics_simulation.get_state().physics_state,
+            reach,
+        }
+    }
+
+    /// Find the block pointed by a player, and check that it's the target of the interaction
+    pub fn check_target(
+        &self,
+        id: PlayerId,
+        interaction: &BlockInteraction,
+        world: &World,
+    ) -> Result<(BlockPos, usize), InteractionError> {
+        let player = self
+            .physics_state
+            .players
+            .get(&id)
+            .ok_or(InteractionError::OutOfReach)?;
+        interaction::check_target(player, interaction, self.reach, world)
+    }
+
+    /// Return how many seconds it takes to break the block at `pos` with `tool`, or `None` if it can't be broken
+    fn mining_time(&self, world: &World, pos: BlockPos, tool: Option<MiningTool>) -> Option<f64> {
+        self.game_data
+            .blocks
+            .get_value_by_id(world.get_block(pos) as u32)
+            .and_then(|block| block.block_type.mining())
+            .map(|mining| mining.time(tool))
+    }
+
+    /// Start mining the pointed block. Creative players and the blocks that take no time to mine break it immediately.
+    pub fn start_mining(
+        &self,
+        world: &mut World,
+        id: PlayerId,
+        player_data: &mut PlayerData,
+        interaction: &BlockInteraction,
+    ) -> Result<(), InteractionError> {
+        player_data.mining = None;
+        if !player_data.game_mode.interacts_with_world() {
+            return Err(InteractionError::NotAllowed);
+        }
+        let (block, _face) = self.check_target(id, interaction, world)?;
+        let tool = player_data.selected_tool(&self.game_data.items);
+        if let Some(mining_time) = self.mining_time(world, block, tool) {
+            if player_data.game_mode.breaks_blocks_instantly()
+                || mining::is_mining_finished(0.0, mining_time)
+            {
+                world.break_block(block, tool.map(|tool| tool.kind));
+            } else {
+                player_data.mining = Some((block, Instant::now(), tool));
+            }
+        }
+        Ok(())
+    }
+
+    /// Break the block that is being mined, if it was mined for long enough.
+    /// The tool the player started mining with is used, so that switching tools
+    /// doesn't speed up the mining.
+    pub fn finish_mining(
+        &self,
+        world: &mut World,
+        id: PlayerId,
+        player_data: &mut PlayerData,
+        interaction: &BlockInteraction,
+    ) -> Result<(), InteractionError> {
+        let (mined_block, start, tool) = player_data
+            .mining
+            .take()
+            .ok_or(InteractionError::NotMinedLongEnough)?;
+        let (block, _face) = self.check_target(id, interaction, world)?;
+        if block != mined_block {
+            return Err(InteractionError::TargetMismatch);
+        }
+        if let Some(mining_time) = self.mining_time(world, block, tool) {
+            if !mining::is_mining_finished(start.elapsed().as_secs_f64(), mining_time) {
+                return Err(InteractionError::NotMinedLongEnough);
+            }
+            world.break_block(block, tool.map(|tool| tool.kind));
+        }
+        Ok(())
+    }
+
+    /// Place the block of the selected item against the pointed face, or open the pointed container
+    pub fn place_block(
+        &self,
+        server: &mut dyn Server,
+        world: &mut World,
+        id: PlayerId,
+        player_data: &mut PlayerData,
+        interaction: &BlockInteraction,
+    ) -> Result<(), InteractionError> {
+        if !player_data.game_mode.interacts_with_world() {
+            return Err(InteractionError::NotAllowed);
+        }
+        let (target, face) = self.check_target(id, interaction, world)?;
+        // Using a container opens it instead of placing a block
+        if let Some(BlockEntity::Chest { inventory }) = world.get_block_entity(target) {
+            player_data.open_container = Some(target);
+            server.send(id, ToClient::OpenContainer(target, inventory.clone()));
+            return Ok(());
+        }
+        let pos = target.adjacent(face);
+        if !world.is_block_loaded(pos) {
+            return Err(InteractionError::ChunkNotLoaded);
+        }
+        interaction::check_replaceable(world.get_block(pos), world.get_fluid_state(pos))?;
+        // Place the block of the selected item, and consume the item in survival
+        let selected = player_data.selected_slot;
+        let block_to_place = player_data
+            .inventory
+            .get(selected)
+            .and_then(|stack| self.game_data.items.get_value_by_id(stack.item))
+            .and_then(|item| match item.ty {
+                ItemType::Block { block } => Some(block),
+                _ => None,
+            })
+            .ok_or(InteractionError::NoBlockToPlace)?;
+        // Choose the state of the block from the face and the direction of the player
+        let block = self.game_data.blocks.get_value_by_id(block_to_place as u32);
+        let state = block
+            .map(|b| b.placement_state(face, interaction.yaw))
+            .unwrap_or(block_to_place);
+        if let Some(block) = self.game_data.blocks.get_value_by_id(state as u32) {
+            interaction::check_placement(
+                &block.collision_boxes(pos),
+                self.physics_state.players.values(),
+                self.physics_state.solid_entity_aabbs(),
+            )?;
+        }
+        if world.set_block(pos, state) && player_data.game_mode.consumes_items() {
+            player_data.inventory.remove_one(selected);
+            server.send(id, ToClient::PlayerInventory(player_data.inventory.clone()));
+        }
+        Ok(())
+    }
+}
+
+/// Tell the player whether the server accepted its interaction, so that the client can confirm or roll back its prediction
+pub fn report_result(
+    server: &mut dyn Server,
+    id: PlayerId,
+    interaction: &BlockInteraction,
+    result: Result<(), InteractionError>,
+) {
+    let message = match result {
+        Ok(()) => ToClient::InteractionAccepted(interaction.id),
+        Err(error) => ToClient::InteractionRejected(interaction.id, error),
+    };
+    server.send(id, message);
+}
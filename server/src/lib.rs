@@ -1,14 +1,28 @@
+use crate::command::{parse_command, Command};
+use crate::config::load_config;
+use crate::interaction::InteractionContext;
+use crate::mob::Mobs;
+use crate::storage::{SavedPlayer, WorldStorage};
 use crate::world::World;
 use anyhow::Result;
 use log::info;
 use nalgebra::{Point3, Vector3};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::Instant;
-use voxel_rs_common::block::BlockId;
-use voxel_rs_common::physics::player::PhysicsPlayer;
+use voxel_rs_common::block::entity::BlockEntity;
+use voxel_rs_common::block::mining::MiningTool;
+use voxel_rs_common::entity::{EntityId, EntityModel};
+use voxel_rs_common::health::{self, DamageSource, Health, MAX_HEALTH};
+use voxel_rs_common::item::inventory::{
+    self, Inventory, InventoryKind, ItemStack, SlotRef, HOTBAR_SIZE, PLAYER_INVENTORY_SIZE,
+};
+use voxel_rs_common::item::{
+    recipe::{self, Recipe, CRAFTING_GRID_SLOTS},
+    Item,
+};
 use voxel_rs_common::time::BreakdownCounter;
 use voxel_rs_common::{
+    clock::TICK_DURATION,
     data::load_data,
     debug::{send_debug_info, send_perf_breakdown},
     network::{
@@ -16,31 +30,70 @@ use voxel_rs_common::{
         Server, ServerEvent,
     },
     physics::simulation::ServerPhysicsSimulation,
-    player::{CloseChunks, RenderDistance},
+    player::{CloseChunks, GameMode, PlayerId, RenderDistance},
+    registry::Registry,
     world::{BlockPos, ChunkPos},
     worldgen::DefaultWorldGenerator,
 };
 
+mod block_handlers;
+mod command;
+mod config;
+mod fluid;
+mod interaction;
 mod light;
+mod mob;
+mod storage;
+mod tick;
 mod world;
 mod worldgen;
 
-// TODO: refactor
-const D: [[i64; 3]; 6] = [
-    [1, 0, 0],
-    [-1, 0, 0],
-    [0, 1, 0],
-    [0, -1, 0],
-    [0, 0, 1],
-    [0, 0, -1],
+/// Directory where the server config is stored
+const CONFIG_DIRECTORY: &str = "game_data";
+/// Directory where the world is saved
+const WORLD_DIRECTORY: &str = "game_data/world";
+/// Maximum number of game ticks to run in one iteration of the main loop if the server is late
+const MAX_TICKS_PER_ITERATION: u32 = 10;
+/// Number of ticks between two synchronizations of the world time with the clients
+const TIME_SYNC_INTERVAL: u64 = 20;
+/// Number of ticks between two saves of the world
+const AUTOSAVE_INTERVAL: u64 = 60 * 20;
+/// The items in the inventory of new players
+const STARTING_ITEMS: [(&str, u32); 6] = [
+    ("stone", 64),
+    ("dirt", 64),
+    ("wood", 64),
+    ("sand", 64),
+    ("stone_stairs", 64),
+    ("chest", 4),
 ];
+/// Containers are closed when the player is further than this from them,
+/// and the block entities are only sent to the players that are closer than this
+const CONTAINER_MAX_DISTANCE: f64 = 10.0;
+/// Maximum number of characters of a player name
+const MAX_PLAYER_NAME_LENGTH: usize = 32;
+/// Height of the entities spawned with `/summon`, the model is scaled to match it
+const SUMMONED_ENTITY_HEIGHT: f64 = 1.8;
 
 /// The data that the server stores for every player.
 pub struct PlayerData {
     loaded_chunks: HashMap<ChunkPos, u64>,
     render_distance: RenderDistance,
     close_chunks: CloseChunks,
-    block_to_place: BlockId,
+    /// The hotbar slots come first
+    inventory: Inventory,
+    /// The selected slot of the hotbar
+    selected_slot: usize,
+    crafting_grid: Inventory,
+    /// The position of the container the player opened
+    open_container: Option<BlockPos>,
+    /// The name of the player, once it sent it
+    name: Option<String>,
+    /// The health of the player. The server is the only one to change it.
+    health: Health,
+    game_mode: GameMode,
+    /// The block the player is mining, when it started mining it and the tool it started with
+    mining: Option<(BlockPos, Instant, Option<MiningTool>)>,
 }
 
 impl Default for PlayerData {
@@ -51,27 +104,76 @@ impl Default for PlayerData {
             loaded_chunks: Default::default(),
             render_distance,
             close_chunks,
-            block_to_place: 1,
+            inventory: Inventory::new(PLAYER_INVENTORY_SIZE),
+            selected_slot: 0,
+            crafting_grid: Inventory::new(CRAFTING_GRID_SLOTS),
+            open_container: None,
+            name: None,
+            health: Health::new(MAX_HEALTH),
+            game_mode: GameMode::default(),
+            mining: None,
+        }
+    }
+}
+
+impl PlayerData {
+    /// Return the message that sends the crafting grid to the player
+    fn crafting_grid_message(&self, recipes: &[Recipe]) -> ToClient {
+        let result = recipe::find_recipe(recipes, &self.crafting_grid).map(|recipe| recipe.result);
+        ToClient::CraftingGrid(self.crafting_grid.clone(), result)
+    }
+
+    /// Return the tool in the selected slot of the hotbar, if there is one
+    fn selected_tool(&self, items: &Registry<Item>) -> Option<MiningTool> {
+        self.inventory
+            .get(self.selected_slot)
+            .and_then(|stack| items.get_value_by_id(stack.item))
+            .and_then(|item| item.ty.mining_tool())
+    }
+
+    /// Save the data that is kept when the player leaves, if the player sent its name
+    fn save(&self, storage: &WorldStorage) -> Result<()> {
+        match &self.name {
+            Some(name) => storage.save_player(
+                name,
+                &SavedPlayer {
+                    game_mode: self.game_mode,
+                },
+            ),
+            None => Ok(()),
         }
     }
 }
 
-/// Start a new server instance.
-pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
+/// Start a new server instance. In singleplayer, the player is an operator.
+pub fn launch_server(mut server: Box<dyn Server>, singleplayer: bool) -> Result<()> {
     info!("Starting server");
 
     let mut server_timing = BreakdownCounter::new();
 
-    // Load data
+    // Load config and data
+    let config = load_config(CONFIG_DIRECTORY)?;
+    info!("Current server config: {:?}", config);
     let game_data = load_data("data".into())?;
 
+    // Load world
+    let storage = WorldStorage::open(WORLD_DIRECTORY)?;
+    let mut world_metadata = storage.load_metadata()?;
+
     let mut world = World::new(
         game_data.blocks.clone(),
+        game_data.loot_tables.clone(),
         Box::new(DefaultWorldGenerator::new(&game_data.blocks.clone())),
+        storage.open_chunks(&game_data.blocks)?,
+        &config,
+        world_metadata.seed,
     );
     let mut players = HashMap::new();
     let mut physics_simulation = ServerPhysicsSimulation::new();
+    let mut entity_models: BTreeMap<EntityId, EntityModel> = BTreeMap::new();
+    let mut mobs = Mobs::new(world_metadata.seed.wrapping_add(2));
     let mut close_chunks_merged = Vec::new();
+    let mut last_tick_time = Instant::now();
 
     info!("Server initialized successfully! Starting server loop");
     loop {
@@ -84,83 +186,337 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                 ServerEvent::ClientConnected(id) => {
                     info!("Client connected to the server!");
                     physics_simulation.set_player_input(id, Default::default());
-                    players.insert(id, PlayerData::default());
+                    let mut player_data = PlayerData {
+                        game_mode: config.default_game_mode,
+                        ..PlayerData::default()
+                    };
+                    physics_simulation.set_game_mode(id, player_data.game_mode);
+                    for &(name, count) in STARTING_ITEMS.iter() {
+                        if let Some(item) = game_data.items.get_id_by_name(&name.to_owned()) {
+                            player_data.inventory.insert(ItemStack { item, count });
+                        }
+                    }
+                    let inventory = player_data.inventory.clone();
+                    let crafting_grid = player_data.crafting_grid_message(&game_data.recipes);
+                    players.insert(id, player_data);
                     server.send(id, ToClient::GameData(game_data.clone()));
                     server.send(id, ToClient::CurrentId(id));
+                    server.send(id, ToClient::UpdateTime(world_metadata.time));
+                    server.send(id, ToClient::PlayerInventory(inventory));
+                    server.send(id, ToClient::UpdateHealth(players[&id].health));
+                    server.send(id, ToClient::SetReach(config.reach));
+                    server.send(id, crafting_grid);
+                    for (&entity, model) in entity_models.iter() {
+                        server.send(id, ToClient::SpawnEntity(entity, model.clone()));
+                    }
+                    for (&player, data) in players.iter() {
+                        if let Some(name) = &data.name {
+                            server.send(id, ToClient::PlayerName(player, name.clone()));
+                        }
+                    }
                 }
                 ServerEvent::ClientDisconnected(id) => {
                     physics_simulation.remove(id);
-                    players.remove(&id);
+                    if let Some(player_data) = players.remove(&id) {
+                        player_data.save(&storage)?;
+                    }
                 }
                 ServerEvent::ClientMessage(id, message) => match message {
                     ToServer::UpdateInput(input) => {
                         assert!(players.contains_key(&id));
                         physics_simulation.set_player_input(id, input);
                     }
+                    ToServer::SetPlayerName(name) => {
+                        let name = name
+                            .trim()
+                            .chars()
+                            .take(MAX_PLAYER_NAME_LENGTH)
+                            .collect::<String>();
+                        let name_taken = players
+                            .values()
+                            .any(|data| data.name.as_ref() == Some(&name));
+                        if players[&id].name.is_some() {
+                            // Only the first name is accepted, the operator rights depend on it
+                            server.send(
+                                id,
+                                ToClient::CommandFeedback("You can't change your name".to_owned()),
+                            );
+                        } else if name_taken {
+                            server.send(
+                                id,
+                                ToClient::CommandFeedback(format!(
+                                    "The name {} is already used",
+                                    name
+                                )),
+                            );
+                        } else if !name.is_empty() {
+                            for (&player, _) in players.iter() {
+                                server.send(player, ToClient::PlayerName(id, name.clone()));
+                            }
+                            let player_data = players.get_mut(&id).unwrap();
+                            // Restore the game mode the player had when it left
+                            if let Some(saved_player) = storage.load_player(&name)? {
+                                player_data.game_mode = saved_player.game_mode;
+                                physics_simulation.set_game_mode(id, saved_player.game_mode);
+                            }
+                            player_data.name = Some(name);
+                        }
+                    }
                     ToServer::SetRenderDistance(render_distance) => {
                         assert!(players.contains_key(&id));
                         players.entry(id).and_modify(move |player_data| {
                             player_data.render_distance = render_distance
                         });
                     }
-                    ToServer::BreakBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer::from_coords(Point3::from(player_pos));
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(block.pos_in_containing_chunk(), 0);
-                                world.set_chunk(Arc::new(new_chunk));
+                    ToServer::StartMining(interaction) => {
+                        let result =
+                            InteractionContext::new(&game_data, &physics_simulation, config.reach)
+                                .start_mining(
+                                    &mut world,
+                                    id,
+                                    players.get_mut(&id).unwrap(),
+                                    &interaction,
+                                );
+                        interaction::report_result(server.as_mut(), id, &interaction, result);
+                    }
+                    ToServer::AbortMining => {
+                        players.get_mut(&id).unwrap().mining = None;
+                    }
+                    ToServer::FinishMining(interaction) => {
+                        let result =
+                            InteractionContext::new(&game_data, &physics_simulation, config.reach)
+                                .finish_mining(
+                                    &mut world,
+                                    id,
+                                    players.get_mut(&id).unwrap(),
+                                    &interaction,
+                                );
+                        interaction::report_result(server.as_mut(), id, &interaction, result);
+                    }
+                    ToServer::PickBlock(interaction) => {
+                        let target =
+                            InteractionContext::new(&game_data, &physics_simulation, config.reach)
+                                .check_target(id, &interaction, &world);
+                        if let Ok((block, _face)) = target {
+                            let player_data = players.get_mut(&id).unwrap();
+                            let slot = game_data
+                                .block_item(world.get_block(block))
+                                .and_then(|item| player_data.inventory.find(item));
+                            match slot {
+                                Some(slot) if slot < HOTBAR_SIZE => {
+                                    player_data.selected_slot = slot;
+                                    server.send(id, ToClient::SelectHotbarSlot(slot));
+                                }
+                                Some(slot) => {
+                                    // Swap the stack with the selected slot of the hotbar
+                                    let inventory = &mut player_data.inventory;
+                                    let selected = player_data.selected_slot;
+                                    let (stack, selected_stack) =
+                                        (inventory.get(slot), inventory.get(selected));
+                                    inventory.set(slot, selected_stack);
+                                    inventory.set(selected, stack);
+                                    server.send(id, ToClient::PlayerInventory(inventory.clone()));
+                                }
+                                None => {}
                             }
                         }
                     }
-                    ToServer::SelectBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer::from_coords(Point3::from(player_pos));
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((block, _face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            // TODO: careful with more complicated blocks
-                            players.get_mut(&id).unwrap().block_to_place = world.get_block(block);
+                    ToServer::SelectHotbarSlot(slot) => {
+                        if slot < HOTBAR_SIZE {
+                            players.get_mut(&id).unwrap().selected_slot = slot;
                         }
                     }
-                    ToServer::PlaceBlock(player_pos, yaw, pitch) => {
-                        // TODO: check player pos and block
-                        let physics_player = PhysicsPlayer::from_coords(Point3::from(player_pos));
-                        let y = yaw.to_radians();
-                        let p = pitch.to_radians();
-                        let dir = Vector3::new(-y.sin() * p.cos(), p.sin(), -y.cos() * p.cos());
-                        // TODO: don't hardcode max dist
-                        if let Some((mut block, face)) =
-                            physics_player.get_pointed_at(dir, 10.0, &world)
-                        {
-                            block.px += D[face][0];
-                            block.py += D[face][1];
-                            block.pz += D[face][2];
-                            let chunk_pos = block.containing_chunk_pos();
-                            if let Some(chunk) = world.get_chunk(chunk_pos) {
-                                let mut new_chunk = (*chunk).clone();
-                                new_chunk.set_block_at(
-                                    block.pos_in_containing_chunk(),
-                                    players.get(&id).unwrap().block_to_place,
+                    ToServer::PlaceBlock(interaction) => {
+                        let result =
+                            InteractionContext::new(&game_data, &physics_simulation, config.reach)
+                                .place_block(
+                                    server.as_mut(),
+                                    &mut world,
+                                    id,
+                                    players.get_mut(&id).unwrap(),
+                                    &interaction,
                                 );
-                                world.set_chunk(Arc::new(new_chunk));
+                        interaction::report_result(server.as_mut(), id, &interaction, result);
+                    }
+                    ToServer::RequestBlockEntity(pos) => {
+                        if is_close_to_block(&physics_simulation, id, pos) {
+                            let entity = world.get_block_entity(pos).cloned();
+                            server.send(id, ToClient::BlockEntity(pos, entity));
+                        } else {
+                            log::warn!("Player {:?} requested a block entity too far away", id);
+                        }
+                    }
+                    ToServer::MoveStack(from, to) => {
+                        let player_data = players.get_mut(&id).unwrap();
+                        let container = player_data.open_container.and_then(|pos| {
+                            match world.get_block_entity(pos) {
+                                Some(BlockEntity::Chest { inventory }) => {
+                                    Some((pos, inventory.clone()))
+                                }
+                                _ => None,
+                            }
+                        });
+                        let (container_pos, mut container_inventory) = match container {
+                            Some((pos, inventory)) => (Some(pos), Some(inventory)),
+                            None => (None, None),
+                        };
+                        if !move_stack(player_data, container_inventory.as_mut(), from, to) {
+                            log::warn!("Player {:?} sent an invalid stack move", id);
+                        }
+                        server.send(id, ToClient::PlayerInventory(player_data.inventory.clone()));
+                        server.send(id, player_data.crafting_grid_message(&game_data.recipes));
+                        if let (Some(pos), Some(inventory)) = (container_pos, container_inventory) {
+                            world.set_block_entity(
+                                pos,
+                                BlockEntity::Chest {
+                                    inventory: inventory.clone(),
+                                },
+                            );
+                            // Every player looking into the container sees the change
+                            for (&player, data) in players.iter() {
+                                if data.open_container == Some(pos) {
+                                    server.send(
+                                        player,
+                                        ToClient::OpenContainer(pos, inventory.clone()),
+                                    );
+                                }
+                            }
+                        }
+                    }
+                    ToServer::CloseContainer => {
+                        players.get_mut(&id).unwrap().open_container = None;
+                    }
+                    ToServer::Craft => {
+                        let player_data = players.get_mut(&id).unwrap();
+                        let mut crafting_grid = player_data.crafting_grid.clone();
+                        if let Some(result) = recipe::craft(&game_data.recipes, &mut crafting_grid)
+                        {
+                            // Only craft if the result fits in the inventory
+                            let mut inventory = player_data.inventory.clone();
+                            if inventory.insert(result).is_none() {
+                                player_data.inventory = inventory;
+                                player_data.crafting_grid = crafting_grid;
+                            }
+                        }
+                        server.send(id, ToClient::PlayerInventory(player_data.inventory.clone()));
+                        server.send(id, player_data.crafting_grid_message(&game_data.recipes));
+                    }
+                    ToServer::Command(command) => {
+                        let is_operator = singleplayer
+                            || players[&id]
+                                .name
+                                .as_ref()
+                                .map_or(false, |name| config.operators.contains(name));
+                        let time = &mut world_metadata.time;
+                        let mut time_changed = false;
+                        let feedback = match parse_command(&command) {
+                            Ok(command) if command.needs_operator() && !is_operator => {
+                                "Only the operators can use this command".to_owned()
+                            }
+                            Ok(Command::TimeSet(time_of_day)) => {
+                                time.set_tick_of_day(time_of_day.to_ticks(time.day_length));
+                                time_changed = true;
+                                format!("Set the time to {}", time.tick_of_day())
+                            }
+                            Ok(Command::TimeAdd(ticks)) => {
+                                time.add_ticks(ticks);
+                                time_changed = true;
+                                format!("Set the time to {}", time.tick_of_day())
+                            }
+                            Ok(Command::TimeQuery) => format!(
+                                "Day {}, time {} of {}",
+                                time.day(),
+                                time.tick_of_day(),
+                                time.day_length
+                            ),
+                            Ok(Command::TimeDayLength(day_length)) => {
+                                time.set_day_length(day_length);
+                                time_changed = true;
+                                format!("Set the day length to {} ticks", time.day_length)
+                            }
+                            Ok(Command::Give(item_name, count)) => {
+                                match game_data.items.get_id_by_name(&item_name) {
+                                    Some(item) => {
+                                        let inventory =
+                                            &mut players.get_mut(&id).unwrap().inventory;
+                                        let leftover = inventory.insert(ItemStack { item, count });
+                                        server
+                                            .send(id, ToClient::PlayerInventory(inventory.clone()));
+                                        let given = count - leftover.map_or(0, |stack| stack.count);
+                                        format!("Gave {} {}", given, item_name)
+                                    }
+                                    None => format!("Unknown item: {}", item_name),
+                                }
+                            }
+                            Ok(Command::Summon(model_name)) => {
+                                match game_data.models.get_id_by_name(&model_name) {
+                                    Some(model_id) => {
+                                        let model =
+                                            game_data.models.get_value_by_id(model_id).unwrap();
+                                        let scale = SUMMONED_ENTITY_HEIGHT / model.size_y as f64;
+                                        let size = Vector3::new(
+                                            model.size_x as f64,
+                                            model.size_y as f64,
+                                            model.size_z as f64,
+                                        ) * scale;
+                                        let position = physics_simulation
+                                            .get_state()
+                                            .physics_state
+                                            .players
+                                            .get(&id)
+                                            .unwrap()
+                                            .position();
+                                        let entity =
+                                            physics_simulation.spawn_entity(position, size);
+                                        let model = EntityModel {
+                                            model: model_name.clone(),
+                                            scale: scale as f32,
+                                        };
+                                        for (&player, _) in players.iter() {
+                                            server.send(
+                                                player,
+                                                ToClient::SpawnEntity(entity, model.clone()),
+                                            );
+                                        }
+                                        entity_models.insert(entity, model);
+                                        format!("Summoned {}", model_name)
+                                    }
+                                    None => format!("Unknown model: {}", model_name),
+                                }
+                            }
+                            Ok(Command::Despawn) => {
+                                let count = entity_models.len();
+                                for (entity, _) in std::mem::take(&mut entity_models) {
+                                    physics_simulation.despawn_entity(entity);
+                                    for (&player, _) in players.iter() {
+                                        server.send(player, ToClient::DespawnEntity(entity));
+                                    }
+                                }
+                                format!("Removed {} entities", count)
+                            }
+                            Ok(Command::GameMode(game_mode)) => {
+                                let player_data = players.get_mut(&id).unwrap();
+                                player_data.game_mode = game_mode;
+                                player_data.save(&storage)?;
+                                physics_simulation.set_game_mode(id, game_mode);
+                                format!("Set the game mode to {:?}", game_mode)
+                            }
+                            Err(error) => error,
+                        };
+                        server.send(id, ToClient::CommandFeedback(feedback));
+                        if time_changed {
+                            for (&player, _) in players.iter() {
+                                server.send(player, ToClient::UpdateTime(world_metadata.time));
                             }
                         }
                     }
                     ToServer::StopServer => {
                         log::info!("Shutting down server.");
+                        storage.save_metadata(&world_metadata)?;
+                        world.save_chunks()?;
+                        for player_data in players.values() {
+                            player_data.save(&storage)?;
+                        }
                         return Ok(());
                     }
                 },
@@ -176,8 +532,150 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
         world.get_new_light_chunks();
         server_timing.record_part("Receive lighted chunks");
 
+        // Tick world
+        let mut ticks_this_iteration = 0;
+        while last_tick_time.elapsed() >= TICK_DURATION {
+            if ticks_this_iteration == MAX_TICKS_PER_ITERATION {
+                // Skip the ticks we can't catch up with
+                last_tick_time = Instant::now();
+                break;
+            }
+            last_tick_time += TICK_DURATION;
+            ticks_this_iteration += 1;
+
+            world_metadata.time.tick();
+            world_metadata.game_tick += 1;
+            world.tick(world_metadata.game_tick);
+            for (pos, block) in world.take_falling_blocks() {
+                physics_simulation.spawn_falling_block(block, pos);
+            }
+            let player_positions = physics_simulation
+                .get_state()
+                .physics_state
+                .players
+                .values()
+                .map(|player| player.position())
+                .collect::<Vec<_>>();
+            let mob_updates = mobs.tick(
+                &game_data.mobs,
+                physics_simulation.get_entities_mut(),
+                &player_positions,
+                &world,
+                TICK_DURATION.as_secs_f64(),
+            );
+            for (entity, model) in mob_updates.spawned {
+                for (&player, _) in players.iter() {
+                    server.send(player, ToClient::SpawnEntity(entity, model.clone()));
+                }
+                entity_models.insert(entity, model);
+            }
+            for entity in mob_updates.despawned {
+                for (&player, _) in players.iter() {
+                    server.send(player, ToClient::DespawnEntity(entity));
+                }
+                entity_models.remove(&entity);
+            }
+            // Damage the players whose head is inside a block or a fluid
+            for (&player, data) in players.iter_mut() {
+                if !data.game_mode.takes_damage() {
+                    continue;
+                }
+                let (head_in_block, head_in_fluid) = match physics_simulation
+                    .get_state()
+                    .physics_state
+                    .players
+                    .get(&player)
+                {
+                    Some(physics_player) => (
+                        physics_player.is_head_in_block(&world),
+                        physics_player.is_head_in_fluid(&world),
+                    ),
+                    None => continue,
+                };
+                let previous_health = data.health;
+                let damage_source =
+                    data.health
+                        .tick(TICK_DURATION.as_secs_f64(), head_in_block, head_in_fluid);
+                update_health(
+                    server.as_mut(),
+                    &mut physics_simulation,
+                    player,
+                    data,
+                    previous_health,
+                    damage_source,
+                );
+            }
+            if world_metadata.game_tick % TIME_SYNC_INTERVAL == 0 {
+                for (&player, _) in players.iter() {
+                    server.send(player, ToClient::UpdateTime(world_metadata.time));
+                }
+            }
+            if world_metadata.game_tick % AUTOSAVE_INTERVAL == 0 {
+                storage.save_metadata(&world_metadata)?;
+                world.save_chunks()?;
+            }
+        }
+        server_timing.record_part("Tick world");
+
+        // Drop the items in the world, at the center of the block that dropped them
+        for (pos, stack) in world.take_dropped_items() {
+            let drop_position = Point3::new(
+                pos.px as f64 + 0.5,
+                pos.py as f64 + 0.5,
+                pos.pz as f64 + 0.5,
+            );
+            physics_simulation.spawn_item(stack, drop_position);
+        }
+
+        // Close the containers that were broken or that are too far from their player
+        for (&player, data) in players.iter_mut() {
+            if let Some(pos) = data.open_container {
+                let is_chest =
+                    matches!(world.get_block_entity(pos), Some(BlockEntity::Chest { .. }));
+                if !is_chest || !is_close_to_block(&physics_simulation, player, pos) {
+                    data.open_container = None;
+                    server.send(player, ToClient::CloseContainer);
+                }
+            }
+        }
+
         // Tick game
-        physics_simulation.step_simulation(Instant::now(), &world);
+        let landed_blocks = physics_simulation.step_simulation(Instant::now(), &world);
+        for (pos, block) in landed_blocks {
+            world.place_landed_block(pos, block);
+        }
+        for (&player, data) in players.iter_mut() {
+            let damage = health::fall_damage(physics_simulation.take_landing_speed(player));
+            if damage > 0 && data.game_mode.takes_damage() {
+                let previous_health = data.health;
+                data.health.damage(damage);
+                update_health(
+                    server.as_mut(),
+                    &mut physics_simulation,
+                    player,
+                    data,
+                    previous_health,
+                    Some(DamageSource::Fall),
+                );
+            }
+        }
+        let mut updated_inventories = HashSet::new();
+        physics_simulation.pick_up_items(|player, stack| match players.get_mut(&player) {
+            Some(data) => {
+                let leftover = data.inventory.insert(stack);
+                if leftover != Some(stack) {
+                    updated_inventories.insert(player);
+                }
+                leftover
+            }
+            None => Some(stack),
+        });
+        for player in updated_inventories {
+            server.send(
+                player,
+                ToClient::PlayerInventory(players[&player].inventory.clone()),
+            );
+        }
         server_timing.record_part("Update physics");
 
         // Send physics updates to players
@@ -204,7 +702,7 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
             let player_chunk = player_pos.containing_chunk_pos();
             player_positions.push((player_chunk, data.render_distance));
             // Send new chunks
-            let updates = world.send_chunks_to_player(player_chunk, data);
+            let updates = world.send_chunks_to_player(player_chunk, data)?;
             for (chunk, light_chunk) in updates {
                 server.send(*player, ToClient::Chunk(chunk, light_chunk));
             }
@@ -247,12 +745,12 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
         world.enqueue_chunks_for_lighting(&close_chunks);
         server_timing.record_part("Send chunks to light worker");
 
-        // Update worldgen
-        world.enqueue_chunks_for_worldgen(&close_chunks);
+        // Load or generate chunks
+        world.enqueue_chunks_for_worldgen(&close_chunks)?;
         server_timing.record_part("Send chunks to worldgen worker");
 
         // Drop chunks that are far from all players
-        world.drop_far_chunks(&player_positions);
+        world.drop_far_chunks(&player_positions)?;
         server_timing.record_part("Drop far chunks");
 
         send_debug_info(
@@ -264,6 +762,15 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
                 world.num_loaded_chunk_columns(),
             ),
         );
+        send_debug_info(
+            "World",
+            "ticks",
+            format!(
+                "Scheduled block ticks = {}\nPending neighbor updates = {}",
+                world.num_scheduled_ticks(),
+                world.num_neighbor_updates(),
+            ),
+        );
 
         // Nothing else to do for now :-)
         send_perf_breakdown(
@@ -275,6 +782,88 @@ pub fn launch_server(mut server: Box<dyn Server>) -> Result<()> {
     }
 }
 
+/// Return true if the camera of a player is close enough to the block at `pos` to open it or to see its block entity
+fn is_close_to_block(
+    physics_simulation: &ServerPhysicsSimulation,
+    id: PlayerId,
+    pos: BlockPos,
+) -> bool {
+    physics_simulation
+        .get_state()
+        .physics_state
+        .players
+        .get(&id)
+        .map_or(false, |physics_player| {
+            let center = Point3::new(
+                pos.px as f64 + 0.5,
+                pos.py as f64 + 0.5,
+                pos.pz as f64 + 0.5,
+            );
+            (physics_player.get_camera_position() - center).norm() <= CONTAINER_MAX_DISTANCE
+        })
+}
+
+/// Respawn a player at the world spawn if `damage_source` killed it, and send its health if it changed
+fn update_health(
+    server: &mut dyn Server,
+    physics_simulation: &mut ServerPhysicsSimulation,
+    id: PlayerId,
+    player_data: &mut PlayerData,
+    previous_health: Health,
+    damage_source: Option<DamageSource>,
+) {
+    let health = &mut player_data.health;
+    if health.is_dead() {
+        physics_simulation.respawn_player(id);
+        *health = Health::new(MAX_HEALTH);
+        if let Some(source) = damage_source {
+            server.send(id, ToClient::Died(source));
+        }
+    }
+    if health.health != previous_health.health || (health.air - previous_health.air).abs() > 1e-9 {
+        server.send(id, ToClient::UpdateHealth(*health));
+    }
+}
+
+/// Move a stack between two slots of the inventories of a player and of the container they opened.
+/// Return false if one of the slots doesn't exist.
+fn move_stack(
+    player_data: &mut PlayerData,
+    mut container: Option<&mut Inventory>,
+    from: SlotRef,
+    to: SlotRef,
+) -> bool {
+    fn slot_inventory<'a>(
+        kind: InventoryKind,
+        player_data: &'a mut PlayerData,
+        container: &'a mut Option<&mut Inventory>,
+    ) -> Option<&'a mut Inventory> {
+        match kind {
+            InventoryKind::Player => Some(&mut player_data.inventory),
+            InventoryKind::CraftingGrid => Some(&mut player_data.crafting_grid),
+            InventoryKind::Container => container.as_deref_mut(),
+        }
+    }
+
+    let mut get = |slot: SlotRef| {
+        slot_inventory(slot.inventory, player_data, &mut container)
+            .filter(|inventory| slot.slot < inventory.size())
+            .map(|inventory| inventory.get(slot.slot))
+    };
+    let (from_stack, to_stack) = match (get(from), get(to)) {
+        (Some(from_stack), Some(to_stack)) => inventory::move_stack(from_stack, to_stack),
+        _ => return false,
+    };
+    if from != to {
+        for &(slot, stack) in &[(from, from_stack), (to, to_stack)] {
+            slot_inventory(slot.inventory, player_data, &mut container)
+                .expect("The slot exists")
+                .set(slot.slot, stack);
+        }
+    }
+    true
+}
+
 #[derive(Clone, Copy)]
 struct CloseChunkPos {
     square_dist: u64,
@@ -1,10 +1,29 @@
 use std::sync::Arc;
-use voxel_rs_common::world::{Chunk, CHUNK_SIZE};
+use voxel_rs_common::{
+    block::Block,
+    registry::Registry,
+    world::{Chunk, CHUNK_SIZE},
+};
 
 mod sunlight;
 pub mod worker;
 
-/// This data structure contains the y position of the highest opaque block
+/// Build the table of the light attenuation of every block, indexed by block id.
+pub fn light_attenuation_table(block_registry: &Registry<Block>) -> Arc<Vec<u8>> {
+    Arc::new(
+        (0..block_registry.get_number_of_ids())
+            .map(|id| {
+                block_registry
+                    .get_value_by_id(id)
+                    .expect("Block registry ids are contiguous")
+                    .light_attenuation()
+            })
+            .collect(),
+    )
+}
+
+/// This data structure contains the y position of the highest opaque block.
+/// Every block that attenuates light, for example a fluid, is considered opaque here because sunlight can't go straight through it.
 #[derive(Clone)]
 pub struct HighestOpaqueBlock {
     pub y: [i64; (CHUNK_SIZE * CHUNK_SIZE) as usize],
@@ -17,15 +36,14 @@ impl HighestOpaqueBlock {
         }
     }
 
-    pub fn from_chunk(chunk: &Arc<Chunk>) -> Self {
+    pub fn from_chunk(chunk: &Arc<Chunk>, light_attenuation: &[u8]) -> Self {
         let mut hob = Self {
             y: [i64::MIN; (CHUNK_SIZE * CHUNK_SIZE) as usize],
         };
         for i in 0..CHUNK_SIZE {
             for k in 0..CHUNK_SIZE {
                 for j in (0..CHUNK_SIZE).rev() {
-                    // TODO: use BlockRegistry
-                    if chunk.get_block_at((i, j, k)) != 0 {
+                    if light_attenuation[chunk.get_block_at((i, j, k)) as usize] > 0 {
                         hob.y[(i * CHUNK_SIZE + k) as usize] =
                             j as i64 + chunk.pos.py * CHUNK_SIZE as i64;
                         break;
@@ -17,15 +17,18 @@ impl LightData {
 }
 
 /// Take a 3x3x3 chunks bloc and 3x3 HighestOpaqueBlock and compute the light by using a BFS
+///
+/// `light_attenuation` is the light attenuation of every block, indexed by block id.
 pub fn compute_light(
     chunks: Vec<Option<Arc<Chunk>>>,
     highest_opaque_blocks: Vec<Arc<HighestOpaqueBlock>>,
+    light_attenuation: &[u8],
     queue: &mut FastBFSQueue,
     light_data: &mut [u8],
-    opaque: &mut [bool],
+    attenuation: &mut [u8],
 ) -> LightData {
     assert!(light_data.len() >= (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize);
-    assert!(opaque.len() >= (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize);
+    assert!(attenuation.len() >= (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize);
     let mut res = LightData::new();
     queue.clear();
 
@@ -77,7 +80,7 @@ pub fn compute_light(
                                         let s = (*cx * csize + i as usize) * csize * csize * 9
                                             + (*cy * csize + j as usize) * csize * 3
                                             + (*cz * csize + k as usize);
-                                        *opaque.get_unchecked_mut(s) = false;
+                                        *attenuation.get_unchecked_mut(s) = 0;
                                         if (y0 + *cy as i64 - 1) * CHUNK_SIZE as i64 + j as i64
                                             > *highest_opaque_block
                                                 .y
@@ -104,11 +107,13 @@ pub fn compute_light(
                                         let s = (*cx * csize + i as usize) * csize * csize * 9
                                             + (*cy * csize + j as usize) * csize * 3
                                             + (*cz * csize + k as usize);
-                                        if c.get_block_at_unsafe((i, j, k)) != 0 {
-                                            // TODO : replace by is opaque
-                                            *opaque.get_unchecked_mut(s) = true;
+                                        let block_attenuation = *light_attenuation.get_unchecked(
+                                            c.get_block_at_unsafe((i, j, k)) as usize,
+                                        );
+                                        *attenuation.get_unchecked_mut(s) = block_attenuation;
+                                        if block_attenuation >= MAX_LIGHT as u8 {
+                                            *light_data.get_unchecked_mut(s) = 0;
                                         } else {
-                                            *opaque.get_unchecked_mut(s) = false;
                                             if c.pos.py * CHUNK_SIZE as i64 + j as i64
                                                 > *highest_opaque_block
                                                     .y
@@ -157,23 +162,23 @@ pub fn compute_light(
                     let s = (nx as usize) * csize * csize * 9
                         + (ny as usize) * csize * 3
                         + (nz as usize);
-                    if *opaque.get_unchecked(s as usize) {
+                    let block_attenuation = *attenuation.get_unchecked(s as usize);
+                    if block_attenuation >= MAX_LIGHT as u8 {
                         continue;
                     }
+                    let new_light = ll.saturating_sub(1 + block_attenuation);
                     let ref_light = light_data.get_unchecked_mut(s as usize);
-                    if *ref_light < ll - 1 {
-                        *ref_light = ll - 1;
-                        if ll > 1 {
-                            queue.push((nx as usize, ny as usize, nz as usize, ll - 1));
+                    if *ref_light < new_light {
+                        let was_dark = *ref_light == 0;
+                        *ref_light = new_light;
+                        if new_light > 1 {
+                            queue.push((nx as usize, ny as usize, nz as usize, new_light));
                         }
-                        if nx as usize / csize == 1
+                        // Blocks can be lit multiple times when light is attenuated, only count them once
+                        if was_dark
+                            && nx as usize / csize == 1
                             && ny as usize / csize == 1
                             && nz as usize / csize == 1
-                            && !*opaque.get_unchecked(
-                                nx as usize * csize * csize * 9
-                                    + ny as usize * csize * 3
-                                    + nz as usize,
-                            )
                         {
                             transparent_count -= 1;
                         }
@@ -7,36 +7,47 @@ use voxel_rs_common::{
     world::{Chunk, LightChunk, CHUNK_SIZE},
 };
 
+/// Size of the lighting queue, per thread
 static LIGHTING_QUEUE_SIZE: usize = 20;
 
-pub fn start_lighting_worker() -> ChunkLightingWorker {
-    Worker::new(
-        ChunkLightingState::new(),
+/// Start a lighting worker running on `thread_count` threads
+pub fn start_lighting_worker(
+    light_attenuation: Arc<Vec<u8>>,
+    thread_count: usize,
+) -> ChunkLightingWorker {
+    let thread_count = thread_count.max(1);
+    Worker::new_pool(
+        (0..thread_count)
+            .map(|_| ChunkLightingState::new(light_attenuation.clone()))
+            .collect(),
         LIGHTING_QUEUE_SIZE,
         "Light".into(),
     )
 }
 
 /// The chunk-specific data that is needed to generate light for it.
+#[derive(Clone)]
 pub struct ChunkLightingData {
     pub chunks: Vec<Option<Arc<Chunk>>>,
     pub highest_opaque_blocks: Vec<Arc<HighestOpaqueBlock>>,
 }
 
 pub struct ChunkLightingState {
+    light_attenuation: Arc<Vec<u8>>,
     queue_reuse: FastBFSQueue,
     light_data_reuse: Vec<u8>,
-    opaque_reuse: Vec<bool>,
+    attenuation_reuse: Vec<u8>,
 }
 
 impl ChunkLightingState {
-    pub(self) fn new() -> Self {
+    pub(self) fn new(light_attenuation: Arc<Vec<u8>>) -> Self {
         Self {
+            light_attenuation,
             queue_reuse: FastBFSQueue::new(),
             light_data_reuse: unsafe {
                 zero_initialized_vec((CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize)
             },
-            opaque_reuse: unsafe {
+            attenuation_reuse: unsafe {
                 zero_initialized_vec((CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 27) as usize)
             },
         }
@@ -53,9 +64,10 @@ impl WorkerState<ChunkLightingData, Arc<LightChunk>> for ChunkLightingState {
             light: compute_light(
                 data.chunks,
                 data.highest_opaque_blocks,
+                &self.light_attenuation,
                 &mut self.queue_reuse,
                 &mut self.light_data_reuse,
-                &mut self.opaque_reuse,
+                &mut self.attenuation_reuse,
             )
             .light_level
             .to_vec(),
@@ -65,3 +77,163 @@ impl WorkerState<ChunkLightingData, Arc<LightChunk>> for ChunkLightingState {
 }
 
 pub type ChunkLightingWorker = Worker<ChunkLightingData, Arc<LightChunk>, ChunkLightingState>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use voxel_rs_common::{block::OPAQUE_LIGHT_ATTENUATION, world::ChunkPos};
+
+    /// Light attenuation of air, an opaque block and water
+    fn test_light_attenuation() -> Arc<Vec<u8>> {
+        Arc::new(vec![0, OPAQUE_LIGHT_ATTENUATION, 2])
+    }
+
+    /// Compute the 3x3 highest opaque blocks of 3x3x3 chunks
+    fn highest_opaque_blocks(chunks: &[Option<Arc<Chunk>>]) -> Vec<Arc<HighestOpaqueBlock>> {
+        let light_attenuation = test_light_attenuation();
+        let mut highest_opaque_blocks = Vec::with_capacity(9);
+        for cx in 0..3 {
+            for cz in 0..3 {
+                let mut hob = HighestOpaqueBlock::new();
+                for cy in 0..3 {
+                    hob.merge(&HighestOpaqueBlock::from_chunk(
+                        chunks[cx * 9 + cy * 3 + cz].as_ref().unwrap(),
+                        &light_attenuation,
+                    ));
+                }
+                highest_opaque_blocks.push(Arc::new(hob));
+            }
+        }
+        highest_opaque_blocks
+    }
+
+    /// Create the lighting data for the chunk at the origin, in some hilly terrain with a few holes
+    fn synthetic_lighting_data() -> ChunkLightingData {
+        let csize = CHUNK_SIZE as i64;
+        let mut chunks = Vec::with_capacity(27);
+        for cx in -1..=1 {
+            for cy in -1..=1 {
+                for cz in -1..=1 {
+                    let mut chunk = Chunk::new(ChunkPos::from((cx, cy, cz)));
+                    for i in 0..CHUNK_SIZE {
+                        for k in 0..CHUNK_SIZE {
+                            let x = cx * csize + i as i64;
+                            let z = cz * csize + k as i64;
+                            let height = ((x * 7 + z * 13).rem_euclid(24)) - 4;
+                            for j in 0..CHUNK_SIZE {
+                                let y = cy * csize + j as i64;
+                                if y < height && (x + y + z).rem_euclid(11) != 0 {
+                                    chunk.set_block_at((i, j, k), 1);
+                                }
+                            }
+                        }
+                    }
+                    chunks.push(Some(Arc::new(chunk)));
+                }
+            }
+        }
+        let highest_opaque_blocks = highest_opaque_blocks(&chunks);
+        ChunkLightingData {
+            chunks,
+            highest_opaque_blocks,
+        }
+    }
+
+    /// Wait until `count` results have been received from the worker
+    fn receive_results(worker: &ChunkLightingWorker, count: usize) -> Vec<Arc<LightChunk>> {
+        let mut results = Vec::with_capacity(count);
+        while results.len() < count {
+            match worker.get_result() {
+                Some(light_chunk) => results.push(light_chunk),
+                None => std::thread::yield_now(),
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn test_pool_matches_single_thread() {
+        let data = synthetic_lighting_data();
+        let expected = ChunkLightingState::new(test_light_attenuation()).compute(data.clone());
+
+        let worker = start_lighting_worker(test_light_attenuation(), 3);
+        for _ in 0..6 {
+            assert!(worker.enqueue(data.clone()).is_ok());
+        }
+        for light_chunk in receive_results(&worker, 6) {
+            assert_eq!(light_chunk.pos, expected.pos);
+            assert_eq!(light_chunk.light, expected.light);
+        }
+    }
+
+    #[test]
+    fn test_fluid_attenuates_light() {
+        // A horizontal layer of water from y = 10 to y = 19 in the middle chunks
+        let mut chunks = Vec::with_capacity(27);
+        for cx in -1..=1 {
+            for cy in -1..=1 {
+                for cz in -1..=1 {
+                    let mut chunk = Chunk::new(ChunkPos::from((cx, cy, cz)));
+                    if cy == 0 {
+                        for i in 0..CHUNK_SIZE {
+                            for j in 10..20 {
+                                for k in 0..CHUNK_SIZE {
+                                    chunk.set_block_at((i, j, k), 2);
+                                }
+                            }
+                        }
+                    }
+                    chunks.push(Some(Arc::new(chunk)));
+                }
+            }
+        }
+        let highest_opaque_blocks = highest_opaque_blocks(&chunks);
+        let light_chunk =
+            ChunkLightingState::new(test_light_attenuation()).compute(ChunkLightingData {
+                chunks,
+                highest_opaque_blocks,
+            });
+
+        assert_eq!(light_chunk.get_light_at((5, 20, 5)), 15);
+        assert_eq!(light_chunk.get_light_at((5, 19, 5)), 12);
+        assert_eq!(light_chunk.get_light_at((5, 18, 5)), 9);
+        assert_eq!(light_chunk.get_light_at((5, 15, 5)), 0);
+    }
+
+    /// Measure the number of lit chunks per second for different thread counts.
+    /// Run with `cargo test -p voxel-rs-server --release -- --ignored --nocapture bench_lighting_throughput`.
+    #[test]
+    #[ignore]
+    fn bench_lighting_throughput() {
+        const BENCH_DURATION: Duration = Duration::from_secs(3);
+        let _ = env_logger::builder()
+            .is_test(true)
+            .filter_level(log::LevelFilter::Info)
+            .try_init();
+        let data = synthetic_lighting_data();
+
+        for &thread_count in &[1, 2, 4, 8] {
+            let worker = start_lighting_worker(test_light_attenuation(), thread_count);
+            let mut enqueued = 0;
+            let mut lit = 0;
+            let start = Instant::now();
+            while start.elapsed() < BENCH_DURATION {
+                while worker.enqueue(data.clone()).is_ok() {
+                    enqueued += 1;
+                }
+                while worker.get_result().is_some() {
+                    lit += 1;
+                }
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+            // Don't leave chunks in the worker queue
+            receive_results(&worker, enqueued - lit);
+            log::info!(
+                "{} light thread(s): {:.1} lit chunks/s",
+                thread_count,
+                lit as f64 / elapsed
+            );
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! The behavior of the mobs.
+//!
+//! Mobs are entities of the physics simulation. Every tick, the server:
+//! * forgets the mobs whose entity was removed, and despawns the mobs that are far from all players,
+//! * lets every mob that is idle or whose decision delay expired pick a goal according to its behavior,
+//!   and find a path to it,
+//! * steers every mob along its path by setting the velocity of its entity,
+//! * sometimes spawns a new mob around a random player, on the ground and with the light its type requires.
+use crate::mob::pathfinding::{find_path, MovementRules};
+use nalgebra::{Point3, Vector3};
+use std::collections::BTreeMap;
+use voxel_rs_common::block::BlockId;
+use voxel_rs_common::entity::{Entities, EntityId, EntityModel};
+use voxel_rs_common::mob::{Mob, MobBehavior};
+use voxel_rs_common::physics::BlockContainer;
+use voxel_rs_common::random::Rng;
+use voxel_rs_common::world::BlockPos;
+
+mod pathfinding;
+
+/// Maximum number of mobs in the world
+const MAX_MOBS: usize = 20;
+/// Number of ticks between two spawn attempts
+const SPAWN_INTERVAL: u64 = 20;
+/// Mobs spawn at least this far from every player
+const MIN_SPAWN_DISTANCE: f64 = 16.0;
+/// Mobs spawn at most this far from the player they spawn around
+const MAX_SPAWN_DISTANCE: f64 = 32.0;
+/// Mobs spawn at most this many blocks above or below the player they spawn around
+const SPAWN_HEIGHT_RANGE: i64 = 16;
+/// Mobs further than this from every player despawn
+const DESPAWN_DISTANCE: f64 = 64.0;
+/// Minimum number of ticks between two decisions of a mob. The actual delay is up to twice as long.
+const DECISION_INTERVAL: u64 = 40;
+/// Mobs following a player notice players closer than this
+const FOLLOW_RANGE: f64 = 16.0;
+/// Mobs following a player stop when they are this close to it
+const FOLLOW_DISTANCE: f64 = 2.0;
+/// Mobs fleeing players notice players closer than this
+const FLEE_RANGE: f64 = 8.0;
+/// Mobs fleeing a player try to go this far from it
+const FLEE_DISTANCE: f64 = 12.0;
+/// Maximum distance between a wandering mob and its goal, along each axis
+const WANDER_RANGE: i64 = 8;
+/// Probability that a wandering mob stays where it is instead of walking somewhere
+const IDLE_PROBABILITY: f64 = 0.5;
+/// Maximum number of blocks explored when looking for a path
+const MAX_PATH_NODES: usize = 400;
+/// Maximum number of blocks a mob is willing to drop
+const MAX_DROP: i64 = 3;
+/// Vertical speed of the mobs when they jump to climb a block
+const JUMP_SPEED: f64 = 8.0;
+/// A waypoint is reached when the mob is this close to its center horizontally
+const WAYPOINT_DISTANCE: f64 = 0.2;
+
+/// The world as seen by the mobs
+pub trait MobWorld: BlockContainer {
+    /// Return the base id of the block at `pos`
+    fn get_base_block(&self, pos: BlockPos) -> BlockId;
+    /// Return the light level at `pos`
+    fn get_light(&self, pos: BlockPos) -> u8;
+}
+
+/// The state of a mob
+#[derive(Debug, Clone)]
+struct MobState {
+    /// The index of the type of the mob
+    mob: usize,
+    /// The blocks the mob still has to walk through
+    path: Vec<BlockPos>,
+    /// Number of ticks before the mob picks a new goal
+    decision_delay: u64,
+}
+
+/// The mobs that spawned and despawned during one tick
+#[derive(Debug, Default)]
+pub struct MobUpdates {
+    pub spawned: Vec<(EntityId, EntityModel)>,
+    pub despawned: Vec<EntityId>,
+}
+
+/// The mobs in the world
+pub struct Mobs {
+    mobs: BTreeMap<EntityId, MobState>,
+    /// The number of ticks since the server started
+    ticks: u64,
+    rng: Rng,
+}
+
+impl Mobs {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            mobs: BTreeMap::new(),
+            ticks: 0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Update the mobs. `mob_types` are the types of the mobs, `players` are the positions of the players.
+    pub fn tick<W: MobWorld>(
+        &mut self,
+        mob_types: &[Mob],
+        entities: &mut Entities,
+        players: &[Point3<f64>],
+        world: &W,
+        seconds_delta: f64,
+    ) -> MobUpdates {
+        let mut updates = MobUpdates::default();
+        self.ticks += 1;
+
+        // Forget the mobs that were removed, and despawn the mobs that are far from all players
+        self.mobs.retain(|&id, _| entities.contains(id));
+        for (&id, _) in self.mobs.iter() {
+            let position = *entities.positions.get(id).unwrap();
+            let is_far = players
+                .iter()
+                .all(|player| (player - position).norm() > DESPAWN_DISTANCE);
+            if is_far {
+                entities.despawn(id);
+                updates.despawned.push(id);
+            }
+        }
+        self.mobs.retain(|&id, _| entities.contains(id));
+
+        // Choose goals and move
+        let Self { mobs, rng, .. } = self;
+        for (&id, state) in mobs.iter_mut() {
+            let mob = &mob_types[state.mob];
+            let rules = movement_rules(mob);
+            let position = *entities.positions.get(id).unwrap();
+            state.decision_delay = state.decision_delay.saturating_sub(1);
+            // Only decide on the ground, where the mob stands on a block
+            if state.decision_delay == 0 && entities.is_on_ground(id, world) {
+                state.decision_delay = DECISION_INTERVAL + rng.gen_range(DECISION_INTERVAL);
+                let start = BlockPos::from(position);
+                let closest_player = players
+                    .iter()
+                    .min_by(|a, b| {
+                        let (a, b) = ((*a - position).norm(), (*b - position).norm());
+                        a.partial_cmp(&b).unwrap()
+                    })
+                    .copied();
+                state.path = choose_goal(mob.behavior, position, closest_player, rng)
+                    .and_then(|goal| find_path(world, start, goal, &rules, MAX_PATH_NODES))
+                    .unwrap_or_default();
+            }
+            steer(state, mob, id, entities, world, seconds_delta);
+        }
+
+        // Spawn new mobs
+        if self.ticks % SPAWN_INTERVAL == 0 {
+            if let Some(spawned) = self.try_spawn(mob_types, entities, players, world) {
+                updates.spawned.push(spawned);
+            }
+        }
+
+        updates
+    }
+
+    /// Try to spawn a random mob around a random player
+    fn try_spawn<W: MobWorld>(
+        &mut self,
+        mob_types: &[Mob],
+        entities: &mut Entities,
+        players: &[Point3<f64>],
+        world: &W,
+    ) -> Option<(EntityId, EntityModel)> {
+        if mob_types.is_empty() || players.is_empty() || self.mobs.len() >= MAX_MOBS {
+            return None;
+        }
+        let player = players[self.rng.gen_range(players.len() as u64) as usize];
+        let mob_index = self.rng.gen_range(mob_types.len() as u64) as usize;
+        let mob = &mob_types[mob_index];
+        let angle = self.rng.gen_f64() * 2.0 * std::f64::consts::PI;
+        // The mob spawns at the center of the block, which can be up to one block closer
+        let min_distance = MIN_SPAWN_DISTANCE + 1.0;
+        let distance = min_distance + self.rng.gen_f64() * (MAX_SPAWN_DISTANCE - min_distance);
+        let column = BlockPos::from(
+            player + Vector3::new(angle.cos() * distance, 0.0, angle.sin() * distance),
+        );
+
+        // Find the highest block where the mob can stand
+        let rules = movement_rules(mob);
+        let pos = (-SPAWN_HEIGHT_RANGE..=SPAWN_HEIGHT_RANGE)
+            .rev()
+            .map(|dy| column.offset(0, dy, 0))
+            .find(|&pos| rules.can_stand_at(world, pos))?;
+        let ground = world.get_base_block(pos.offset(0, -1, 0));
+        if !mob.spawn.allows(ground, world.get_light(pos)) {
+            return None;
+        }
+        let position = Point3::new(pos.px as f64 + 0.5, pos.py as f64, pos.pz as f64 + 0.5);
+        if players
+            .iter()
+            .any(|player| (player - position).norm() < MIN_SPAWN_DISTANCE)
+        {
+            return None;
+        }
+
+        let id = entities.spawn(position);
+        entities.velocities.insert(id, Vector3::zeros());
+        entities
+            .colliders
+            .insert(id, Vector3::new(mob.width, mob.height, mob.width));
+        entities.yaws.insert(id, self.rng.gen_f64() * 360.0);
+        self.mobs.insert(
+            id,
+            MobState {
+                mob: mob_index,
+                path: Vec::new(),
+                decision_delay: 0,
+            },
+        );
+        let model = EntityModel {
+            model: mob.model.clone(),
+            scale: mob.scale,
+        };
+        Some((id, model))
+    }
+}
+
+fn movement_rules(mob: &Mob) -> MovementRules {
+    MovementRules {
+        height: mob.height.ceil() as i64,
+        max_drop: MAX_DROP,
+    }
+}
+
+/// Choose where a mob at `position` goes, given the position of the closest player.
+/// Return `None` if the mob stays where it is.
+fn choose_goal(
+    behavior: MobBehavior,
+    position: Point3<f64>,
+    closest_player: Option<Point3<f64>>,
+    rng: &mut Rng,
+) -> Option<BlockPos> {
+    let start = BlockPos::from(position);
+    let player = closest_player.map(|player| (player, (player - position).norm()));
+    let away_from =
+        |player: Point3<f64>| Vector3::new(position.x - player.x, 0.0, position.z - player.z);
+    match (behavior, player) {
+        (MobBehavior::Follow, Some((player, distance))) if distance <= FOLLOW_RANGE => {
+            if distance <= FOLLOW_DISTANCE {
+                None
+            } else {
+                Some(BlockPos::from(player))
+            }
+        }
+        (MobBehavior::Flee, Some((player, distance)))
+            if distance <= FLEE_RANGE && away_from(player).norm() > 0.0 =>
+        {
+            let goal = player + away_from(player).normalize() * FLEE_DISTANCE;
+            Some(BlockPos::from(Point3::new(goal.x, position.y, goal.z)))
+        }
+        _ => {
+            if rng.gen_f64() < IDLE_PROBABILITY {
+                None
+            } else {
+                let mut offset =
+                    || rng.gen_range(2 * WANDER_RANGE as u64 + 1) as i64 - WANDER_RANGE;
+                Some(start.offset(offset(), 0, offset()))
+            }
+        }
+    }
+}
+
+/// Set the velocity and the yaw of the entity of a mob so it walks along its path
+fn steer<BC: BlockContainer>(
+    state: &mut MobState,
+    mob: &Mob,
+    id: EntityId,
+    entities: &mut Entities,
+    world: &BC,
+    seconds_delta: f64,
+) {
+    let position = *entities.positions.get(id).unwrap();
+    let horizontal_offset = |waypoint: BlockPos| {
+        Vector3::new(
+            waypoint.px as f64 + 0.5 - position.x,
+            0.0,
+            waypoint.pz as f64 + 0.5 - position.z,
+        )
+    };
+    // Skip the waypoints that were reached. Waypoints above the mob are only reached after climbing.
+    while let Some(&waypoint) = state.path.first() {
+        if horizontal_offset(waypoint).norm() < WAYPOINT_DISTANCE
+            && position.y > waypoint.py as f64 - 0.5
+        {
+            state.path.remove(0);
+        } else {
+            break;
+        }
+    }
+
+    let on_ground = entities.is_on_ground(id, world);
+    let velocity = entities.velocities.get_mut(id).unwrap();
+    match state.path.first() {
+        Some(&waypoint) => {
+            let offset = horizontal_offset(waypoint);
+            // Don't overshoot the waypoint
+            let speed = mob.speed.min(offset.norm() / seconds_delta);
+            let horizontal_velocity =
+                offset.try_normalize(1e-9).unwrap_or_else(Vector3::zeros) * speed;
+            velocity.x = horizontal_velocity.x;
+            velocity.z = horizontal_velocity.z;
+            if on_ground && waypoint.py as f64 > position.y + 0.5 {
+                velocity.y = JUMP_SPEED;
+            }
+            entities
+                .yaws
+                .insert(id, (-offset.x).atan2(-offset.z).to_degrees());
+        }
+        None => {
+            velocity.x = 0.0;
+            velocity.z = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use voxel_rs_common::mob::SpawnRule;
+    use voxel_rs_common::physics::test_world::TestWorld;
+
+    const SECONDS_DELTA: f64 = 0.05;
+    const GRASS: BlockId = 1;
+
+    /// The test world is lit everywhere, and its solid blocks are grass
+    impl MobWorld for TestWorld {
+        fn get_base_block(&self, pos: BlockPos) -> BlockId {
+            if self.is_block_full(pos) {
+                GRASS
+            } else {
+                0
+            }
+        }
+
+        fn get_light(&self, _pos: BlockPos) -> u8 {
+            15
+        }
+    }
+
+    /// A flat grass ground at y = 0, large enough for the mobs to spawn on it
+    fn ground() -> TestWorld {
+        TestWorld::floor(MAX_SPAWN_DISTANCE as i64 + 8)
+    }
+
+    fn mob(behavior: MobBehavior) -> Mob {
+        Mob {
+            name: "knight".to_owned(),
+            model: "knight".to_owned(),
+            scale: 0.1,
+            height: 1.8,
+            width: 0.6,
+            speed: 3.0,
+            behavior,
+            spawn: SpawnRule {
+                blocks: vec![GRASS],
+                min_light: 10,
+                max_light: 15,
+            },
+        }
+    }
+
+    #[test]
+    fn test_goals_depend_on_the_behavior() {
+        let mut rng = Rng::new(42);
+        let position = Point3::new(0.5, 1.0, 0.5);
+        let player = Point3::new(5.5, 1.0, 0.5);
+        assert_eq!(
+            choose_goal(MobBehavior::Follow, position, Some(player), &mut rng),
+            Some(BlockPos::from((5, 1, 0)))
+        );
+        let close_player = Point3::new(1.5, 1.0, 0.5);
+        assert_eq!(
+            choose_goal(MobBehavior::Follow, position, Some(close_player), &mut rng),
+            None
+        );
+        let goal = choose_goal(MobBehavior::Flee, position, Some(player), &mut rng).unwrap();
+        assert_eq!(goal, BlockPos::from((-7, 1, 0)));
+        // Without a player nearby, the mobs wander
+        let far_player = Point3::new(100.0, 1.0, 0.5);
+        for behavior in [MobBehavior::Follow, MobBehavior::Flee, MobBehavior::Wander].iter() {
+            for _ in 0..20 {
+                if let Some(goal) = choose_goal(*behavior, position, Some(far_player), &mut rng) {
+                    assert_eq!(goal.py, 1);
+                    assert!(goal.px.abs() <= WANDER_RANGE && goal.pz.abs() <= WANDER_RANGE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mobs_spawn_on_the_right_ground_and_light() {
+        let players = vec![Point3::new(0.5, 1.0, 0.5)];
+        let mut entities = Entities::default();
+        let world = ground();
+
+        // The mob only spawns in the dark
+        let mut dark_mob = mob(MobBehavior::Wander);
+        dark_mob.spawn.min_light = 0;
+        dark_mob.spawn.max_light = 5;
+        let mob_types = vec![dark_mob];
+        let mut mobs = Mobs::new(1);
+        for _ in 0..10 * SPAWN_INTERVAL {
+            let updates = mobs.tick(&mob_types, &mut entities, &players, &world, SECONDS_DELTA);
+            assert!(updates.spawned.is_empty());
+        }
+
+        let mob_types = vec![mob(MobBehavior::Wander)];
+        let mut spawned = Vec::new();
+        for _ in 0..10 * SPAWN_INTERVAL {
+            let updates = mobs.tick(&mob_types, &mut entities, &players, &world, SECONDS_DELTA);
+            spawned.extend(updates.spawned);
+        }
+        assert_eq!(spawned.len(), 10);
+        for (id, model) in spawned {
+            assert_eq!(model.model, "knight");
+            let position = entities.positions.get(id).unwrap();
+            let distance = (position - players[0]).norm();
+            assert!((MIN_SPAWN_DISTANCE..=MAX_SPAWN_DISTANCE + 1.0).contains(&distance));
+            assert!(position.y >= 1.0 && position.y < 1.5);
+        }
+    }
+
+    #[test]
+    fn test_far_mobs_despawn() {
+        let mob_types = vec![mob(MobBehavior::Wander)];
+        let mut entities = Entities::default();
+        let world = ground();
+        let mut mobs = Mobs::new(1);
+        let mut players = vec![Point3::new(0.5, 1.0, 0.5)];
+        let mut spawned = Vec::new();
+        for _ in 0..SPAWN_INTERVAL {
+            let updates = mobs.tick(&mob_types, &mut entities, &players, &world, SECONDS_DELTA);
+            spawned.extend(updates.spawned);
+        }
+        assert_eq!(spawned.len(), 1);
+        let id = spawned[0].0;
+
+        players[0].x += 200.0;
+        let updates = mobs.tick(&mob_types, &mut entities, &players, &world, SECONDS_DELTA);
+        assert_eq!(updates.despawned, vec![id]);
+        assert!(!entities.contains(id));
+    }
+
+    /// Let a mob follow a path and return its final position
+    fn walk(world: &TestWorld, start: BlockPos, goal: BlockPos) -> Point3<f64> {
+        let mob = mob(MobBehavior::Wander);
+        let mut entities = Entities::default();
+        let id = entities.spawn(Point3::new(
+            start.px as f64 + 0.5,
+            start.py as f64,
+            start.pz as f64 + 0.5,
+        ));
+        entities.velocities.insert(id, Vector3::zeros());
+        entities
+            .colliders
+            .insert(id, Vector3::new(mob.width, mob.height, mob.width));
+        let mut state = MobState {
+            mob: 0,
+            path: find_path(world, start, goal, &movement_rules(&mob), MAX_PATH_NODES).unwrap(),
+            decision_delay: 0,
+        };
+        for _ in 0..200 {
+            steer(&mut state, &mob, id, &mut entities, world, SECONDS_DELTA);
+            entities.step(SECONDS_DELTA, world);
+        }
+        assert!(state.path.is_empty());
+        *entities.positions.get(id).unwrap()
+    }
+
+    #[test]
+    fn test_mobs_walk_along_their_path() {
+        let world = ground();
+        let position = walk(&world, (0, 1, 0).into(), (5, 1, 3).into());
+        assert!((position - Point3::new(5.5, 1.0, 3.5)).norm() < 0.3);
+
+        // Climb a step
+        let mut world = ground();
+        for z in -5..=5 {
+            world.solid.insert((2, 1, z).into());
+        }
+        let position = walk(&world, (0, 1, 0).into(), (2, 2, 0).into());
+        assert!((position - Point3::new(2.5, 2.0, 0.5)).norm() < 0.3);
+    }
+}
@@ -0,0 +1,312 @@
+//! A* pathfinding for the mobs.
+//!
+//! The nodes of the search are the blocks where a mob can stand: the block below is solid, and the blocks
+//! of the body of the mob are neither solid nor fluid. From a node, a mob can walk to the four horizontal
+//! neighbors, climb one block or drop a few blocks.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use voxel_rs_common::physics::BlockContainer;
+use voxel_rs_common::world::BlockPos;
+
+const HORIZONTAL_NEIGHBORS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+/// Cost of walking to a neighbor
+const MOVE_COST: u32 = 10;
+/// Additional cost of climbing one block
+const STEP_UP_COST: u32 = 5;
+/// Additional cost of dropping, per block
+const DROP_COST: u32 = 2;
+
+/// How a mob can move in the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovementRules {
+    /// The number of blocks the body of the mob goes through
+    pub height: i64,
+    /// The maximum number of blocks the mob is willing to drop
+    pub max_drop: i64,
+}
+
+impl MovementRules {
+    /// Return true if the block at `pos` doesn't block a mob and is not a fluid
+    fn is_free<BC: BlockContainer>(world: &BC, pos: BlockPos) -> bool {
+        world.collision_boxes(pos).is_empty() && world.get_fluid_state(pos).is_none()
+    }
+
+    /// Return true if the body of a mob standing at `pos` fits
+    fn is_body_free<BC: BlockContainer>(&self, world: &BC, pos: BlockPos) -> bool {
+        (0..self.height).all(|dy| Self::is_free(world, pos.offset(0, dy, 0)))
+    }
+
+    /// Return true if a mob can stand with its feet in block `pos`
+    pub fn can_stand_at<BC: BlockContainer>(&self, world: &BC, pos: BlockPos) -> bool {
+        !world.collision_boxes(pos.offset(0, -1, 0)).is_empty() && self.is_body_free(world, pos)
+    }
+
+    /// Return the blocks where a mob standing at `pos` can go in one move, with the cost of the move
+    fn neighbors<BC: BlockContainer>(&self, world: &BC, pos: BlockPos) -> Vec<(BlockPos, u32)> {
+        let mut neighbors = Vec::new();
+        for &(dx, dz) in HORIZONTAL_NEIGHBORS.iter() {
+            let side = pos.offset(dx, 0, dz);
+            if self.can_stand_at(world, side) {
+                neighbors.push((side, MOVE_COST));
+                continue;
+            }
+            // Climb one block, which requires some room above the head of the mob to jump
+            let up = side.offset(0, 1, 0);
+            if Self::is_free(world, pos.offset(0, self.height, 0)) && self.can_stand_at(world, up) {
+                neighbors.push((up, MOVE_COST + STEP_UP_COST));
+                continue;
+            }
+            // Drop, which requires the whole column to be free until the ground
+            if !self.is_body_free(world, side) {
+                continue;
+            }
+            for depth in 1..=self.max_drop {
+                let below = side.offset(0, -depth, 0);
+                if !Self::is_free(world, below) {
+                    break;
+                }
+                if self.can_stand_at(world, below) {
+                    neighbors.push((below, MOVE_COST + DROP_COST * depth as u32));
+                    break;
+                }
+            }
+        }
+        neighbors
+    }
+}
+
+/// Lower bound of the cost of the path between `a` and `b`
+fn heuristic(a: BlockPos, b: BlockPos) -> u32 {
+    MOVE_COST * ((a.px - b.px).abs() + (a.pz - b.pz).abs()) as u32
+}
+
+struct Node {
+    pos: BlockPos,
+    cost: u32,
+    parent: Option<usize>,
+}
+
+/// Find a path from `start` to `goal`, exploring at most `max_nodes` nodes.
+/// The path contains the blocks the mob must walk through, without `start`.
+/// If `goal` can't be reached, return the path to the explored block that is the closest to `goal`,
+/// or `None` if no block is closer than `start`.
+pub fn find_path<BC: BlockContainer>(
+    world: &BC,
+    start: BlockPos,
+    goal: BlockPos,
+    rules: &MovementRules,
+    max_nodes: usize,
+) -> Option<Vec<BlockPos>> {
+    if !rules.can_stand_at(world, start) {
+        return None;
+    }
+    let mut nodes = vec![Node {
+        pos: start,
+        cost: 0,
+        parent: None,
+    }];
+    let mut node_ids = HashMap::new();
+    node_ids.insert(start, 0);
+    // Ties are broken by node id to keep the search deterministic
+    let mut open = BinaryHeap::new();
+    open.push(Reverse((heuristic(start, goal), 0)));
+    let distance_to_goal = |pos: BlockPos| (heuristic(pos, goal), (pos.py - goal.py).abs());
+    let mut closest = 0;
+    let mut explored = 0;
+
+    while let Some(Reverse((estimate, id))) = open.pop() {
+        let Node { pos, cost, .. } = nodes[id];
+        if estimate > cost + heuristic(pos, goal) {
+            // Outdated entry, the node was reached by a cheaper path
+            continue;
+        }
+        if distance_to_goal(pos) < distance_to_goal(nodes[closest].pos) {
+            closest = id;
+        }
+        if pos == goal {
+            break;
+        }
+        explored += 1;
+        if explored > max_nodes {
+            break;
+        }
+        for (neighbor, move_cost) in rules.neighbors(world, pos) {
+            let neighbor_cost = cost + move_cost;
+            match node_ids.get(&neighbor) {
+                Some(&neighbor_id) if nodes[neighbor_id].cost <= neighbor_cost => continue,
+                Some(&neighbor_id) => {
+                    nodes[neighbor_id].cost = neighbor_cost;
+                    nodes[neighbor_id].parent = Some(id);
+                    open.push(Reverse((
+                        neighbor_cost + heuristic(neighbor, goal),
+                        neighbor_id,
+                    )));
+                }
+                None => {
+                    let neighbor_id = nodes.len();
+                    nodes.push(Node {
+                        pos: neighbor,
+                        cost: neighbor_cost,
+                        parent: Some(id),
+                    });
+                    node_ids.insert(neighbor, neighbor_id);
+                    open.push(Reverse((
+                        neighbor_cost + heuristic(neighbor, goal),
+                        neighbor_id,
+                    )));
+                }
+            }
+        }
+    }
+
+    if closest == 0 && start != goal {
+        return None;
+    }
+    let mut path = Vec::new();
+    let mut current = closest;
+    while let Some(parent) = nodes[current].parent {
+        path.push(nodes[current].pos);
+        current = parent;
+    }
+    path.reverse();
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use voxel_rs_common::physics::test_world::TestWorld;
+
+    const RULES: MovementRules = MovementRules {
+        height: 2,
+        max_drop: 3,
+    };
+
+    /// A wall along z from `z1` to `z2` at `x`, from y = 1 to y = `height`
+    fn add_wall(world: &mut TestWorld, x: i64, z1: i64, z2: i64, height: i64) {
+        for z in z1..=z2 {
+            for y in 1..=height {
+                world.solid.insert((x, y, z).into());
+            }
+        }
+    }
+
+    fn pos(x: i64, y: i64, z: i64) -> BlockPos {
+        (x, y, z).into()
+    }
+
+    fn assert_path_is_connected(start: BlockPos, path: &[BlockPos]) {
+        let mut previous = start;
+        for &pos in path {
+            let distance = (pos.px - previous.px).abs() + (pos.pz - previous.pz).abs();
+            assert_eq!(distance, 1, "{:?} -> {:?}", previous, pos);
+            previous = pos;
+        }
+    }
+
+    #[test]
+    fn test_straight_path_on_flat_ground() {
+        let world = TestWorld::floor(10);
+        let path = find_path(&world, pos(0, 1, 0), pos(5, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path, (1..=5).map(|x| pos(x, 1, 0)).collect::<Vec<_>>());
+        assert_eq!(
+            find_path(&world, pos(0, 1, 0), pos(0, 1, 0), &RULES, 1000),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_walls_are_avoided() {
+        let mut world = TestWorld::floor(10);
+        add_wall(&mut world, 2, -3, 3, 2);
+        let start = pos(0, 1, 0);
+        let path = find_path(&world, start, pos(4, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path.last(), Some(&pos(4, 1, 0)));
+        assert_path_is_connected(start, &path);
+        assert!(path
+            .iter()
+            .all(|p| p.py == 1 && (p.px != 2 || p.pz.abs() > 3)));
+        // The shortest detour goes around one end of the wall
+        assert_eq!(path.len(), 4 + 2 * 4);
+    }
+
+    #[test]
+    fn test_mobs_climb_one_block_but_not_two() {
+        let mut world = TestWorld::floor(10);
+        add_wall(&mut world, 2, -10, 10, 1);
+        let start = pos(0, 1, 0);
+        let path = find_path(&world, start, pos(4, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(
+            path,
+            vec![pos(1, 1, 0), pos(2, 2, 0), pos(3, 1, 0), pos(4, 1, 0)]
+        );
+
+        // No room to jump
+        world.solid.insert(pos(1, 3, 0));
+        let path = find_path(&world, start, pos(4, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path.last(), Some(&pos(4, 1, 0)));
+        assert!(path.windows(2).all(|w| w != [pos(1, 1, 0), pos(2, 2, 0)]));
+
+        let mut world = TestWorld::floor(10);
+        add_wall(&mut world, 2, -10, 10, 2);
+        // The closest reachable block is in front of the wall
+        let path = find_path(&world, start, pos(4, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path, vec![pos(1, 1, 0)]);
+    }
+
+    #[test]
+    fn test_water_is_avoided() {
+        let mut world = TestWorld::floor(10);
+        for z in -10..=10 {
+            if z != 5 {
+                world.fluid.insert(pos(2, 1, z));
+            }
+        }
+        let start = pos(0, 1, 0);
+        let path = find_path(&world, start, pos(4, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path.last(), Some(&pos(4, 1, 0)));
+        assert_path_is_connected(start, &path);
+        assert!(path.iter().all(|p| !world.fluid.contains(p)));
+        assert!(path.contains(&pos(2, 1, 5)));
+    }
+
+    #[test]
+    fn test_mobs_drop_from_small_ledges_only() {
+        let mut world = TestWorld::floor(10);
+        // A platform at y = 3 for x <= 0
+        for x in -10..=0 {
+            for z in -10..=10 {
+                for y in 1..=3 {
+                    world.solid.insert(pos(x, y, z));
+                }
+            }
+        }
+        let start = pos(0, 4, 0);
+        let path = find_path(&world, start, pos(3, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path, vec![pos(1, 1, 0), pos(2, 1, 0), pos(3, 1, 0)]);
+
+        let rules = MovementRules {
+            max_drop: 2,
+            ..RULES
+        };
+        assert_eq!(find_path(&world, start, pos(3, 1, 0), &rules, 1000), None);
+    }
+
+    #[test]
+    fn test_unreachable_goals_are_approached() {
+        let mut world = TestWorld::floor(10);
+        // Surround the goal with walls
+        add_wall(&mut world, 4, -1, 1, 2);
+        add_wall(&mut world, 6, -1, 1, 2);
+        world.solid.insert(pos(5, 1, -1));
+        world.solid.insert(pos(5, 2, -1));
+        world.solid.insert(pos(5, 1, 1));
+        world.solid.insert(pos(5, 2, 1));
+        let start = pos(0, 1, 0);
+        let path = find_path(&world, start, pos(5, 1, 0), &RULES, 1000).unwrap();
+        assert_eq!(path.last(), Some(&pos(3, 1, 0)));
+        assert_eq!(path.len(), 3);
+        // Mobs can't start in a wall
+        assert_eq!(find_path(&world, pos(4, 1, 0), start, &RULES, 1000), None);
+    }
+}
@@ -0,0 +1,348 @@
+//! Persistence of the world to disk
+use crate::tick::ScheduledTick;
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use voxel_rs_common::{
+    block::{entity::BlockEntity, Block, BlockId},
+    clock::WorldTime,
+    player::GameMode,
+    registry::Registry,
+    world::{BlockPos, Chunk, ChunkPos, CompressedChunk},
+};
+
+const METADATA_FILENAME: &str = "world.ron";
+const CHUNKS_DIRECTORY: &str = "chunks";
+const PLAYERS_DIRECTORY: &str = "players";
+
+/// The data of the world that is not stored in the chunks.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldMetadata {
+    /// The world clock
+    pub time: WorldTime,
+    /// The number of game ticks since the world was created. Unlike the world clock, the commands can't move it
+    /// backwards, so it is used for the scheduled ticks and the periodic tasks of the server.
+    pub game_tick: u64,
+    /// The seed of the world, used for everything random that happens in the world
+    pub seed: u64,
+}
+
+/// The data of a player that is kept when it leaves the server.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedPlayer {
+    pub game_mode: GameMode,
+}
+
+/// Helper to read and write the world data in some directory.
+pub struct WorldStorage {
+    directory: PathBuf,
+}
+
+impl WorldStorage {
+    /// Open the world stored in `directory`, creating the directory if necessary.
+    pub fn open(directory: impl AsRef<Path>) -> Result<Self> {
+        let directory = directory.as_ref().to_owned();
+        fs::create_dir_all(&directory).context(format!(
+            "Failed to create world directory {}",
+            directory.display()
+        ))?;
+        Ok(Self { directory })
+    }
+
+    /// Load the world metadata, or create default metadata if this is a new world.
+    pub fn load_metadata(&self) -> Result<WorldMetadata> {
+        let path = self.directory.join(METADATA_FILENAME);
+        if path.is_file() {
+            info!("Loading world metadata from {}", path.display());
+            let buffer = fs::read_to_string(&path)
+                .context(format!("Failed to read world metadata {}", path.display()))?;
+            let mut metadata: WorldMetadata = ron::de::from_str(&buffer)
+                .context(format!("Failed to parse world metadata {}", path.display()))?;
+            // The scheduled ticks of the worlds saved without a game tick are due at some tick of the world clock
+            if metadata.game_tick == 0 {
+                metadata.game_tick = metadata.time.ticks;
+            }
+            Ok(metadata)
+        } else {
+            info!(
+                "No world metadata at {}, creating a new world",
+                path.display()
+            );
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_nanos() as u64)
+                .unwrap_or(0);
+            let metadata = WorldMetadata {
+                seed,
+                ..WorldMetadata::default()
+            };
+            self.save_metadata(&metadata)?;
+            Ok(metadata)
+        }
+    }
+
+    /// Save the world metadata
+    pub fn save_metadata(&self, metadata: &WorldMetadata) -> Result<()> {
+        let path = self.directory.join(METADATA_FILENAME);
+        let string = ron::ser::to_string_pretty(metadata, Default::default())
+            .context("Failed to serialize world metadata")?;
+        fs::write(&path, string)
+            .context(format!("Failed to write world metadata {}", path.display()))
+    }
+
+    fn player_path(&self, name: &str) -> PathBuf {
+        // Only keep the characters that are safe in a filename
+        let filename: String = name
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+            .collect();
+        self.directory
+            .join(PLAYERS_DIRECTORY)
+            .join(format!("{}.ron", filename))
+    }
+
+    /// Load the data of a player, or return `None` if it never joined the server
+    pub fn load_player(&self, name: &str) -> Result<Option<SavedPlayer>> {
+        let path = self.player_path(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let buffer = fs::read_to_string(&path)
+            .context(format!("Failed to read player {}", path.display()))?;
+        let saved_player = ron::de::from_str(&buffer)
+            .context(format!("Failed to parse player {}", path.display()))?;
+        Ok(Some(saved_player))
+    }
+
+    /// Save the data of a player
+    pub fn save_player(&self, name: &str, player: &SavedPlayer) -> Result<()> {
+        let directory = self.directory.join(PLAYERS_DIRECTORY);
+        fs::create_dir_all(&directory).context(format!(
+            "Failed to create player directory {}",
+            directory.display()
+        ))?;
+        let path = self.player_path(name);
+        let string = ron::ser::to_string_pretty(player, Default::default())
+            .context("Failed to serialize player")?;
+        fs::write(&path, string).context(format!("Failed to write player {}", path.display()))
+    }
+
+    /// Open the storage of the chunks of the world, which contain the blocks of the registry
+    pub fn open_chunks(&self, blocks: &Registry<Block>) -> Result<ChunkStorage> {
+        ChunkStorage::open(
+            self.directory.join(CHUNKS_DIRECTORY),
+            BlockNames::from_registry(blocks),
+        )
+    }
+}
+
+/// A chunk as it is saved to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedChunk {
+    pub chunk: CompressedChunk,
+    /// The name of every block id used in the chunk and its scheduled ticks. The ids change when blocks are added
+    /// or when their states change, so the blocks are found by name when the chunk is loaded.
+    #[serde(default)]
+    pub palette: Vec<(BlockId, String)>,
+    /// The ticks that were scheduled in the chunk when it was saved
+    pub scheduled_ticks: Vec<ScheduledTick>,
+    /// The block entities of the chunk
+    #[serde(default)]
+    pub block_entities: Vec<(BlockPos, BlockEntity)>,
+}
+
+/// Helper to read and write the modified chunks, one file per chunk.
+pub struct ChunkStorage {
+    directory: PathBuf,
+    /// The chunks that have been saved, so we don't have to check the disk for every generated chunk
+    saved_chunks: HashSet<ChunkPos>,
+    block_names: BlockNames,
+}
+
+impl ChunkStorage {
+    fn open(directory: PathBuf, block_names: BlockNames) -> Result<Self> {
+        fs::create_dir_all(&directory).context(format!(
+            "Failed to create chunk directory {}",
+            directory.display()
+        ))?;
+        let mut saved_chunks = HashSet::new();
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if let Some(pos) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(parse_chunk_filename)
+            {
+                saved_chunks.insert(pos);
+            }
+        }
+        info!("Found {} saved chunks", saved_chunks.len());
+        Ok(Self {
+            directory,
+            saved_chunks,
+            block_names,
+        })
+    }
+
+    fn chunk_path(&self, pos: ChunkPos) -> PathBuf {
+        self.directory
+            .join(format!("{}_{}_{}.ron", pos.px, pos.py, pos.pz))
+    }
+
+    /// Load a chunk, or return `None` if it was never saved
+    pub fn load_chunk(&self, pos: ChunkPos) -> Result<Option<SavedChunk>> {
+        if !self.saved_chunks.contains(&pos) {
+            return Ok(None);
+        }
+        let path = self.chunk_path(pos);
+        let buffer = fs::read_to_string(&path)
+            .context(format!("Failed to read chunk {}", path.display()))?;
+        let mut saved_chunk: SavedChunk = ron::de::from_str(&buffer)
+            .context(format!("Failed to parse chunk {}", path.display()))?;
+        // Chunks saved without a palette use the current ids
+        if !saved_chunk.palette.is_empty() {
+            let ids = self.block_names.remap(&saved_chunk.palette);
+            let current_id = |id: BlockId| ids.get(&id).copied().unwrap_or(0);
+            for (_, block) in saved_chunk.chunk.data.iter_mut() {
+                *block = current_id(*block);
+            }
+            for scheduled_tick in saved_chunk.scheduled_ticks.iter_mut() {
+                scheduled_tick.block = current_id(scheduled_tick.block);
+            }
+        }
+        Ok(Some(saved_chunk))
+    }
+
+    /// Save a chunk, its scheduled ticks and its block entities
+    pub fn save_chunk(
+        &mut self,
+        chunk: &Chunk,
+        scheduled_ticks: Vec<ScheduledTick>,
+        block_entities: &HashMap<BlockPos, BlockEntity>,
+    ) -> Result<()> {
+        let path = self.chunk_path(chunk.pos);
+        let mut block_entities = block_entities
+            .iter()
+            .map(|(pos, entity)| (*pos, entity.clone()))
+            .collect::<Vec<_>>();
+        block_entities.sort_by_key(|(pos, _)| (pos.px, pos.py, pos.pz));
+        let chunk = CompressedChunk::from_chunk(chunk);
+        let used_ids = chunk
+            .data
+            .iter()
+            .map(|&(_, block)| block)
+            .chain(scheduled_ticks.iter().map(|tick| tick.block));
+        let saved_chunk = SavedChunk {
+            palette: self.block_names.palette(used_ids),
+            chunk,
+            scheduled_ticks,
+            block_entities,
+        };
+        let string = ron::ser::to_string(&saved_chunk).context("Failed to serialize chunk")?;
+        fs::write(&path, string).context(format!("Failed to write chunk {}", path.display()))?;
+        self.saved_chunks.insert(saved_chunk.chunk.pos);
+        Ok(())
+    }
+}
+
+/// The names of the blocks, to save the blocks of the chunks independently of their ids
+struct BlockNames {
+    names: Vec<String>,
+    ids: HashMap<String, BlockId>,
+}
+
+impl BlockNames {
+    fn new(names: Vec<String>) -> Self {
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as BlockId))
+            .collect();
+        Self { names, ids }
+    }
+
+    fn from_registry(blocks: &Registry<Block>) -> Self {
+        Self::new(
+            (0..blocks.get_number_of_ids())
+                .map(|id| blocks.get_value_by_id(id).unwrap().name.clone())
+                .collect(),
+        )
+    }
+
+    /// Return the name of each of the ids
+    fn palette(&self, ids: impl Iterator<Item = BlockId>) -> Vec<(BlockId, String)> {
+        let mut ids = ids.collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .filter_map(|id| Some((id, self.names.get(id as usize)?.clone())))
+            .collect()
+    }
+
+    /// Return the current id of each of the saved ids of a palette.
+    /// The states that don't exist anymore are replaced by the default state of their block, and the blocks that
+    /// don't exist anymore by air.
+    fn remap(&self, palette: &[(BlockId, String)]) -> HashMap<BlockId, BlockId> {
+        palette
+            .iter()
+            .map(|(saved_id, name)| {
+                // The name of a state is the name of its block followed by the values of its properties
+                let block_name = name.split('[').next().unwrap_or(name);
+                let id = match self.ids.get(name).or_else(|| self.ids.get(block_name)) {
+                    Some(&id) => id,
+                    None => {
+                        log::warn!(
+                            "Unknown block {} in a saved chunk, replacing it with air",
+                            name
+                        );
+                        0
+                    }
+                };
+                (*saved_id, id)
+            })
+            .collect()
+    }
+}
+
+fn parse_chunk_filename(stem: &str) -> Option<ChunkPos> {
+    let mut coords = stem.split('_').map(|coord| coord.parse::<i64>().ok());
+    match (coords.next(), coords.next(), coords.next(), coords.next()) {
+        (Some(Some(px)), Some(Some(py)), Some(Some(pz)), None) => Some(ChunkPos { px, py, pz }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_names(names: &[&str]) -> BlockNames {
+        BlockNames::new(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    #[test]
+    fn test_saved_blocks_are_found_by_name() {
+        let saved = block_names(&["air", "dirt", "stairs", "stairs[facing=east]", "stone"]);
+        let palette = saved.palette(vec![4, 1, 3, 4].into_iter());
+        assert_eq!(
+            palette,
+            vec![
+                (1, "dirt".to_owned()),
+                (3, "stairs[facing=east]".to_owned()),
+                (4, "stone".to_owned()),
+            ]
+        );
+        // Blocks were added, the stairs lost their facing and the dirt was removed
+        let current = block_names(&["air", "sand", "stairs", "stairs[half=top]", "stone"]);
+        let ids = current.remap(&palette);
+        assert_eq!(ids[&1], 0);
+        assert_eq!(ids[&3], 2);
+        assert_eq!(ids[&4], 4);
+    }
+}
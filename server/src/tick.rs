@@ -0,0 +1,316 @@
+//! Scheduled and random block ticks.
+//!
+//! Blocks get behavior through a `BlockHandler`. Handlers can be called in three ways:
+//! * a scheduled tick runs at a given game tick, for example when a fluid should flow or a crop should grow,
+//! * a random tick runs at some random block of every loaded chunk, a few times per game tick,
+//! * a neighbor update runs when a block is placed, and for each of its six neighbors.
+//!
+//! Handlers also create the block entities of their blocks, and tick the block entities that need it.
+//!
+//! Handlers don't modify the world directly: they record their changes in a `TickContext`,
+//! and the changes are applied by the world once every handler has run.
+use crate::world::World;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use voxel_rs_common::{
+    block::{entity::BlockEntity, BlockId},
+    random::Rng,
+    world::{BlockPos, ChunkPos},
+};
+
+/// A tick scheduled for some block at some position.
+/// The tick is dropped if the block at that position changed in the meantime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledTick {
+    pub pos: BlockPos,
+    pub block: BlockId,
+    /// The game tick the tick must run at
+    pub due_tick: u64,
+}
+
+/// The behavior of a block
+pub trait BlockHandler: Send + Sync {
+    /// Called when a tick that was scheduled for this block is due
+    fn scheduled_tick(&self, _ctx: &mut TickContext, _pos: BlockPos, _block: BlockId) {}
+
+    /// Called when this block is randomly chosen for a random tick
+    fn random_tick(&self, _ctx: &mut TickContext, _pos: BlockPos, _block: BlockId) {}
+
+    /// Called when this block was just placed
+    fn placed(&self, _ctx: &mut TickContext, _pos: BlockPos, _block: BlockId) {}
+
+    /// Called when the block at `neighbor`, next to this block, changed
+    fn neighbor_changed(
+        &self,
+        _ctx: &mut TickContext,
+        _pos: BlockPos,
+        _block: BlockId,
+        _neighbor: BlockPos,
+    ) {
+    }
+
+    /// Return the block entity of this block when it is placed, if it has one
+    fn create_block_entity(&self, _block: BlockId) -> Option<BlockEntity> {
+        None
+    }
+
+    /// Return true if `tick_block_entity` must be called for this block entity every game tick
+    fn needs_block_entity_tick(&self, _entity: &BlockEntity) -> bool {
+        false
+    }
+
+    /// Called every game tick for the block entities that need it.
+    /// Return the new data of the block entity if it changed.
+    fn tick_block_entity(
+        &self,
+        _ctx: &mut TickContext,
+        _pos: BlockPos,
+        _block: BlockId,
+        _entity: &BlockEntity,
+    ) -> Option<BlockEntity> {
+        None
+    }
+}
+
+/// The view of the world given to the block handlers
+pub struct TickContext<'a> {
+    world: &'a World,
+    tick: u64,
+    rng: &'a mut Rng,
+    output: TickOutput,
+}
+
+/// What the block handlers did during a tick
+#[derive(Debug, Default)]
+pub struct TickOutput {
+    /// The block changes
+    pub changes: Vec<(BlockPos, BlockId)>,
+    /// The new scheduled ticks
+    pub scheduled_ticks: Vec<ScheduledTick>,
+    /// The blocks that started falling
+    pub falling_blocks: Vec<(BlockPos, BlockId)>,
+}
+
+impl<'a> TickContext<'a> {
+    pub fn new(world: &'a World, tick: u64, rng: &'a mut Rng) -> Self {
+        Self {
+            world,
+            tick,
+            rng,
+            output: TickOutput::default(),
+        }
+    }
+
+    /// The world, as it was at the beginning of the tick
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// The random number generator of the world
+    pub fn rng(&mut self) -> &mut Rng {
+        self.rng
+    }
+
+    /// Return the block at `pos`, as it was at the beginning of the tick
+    pub fn get_block(&self, pos: BlockPos) -> BlockId {
+        self.world.get_block(pos)
+    }
+
+    /// Replace the block at `pos` at the end of the tick
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) {
+        self.output.changes.push((pos, block));
+    }
+
+    /// Schedule a tick for `block` at `pos` in `delay` ticks
+    pub fn schedule_tick(&mut self, pos: BlockPos, block: BlockId, delay: u64) {
+        self.output.scheduled_ticks.push(ScheduledTick {
+            pos,
+            block,
+            due_tick: self.tick + delay.max(1),
+        });
+    }
+
+    /// Replace the block at `pos` by air and turn it into a falling block at the end of the tick
+    pub fn spawn_falling_block(&mut self, pos: BlockPos, block: BlockId) {
+        self.set_block(pos, 0);
+        self.output.falling_blocks.push((pos, block));
+    }
+
+    /// Return what the handlers did
+    pub fn finish(self) -> TickOutput {
+        self.output
+    }
+}
+
+/// A scheduled tick in the queue. The sequence number keeps the ticks that are due at the same time in scheduling order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct QueuedTick {
+    tick: ScheduledTick,
+    sequence: u64,
+}
+
+impl PartialOrd for QueuedTick {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedTick {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.tick.due_tick, self.sequence).cmp(&(other.tick.due_tick, other.sequence))
+    }
+}
+
+/// A priority queue of scheduled ticks. A block at some position can only be scheduled once.
+#[derive(Default)]
+pub struct ScheduledTicks {
+    queue: BinaryHeap<Reverse<QueuedTick>>,
+    scheduled: HashSet<(BlockPos, BlockId)>,
+    next_sequence: u64,
+}
+
+impl ScheduledTicks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule a tick, unless the same block is already scheduled at the same position
+    pub fn schedule(&mut self, tick: ScheduledTick) {
+        if self.scheduled.insert((tick.pos, tick.block)) {
+            self.queue.push(Reverse(QueuedTick {
+                tick,
+                sequence: self.next_sequence,
+            }));
+            self.next_sequence += 1;
+        }
+    }
+
+    /// Remove and return at most `max` ticks that are due at `tick`
+    pub fn pop_due(&mut self, tick: u64, max: usize) -> Vec<ScheduledTick> {
+        let mut due = Vec::new();
+        while due.len() < max {
+            match self.queue.peek() {
+                Some(Reverse(queued)) if queued.tick.due_tick <= tick => {
+                    let queued = self.queue.pop().expect("Logic error").0;
+                    self.scheduled.remove(&(queued.tick.pos, queued.tick.block));
+                    due.push(queued.tick);
+                }
+                _ => break,
+            }
+        }
+        due
+    }
+
+    /// Remove and return the ticks scheduled in some chunk, for example because it is unloaded
+    pub fn remove_chunk(&mut self, chunk_pos: ChunkPos) -> Vec<ScheduledTick> {
+        let mut removed = Vec::new();
+        let mut kept = BinaryHeap::with_capacity(self.queue.len());
+        for Reverse(queued) in self.queue.drain() {
+            if queued.tick.pos.containing_chunk_pos() == chunk_pos {
+                removed.push(queued);
+            } else {
+                kept.push(Reverse(queued));
+            }
+        }
+        self.queue = kept;
+        removed.sort();
+        removed
+            .into_iter()
+            .map(|queued| {
+                self.scheduled.remove(&(queued.tick.pos, queued.tick.block));
+                queued.tick
+            })
+            .collect()
+    }
+
+    /// Return the scheduled ticks grouped by chunk, in the order they will run
+    pub fn ticks_by_chunk(&self) -> HashMap<ChunkPos, Vec<ScheduledTick>> {
+        let mut queued = self
+            .queue
+            .iter()
+            .map(|Reverse(queued)| *queued)
+            .collect::<Vec<_>>();
+        queued.sort();
+        let mut ticks = HashMap::new();
+        for queued in queued {
+            ticks
+                .entry(queued.tick.pos.containing_chunk_pos())
+                .or_insert_with(Vec::new)
+                .push(queued.tick);
+        }
+        ticks
+    }
+
+    /// Number of scheduled ticks
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(x: i64, block: BlockId, due_tick: u64) -> ScheduledTick {
+        ScheduledTick {
+            pos: BlockPos::from((x, 0, 0)),
+            block,
+            due_tick,
+        }
+    }
+
+    #[test]
+    fn test_ticks_run_in_order() {
+        let mut ticks = ScheduledTicks::new();
+        ticks.schedule(tick(0, 1, 5));
+        ticks.schedule(tick(1, 1, 3));
+        ticks.schedule(tick(2, 1, 3));
+        assert_eq!(ticks.pop_due(2, 10), vec![]);
+        assert_eq!(ticks.pop_due(4, 10), vec![tick(1, 1, 3), tick(2, 1, 3)]);
+        assert_eq!(ticks.pop_due(10, 10), vec![tick(0, 1, 5)]);
+        assert_eq!(ticks.len(), 0);
+    }
+
+    #[test]
+    fn test_ticks_are_deduplicated() {
+        let mut ticks = ScheduledTicks::new();
+        ticks.schedule(tick(0, 1, 5));
+        ticks.schedule(tick(0, 1, 2));
+        ticks.schedule(tick(0, 2, 2));
+        assert_eq!(ticks.len(), 2);
+        assert_eq!(ticks.pop_due(5, 10), vec![tick(0, 2, 2), tick(0, 1, 5)]);
+        // Once the tick ran, the block can be scheduled again
+        ticks.schedule(tick(0, 1, 7));
+        assert_eq!(ticks.len(), 1);
+    }
+
+    #[test]
+    fn test_pop_due_respects_budget() {
+        let mut ticks = ScheduledTicks::new();
+        for x in 0..10 {
+            ticks.schedule(tick(x, 1, 1));
+        }
+        assert_eq!(ticks.pop_due(1, 4).len(), 4);
+        assert_eq!(ticks.pop_due(1, 4).len(), 4);
+        assert_eq!(ticks.pop_due(1, 4).len(), 2);
+    }
+
+    #[test]
+    fn test_remove_chunk() {
+        let mut ticks = ScheduledTicks::new();
+        ticks.schedule(tick(40, 1, 2));
+        ticks.schedule(tick(0, 1, 3));
+        ticks.schedule(tick(1, 1, 1));
+        let chunk_pos = BlockPos::from((0, 0, 0)).containing_chunk_pos();
+        assert_eq!(
+            ticks.ticks_by_chunk()[&chunk_pos],
+            vec![tick(1, 1, 1), tick(0, 1, 3)]
+        );
+        assert_eq!(
+            ticks.remove_chunk(chunk_pos),
+            vec![tick(1, 1, 1), tick(0, 1, 3)]
+        );
+        assert_eq!(ticks.pop_due(10, 10), vec![tick(40, 1, 2)]);
+    }
+}
@@ -1,31 +1,59 @@
 use crate::{
+    block_handlers::{create_block_handlers, BlockHandlers},
+    config::ServerConfig,
     light::worker::{start_lighting_worker, ChunkLightingData, ChunkLightingWorker},
-    light::HighestOpaqueBlock,
+    light::{light_attenuation_table, HighestOpaqueBlock},
+    mob::MobWorld,
+    storage::ChunkStorage,
+    tick::{BlockHandler, ScheduledTicks, TickContext},
     worldgen::{start_worldgen_worker, WorldGenerationWorker},
 };
+use anyhow::Result;
 use lazy_static::lazy_static;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     sync::Arc,
 };
 use voxel_rs_common::{
-    block::{Block, BlockId},
-    physics::BlockContainer,
+    block::{entity::BlockEntity, loot::LootTable, Block, BlockId, FluidState},
+    item::{inventory::ItemStack, ToolKind},
+    physics::{BlockContainer, AABB},
     player::RenderDistance,
+    random::Rng,
     registry::Registry,
-    world::{BlockPos, Chunk, ChunkPos, ChunkPosXZ, LightChunk, WorldGenerator},
+    world::{BlockPos, Chunk, ChunkPos, ChunkPosXZ, LightChunk, WorldGenerator, CHUNK_SIZE},
 };
 
 lazy_static! {
     static ref EMPTY_HOB: Arc<HighestOpaqueBlock> = Arc::new(HighestOpaqueBlock::new());
 }
 
+/// Maximum number of scheduled ticks that run in one game tick. The remaining ticks are postponed to the next game tick.
+const MAX_SCHEDULED_TICKS_PER_TICK: usize = 4096;
+/// Maximum number of neighbor updates that run in one game tick. The remaining updates are postponed to the next game tick.
+const MAX_NEIGHBOR_UPDATES_PER_TICK: usize = 4096;
+
+const NEIGHBORS: [[i64; 3]; 6] = [
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+    [0, 0, 1],
+    [0, 0, -1],
+];
+
 /// Server-side world
 /// It is responsible for
 /// * storing chunk data
 /// * generating the chunks
 /// * updating the lighting
+/// * running the block ticks
+/// * saving and loading the modified chunks
 pub struct World {
+    /// The block registry
+    block_registry: Registry<Block>,
+    /// The light attenuation of every block
+    light_attenuation: Arc<Vec<u8>>,
     /// The chunks
     chunks: HashMap<ChunkPos, ServerChunk>,
     /// The chunk columns
@@ -36,22 +64,65 @@ pub struct World {
     worldgen_queue: HashSet<ChunkPos>,
     /// The worldgen worker
     worldgen_worker: WorldGenerationWorker,
-    /// The light worker
+    /// The light worker, running on a pool of threads
     light_worker: ChunkLightingWorker,
+    /// The handler of every block, indexed by block id
+    handlers: BlockHandlers,
+    /// The scheduled block ticks
+    scheduled_ticks: ScheduledTicks,
+    /// The pending neighbor updates
+    neighbor_updates: VecDeque<NeighborUpdate>,
+    /// The neighbor updates that are in `neighbor_updates`
+    queued_neighbor_updates: HashSet<(BlockPos, BlockPos)>,
+    /// The random number generator used by the block ticks
+    rng: Rng,
+    /// Number of random block ticks per loaded chunk and per game tick
+    random_ticks_per_chunk: u32,
+    /// The storage of the modified chunks
+    chunk_storage: ChunkStorage,
+    /// The blocks that started falling and are not in the physics simulation yet
+    falling_blocks: Vec<(BlockPos, BlockId)>,
+    /// The block entities that must be ticked every game tick
+    ticking_block_entities: HashSet<BlockPos>,
+    /// The items that were dropped by broken blocks and removed block entities, for example the contents of a broken chest
+    dropped_items: Vec<(BlockPos, ItemStack)>,
+    /// The loot table of every block, indexed by block id
+    loot_tables: Vec<LootTable>,
+    /// Generator for the drops of broken blocks, separate from `rng` so the drops don't depend on the random ticks
+    loot_rng: Rng,
 }
 
 impl World {
     pub fn new(
         block_registry: Registry<Block>,
+        loot_tables: Vec<LootTable>,
         world_generator: Box<dyn WorldGenerator + Send>,
+        chunk_storage: ChunkStorage,
+        config: &ServerConfig,
+        seed: u64,
     ) -> Self {
+        let light_attenuation = light_attenuation_table(&block_registry);
         Self {
+            light_worker: start_lighting_worker(light_attenuation.clone(), config.light_threads),
+            handlers: create_block_handlers(&block_registry),
+            scheduled_ticks: ScheduledTicks::new(),
+            neighbor_updates: VecDeque::new(),
+            queued_neighbor_updates: HashSet::new(),
+            rng: Rng::new(seed),
+            random_ticks_per_chunk: config.random_ticks_per_chunk,
+            chunk_storage,
+            falling_blocks: Vec::new(),
+            ticking_block_entities: HashSet::new(),
+            dropped_items: Vec::new(),
+            loot_tables,
+            loot_rng: Rng::new(seed.wrapping_add(1)),
+            block_registry: block_registry.clone(),
+            light_attenuation,
             chunks: HashMap::default(),
             chunk_columns: HashMap::default(),
             next_chunk_version: 0,
             worldgen_queue: HashSet::default(),
             worldgen_worker: start_worldgen_worker(block_registry, world_generator),
-            light_worker: start_lighting_worker(),
         }
     }
 
@@ -72,13 +143,88 @@ impl World {
         }
     }
 
+    /// Return the light level at position `pos` in the world. 0 is returned if the chunk is not loaded
+    pub fn get_light(&self, pos: BlockPos) -> u8 {
+        match self.chunks.get(&pos.containing_chunk_pos()) {
+            None => 0,
+            Some(server_chunk) => server_chunk
+                .light_chunk
+                .get_light_at(pos.pos_in_containing_chunk()),
+        }
+    }
+
+    /// Return the block entity at `pos`, if there is one
+    pub fn get_block_entity(&self, pos: BlockPos) -> Option<&BlockEntity> {
+        self.chunks
+            .get(&pos.containing_chunk_pos())
+            .and_then(|server_chunk| server_chunk.block_entities.get(&pos))
+    }
+
+    /// Replace the block entity at `pos`. Return false if there is no block entity at `pos`.
+    pub fn set_block_entity(&mut self, pos: BlockPos, entity: BlockEntity) -> bool {
+        if self.get_block_entity(pos).is_none() {
+            return false;
+        }
+        self.insert_block_entity(pos, entity);
+        self.chunks
+            .get_mut(&pos.containing_chunk_pos())
+            .expect("The chunk of a block entity is loaded")
+            .modified = true;
+        true
+    }
+
+    /// Insert or replace a block entity in a loaded chunk, and start or stop ticking it
+    fn insert_block_entity(&mut self, pos: BlockPos, entity: BlockEntity) {
+        let block = self.get_block(pos);
+        let needs_tick = self
+            .handler(block)
+            .map_or(false, |handler| handler.needs_block_entity_tick(&entity));
+        if let Some(server_chunk) = self.chunks.get_mut(&pos.containing_chunk_pos()) {
+            server_chunk.block_entities.insert(pos, entity);
+            if needs_tick {
+                self.ticking_block_entities.insert(pos);
+            } else {
+                self.ticking_block_entities.remove(&pos);
+            }
+        }
+    }
+
+    /// Remove the block entity of the block that was at `pos`, and create the one of the new block if it has one.
+    /// The items stored in the removed block entity are dropped.
+    fn reset_block_entity(&mut self, pos: BlockPos) {
+        if let Some(server_chunk) = self.chunks.get_mut(&pos.containing_chunk_pos()) {
+            if let Some(BlockEntity::Chest { mut inventory }) =
+                server_chunk.block_entities.remove(&pos)
+            {
+                self.dropped_items
+                    .extend(inventory.take_all().into_iter().map(|stack| (pos, stack)));
+            }
+            self.ticking_block_entities.remove(&pos);
+        }
+        let block = self.get_block(pos);
+        if let Some(entity) = self
+            .handler(block)
+            .and_then(|handler| handler.create_block_entity(block))
+        {
+            self.insert_block_entity(pos, entity);
+        }
+    }
+
+    /// Return the handler of some block
+    fn handler(&self, block: BlockId) -> Option<&Arc<dyn BlockHandler>> {
+        self.handlers.get(block as usize).and_then(Option::as_ref)
+    }
+
     /// Update the highest opaque block in the column, and mark relevant chunks for a light update.
     /// To be called after every chunk loading or modification.
     fn update_chunk_column(&mut self, pos: ChunkPos) {
         let column_pos = pos.into();
 
         // Update chunk HOB
-        let hob = HighestOpaqueBlock::from_chunk(&self.chunks.get(&pos).unwrap().chunk);
+        let hob = HighestOpaqueBlock::from_chunk(
+            &self.chunks.get(&pos).unwrap().chunk,
+            &self.light_attenuation,
+        );
         let column = self.chunk_columns.get_mut(&column_pos).unwrap();
         column.highest_opaque_blocks.insert(pos.py, hob);
 
@@ -111,8 +257,8 @@ impl World {
         }
     }
 
-    /// Set the chunk at some position
-    pub fn set_chunk(&mut self, chunk: Arc<Chunk>) {
+    /// Set the chunk at some position. `modified` is true if the chunk must be saved.
+    fn set_chunk(&mut self, chunk: Arc<Chunk>, modified: bool) {
         let pos = chunk.pos;
         let server_chunk = self.chunks.entry(pos).or_insert_with(|| ServerChunk {
             chunk: chunk.clone(),
@@ -120,8 +266,11 @@ impl World {
             version: 0,
             is_in_light_queue: false,
             needs_light_update: true,
+            modified: false,
+            block_entities: HashMap::new(),
         });
         server_chunk.chunk = chunk;
+        server_chunk.modified |= modified;
         server_chunk.needs_light_update = true;
         server_chunk.version = self.next_chunk_version;
         self.next_chunk_version += 1;
@@ -140,13 +289,221 @@ impl World {
         self.update_chunk_column(pos);
     }
 
+    /// Set the block at some position. Return false if the chunk is not loaded.
+    pub fn set_block(&mut self, pos: BlockPos, block: BlockId) -> bool {
+        self.set_blocks(std::iter::once((pos, block))) == 1
+    }
+
+    /// Break the block at `pos` with `tool`, replacing it with air and dropping its loot.
+    /// Return whether the block was broken.
+    pub fn break_block(&mut self, pos: BlockPos, tool: Option<ToolKind>) -> bool {
+        let block = self.get_block(pos);
+        if block == 0 || !self.set_block(pos, 0) {
+            return false;
+        }
+        let drops = self.loot_tables[block as usize].roll(tool, &mut self.loot_rng);
+        self.dropped_items
+            .extend(drops.into_iter().map(|stack| (pos, stack)));
+        true
+    }
+
+    /// Set multiple blocks at once, updating every modified chunk only once.
+    /// The blocks in chunks that are not loaded are skipped. Return the number of blocks that were set.
+    pub fn set_blocks(&mut self, blocks: impl IntoIterator<Item = (BlockPos, BlockId)>) -> usize {
+        let mut modified_chunks: HashMap<ChunkPos, Chunk> = HashMap::new();
+        let mut changed_positions = Vec::new();
+        let mut count = 0;
+        for (pos, block) in blocks {
+            let chunk_pos = pos.containing_chunk_pos();
+            if !modified_chunks.contains_key(&chunk_pos) {
+                match self.chunks.get(&chunk_pos) {
+                    Some(server_chunk) => {
+                        modified_chunks.insert(chunk_pos, (*server_chunk.chunk).clone());
+                    }
+                    None => continue,
+                }
+            }
+            let chunk = modified_chunks.get_mut(&chunk_pos).expect("Logic error");
+            let pos_in_chunk = pos.pos_in_containing_chunk();
+            if chunk.get_block_at(pos_in_chunk) != block {
+                chunk.set_block_at(pos_in_chunk, block);
+                changed_positions.push(pos);
+            }
+            count += 1;
+        }
+        for (_, chunk) in modified_chunks {
+            self.set_chunk(Arc::new(chunk), true);
+        }
+        for pos in changed_positions {
+            self.reset_block_entity(pos);
+            self.enqueue_neighbor_update(pos, pos);
+            for &[dx, dy, dz] in NEIGHBORS.iter() {
+                let neighbor = BlockPos::from((pos.px + dx, pos.py + dy, pos.pz + dz));
+                self.enqueue_neighbor_update(neighbor, pos);
+            }
+        }
+        count
+    }
+
+    /// Notify the block at `pos` that the block at `source` changed
+    fn enqueue_neighbor_update(&mut self, pos: BlockPos, source: BlockPos) {
+        if self.is_block_loaded(pos) && self.queued_neighbor_updates.insert((pos, source)) {
+            self.neighbor_updates
+                .push_back(NeighborUpdate { pos, source });
+        }
+    }
+
+    /// Run the block handlers on the current state of the world, then apply their changes
+    fn run_handlers(&mut self, tick: u64, run: impl FnOnce(&Self, &mut TickContext)) {
+        let mut rng = self.rng.clone();
+        let mut ctx = TickContext::new(self, tick, &mut rng);
+        run(self, &mut ctx);
+        let output = ctx.finish();
+        self.rng = rng;
+
+        self.set_blocks(output.changes);
+        for scheduled_tick in output.scheduled_ticks {
+            if self.is_block_loaded(scheduled_tick.pos) {
+                self.scheduled_ticks.schedule(scheduled_tick);
+            }
+        }
+        self.falling_blocks.extend(output.falling_blocks);
+    }
+
+    /// Return the blocks that started falling since the last call. They must be added to the physics simulation.
+    pub fn take_falling_blocks(&mut self) -> Vec<(BlockPos, BlockId)> {
+        std::mem::take(&mut self.falling_blocks)
+    }
+
+    /// Return the items that were dropped since the last call. They must be added to the physics simulation.
+    pub fn take_dropped_items(&mut self) -> Vec<(BlockPos, ItemStack)> {
+        std::mem::take(&mut self.dropped_items)
+    }
+
+    /// Place a falling block that landed at `pos`. The block is lost if something solid is already there.
+    pub fn place_landed_block(&mut self, pos: BlockPos, block: BlockId) {
+        if !self.is_block_full(pos) {
+            self.set_block(pos, block);
+        }
+    }
+
+    /// Run one game tick: the scheduled ticks that are due, the random ticks of every loaded chunk,
+    /// and then the neighbor updates caused by the block changes.
+    pub fn tick(&mut self, tick: u64) {
+        let due_ticks = self
+            .scheduled_ticks
+            .pop_due(tick, MAX_SCHEDULED_TICKS_PER_TICK);
+        // Iterate the chunks in a fixed order to keep the random ticks deterministic
+        let mut loaded_chunks = self.chunks.keys().copied().collect::<Vec<_>>();
+        loaded_chunks.sort_by_key(|pos| (pos.px, pos.py, pos.pz));
+        let mut ticking_block_entities = self
+            .ticking_block_entities
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        ticking_block_entities.sort_by_key(|pos| (pos.px, pos.py, pos.pz));
+        let mut updated_block_entities = Vec::new();
+
+        self.run_handlers(tick, |world, ctx| {
+            for scheduled_tick in due_ticks {
+                // The tick is dropped if the block changed since it was scheduled
+                let block = world.get_block(scheduled_tick.pos);
+                if block == scheduled_tick.block {
+                    if let Some(handler) = world.handler(block) {
+                        handler.scheduled_tick(ctx, scheduled_tick.pos, block);
+                    }
+                }
+            }
+            for chunk_pos in loaded_chunks {
+                for _ in 0..world.random_ticks_per_chunk {
+                    let rng = ctx.rng();
+                    let pos = BlockPos::from((
+                        chunk_pos.px * CHUNK_SIZE as i64 + rng.gen_range(CHUNK_SIZE as u64) as i64,
+                        chunk_pos.py * CHUNK_SIZE as i64 + rng.gen_range(CHUNK_SIZE as u64) as i64,
+                        chunk_pos.pz * CHUNK_SIZE as i64 + rng.gen_range(CHUNK_SIZE as u64) as i64,
+                    ));
+                    let block = world.get_block(pos);
+                    if let Some(handler) = world.handler(block) {
+                        handler.random_tick(ctx, pos, block);
+                    }
+                }
+            }
+            for pos in ticking_block_entities {
+                let block = world.get_block(pos);
+                if let (Some(handler), Some(entity)) =
+                    (world.handler(block), world.get_block_entity(pos))
+                {
+                    if let Some(entity) = handler.tick_block_entity(ctx, pos, block, entity) {
+                        updated_block_entities.push((pos, entity));
+                    }
+                }
+            }
+        });
+        // The entities of the blocks that changed during the tick were already replaced
+        for (pos, entity) in updated_block_entities {
+            self.set_block_entity(pos, entity);
+        }
+
+        self.process_neighbor_updates(tick);
+    }
+
+    /// Run the pending neighbor updates, in waves: the changes made by one wave are applied before the next wave runs.
+    /// At most `MAX_NEIGHBOR_UPDATES_PER_TICK` updates run in one tick, and every update runs at most once per tick
+    /// to break update loops. The remaining updates are postponed to the next tick.
+    fn process_neighbor_updates(&mut self, tick: u64) {
+        let mut budget = MAX_NEIGHBOR_UPDATES_PER_TICK;
+        let mut processed = HashSet::new();
+        let mut postponed = Vec::new();
+        while budget > 0 {
+            let mut wave = Vec::new();
+            while wave.len() < budget {
+                let update = match self.neighbor_updates.pop_front() {
+                    Some(update) => update,
+                    None => break,
+                };
+                self.queued_neighbor_updates
+                    .remove(&(update.pos, update.source));
+                if processed.insert(update) {
+                    wave.push(update);
+                } else {
+                    postponed.push(update);
+                }
+            }
+            if wave.is_empty() {
+                break;
+            }
+            budget -= wave.len();
+
+            self.run_handlers(tick, |world, ctx| {
+                for NeighborUpdate { pos, source } in wave {
+                    let block = world.get_block(pos);
+                    if let Some(handler) = world.handler(block) {
+                        if pos == source {
+                            handler.placed(ctx, pos, block);
+                        } else {
+                            handler.neighbor_changed(ctx, pos, block, source);
+                        }
+                    }
+                }
+            });
+        }
+        for update in postponed {
+            self.enqueue_neighbor_update(update.pos, update.source);
+        }
+    }
+
+    /// Return true if the chunk containing `pos` is loaded
+    pub fn is_block_loaded(&self, pos: BlockPos) -> bool {
+        self.chunks.contains_key(&pos.containing_chunk_pos())
+    }
+
     /// Fetch the new chunk meshes from the worldgen worker
     pub fn get_new_generated_chunks(&mut self) {
         // TODO: maybe don't update all the light column every time
         // TODO: if there are multiple chunks in the same column this may save time
         while let Some(chunk) = self.worldgen_worker.get_result() {
             self.worldgen_queue.remove(&chunk.pos);
-            self.set_chunk(Arc::new(chunk));
+            self.set_chunk(Arc::new(chunk), false);
         }
     }
 
@@ -177,7 +534,7 @@ impl World {
                             server_chunk.needs_light_update = false;
                             server_chunk.is_in_light_queue = true;
                         }
-                        // If the lighting queue is full, stop
+                        // If the queues of all the lighting threads are full, stop
                         Err(_) => break,
                     }
                 }
@@ -219,25 +576,47 @@ impl World {
         }
     }
 
-    /// Start the worldgen of a few chunks
-    pub fn enqueue_chunks_for_worldgen(&mut self, player_close_chunks: &[ChunkPos]) {
+    /// Load or start the worldgen of a few chunks
+    pub fn enqueue_chunks_for_worldgen(&mut self, player_close_chunks: &[ChunkPos]) -> Result<()> {
         for pos in player_close_chunks {
             if !self.chunks.contains_key(pos) && !self.worldgen_queue.contains(pos) {
-                let res = self.worldgen_worker.enqueue(*pos);
-                match res {
-                    // If the worldgen queue is not full, update chunk status
-                    Ok(()) => {
-                        self.worldgen_queue.insert(*pos);
-                    }
-                    // If the worldgen queue is full, stop
-                    Err(_) => break,
+                // If the worldgen queue is full, stop
+                if !self.load_or_generate_chunk(*pos)? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load a saved chunk, or start generating it if it was never saved.
+    /// Return false if the worldgen queue is full.
+    fn load_or_generate_chunk(&mut self, pos: ChunkPos) -> Result<bool> {
+        if let Some(saved_chunk) = self.chunk_storage.load_chunk(pos)? {
+            self.set_chunk(Arc::new(saved_chunk.chunk.to_chunk()), false);
+            for scheduled_tick in saved_chunk.scheduled_ticks {
+                self.scheduled_ticks.schedule(scheduled_tick);
+            }
+            for (entity_pos, entity) in saved_chunk.block_entities {
+                self.insert_block_entity(entity_pos, entity);
+            }
+            Ok(true)
+        } else {
+            match self.worldgen_worker.enqueue(pos) {
+                Ok(()) => {
+                    self.worldgen_queue.insert(pos);
+                    Ok(true)
                 }
+                Err(_) => Ok(false),
             }
         }
     }
 
-    /// Drop far chunks
-    pub fn drop_far_chunks(&mut self, player_positions: &[(ChunkPos, RenderDistance)]) {
+    /// Drop far chunks, saving the modified ones
+    pub fn drop_far_chunks(
+        &mut self,
+        player_positions: &[(ChunkPos, RenderDistance)],
+    ) -> Result<()> {
         let loaded_chunks = self.chunks.keys().cloned().collect::<Vec<_>>();
         'chunks: for chunk_pos in loaded_chunks {
             for (player_chunk, render_distance) in player_positions {
@@ -245,14 +624,25 @@ impl World {
                     continue 'chunks;
                 }
             }
-            self.unload_chunk(chunk_pos);
+            self.unload_chunk(chunk_pos)?;
         }
+        Ok(())
     }
 
-    /// Unload chunk
-    // TODO: persist to disk
-    fn unload_chunk(&mut self, pos: ChunkPos) {
-        self.chunks.remove(&pos);
+    /// Unload chunk, saving it with its block entities if it was modified or has scheduled ticks
+    fn unload_chunk(&mut self, pos: ChunkPos) -> Result<()> {
+        let server_chunk = self.chunks.remove(&pos).expect("Chunk is not loaded");
+        let scheduled_ticks = self.scheduled_ticks.remove_chunk(pos);
+        for entity_pos in server_chunk.block_entities.keys() {
+            self.ticking_block_entities.remove(entity_pos);
+        }
+        if server_chunk.modified || !scheduled_ticks.is_empty() {
+            self.chunk_storage.save_chunk(
+                &server_chunk.chunk,
+                scheduled_ticks,
+                &server_chunk.block_entities,
+            )?;
+        }
         let column_pos = ChunkPosXZ::from(pos);
         let col = self
             .chunk_columns
@@ -263,6 +653,24 @@ impl World {
         if col.loaded_chunks.len() == 0 {
             self.chunk_columns.remove(&column_pos);
         }
+        Ok(())
+    }
+
+    /// Save the loaded chunks that were modified or have scheduled ticks
+    pub fn save_chunks(&mut self) -> Result<()> {
+        let mut ticks_by_chunk = self.scheduled_ticks.ticks_by_chunk();
+        for (pos, server_chunk) in self.chunks.iter_mut() {
+            let scheduled_ticks = ticks_by_chunk.remove(pos).unwrap_or_default();
+            if server_chunk.modified || !scheduled_ticks.is_empty() {
+                self.chunk_storage.save_chunk(
+                    &server_chunk.chunk,
+                    scheduled_ticks,
+                    &server_chunk.block_entities,
+                )?;
+                server_chunk.modified = false;
+            }
+        }
+        Ok(())
     }
 
     /// Get chunks to send to a player this frame, and update the `PlayerData` accordingly. Start generating some chunks if necessary
@@ -270,7 +678,7 @@ impl World {
         &mut self,
         player_chunk: ChunkPos,
         data: &mut super::PlayerData,
-    ) -> Vec<(Arc<Chunk>, Arc<LightChunk>)> {
+    ) -> Result<Vec<(Arc<Chunk>, Arc<LightChunk>)>> {
         const MAX_CHUNKS: usize = 20;
         let mut updates = Vec::new();
         for pos in data.close_chunks.get_close_chunks() {
@@ -289,15 +697,12 @@ impl World {
                 if updates.len() == MAX_CHUNKS {
                     break;
                 }
-            } else {
-                // Generate the chunk
-                let res = self.worldgen_worker.enqueue(pos);
-                if res.is_ok() {
-                    self.worldgen_queue.insert(pos);
-                }
+            } else if !self.worldgen_queue.contains(&pos) {
+                // Load or generate the chunk
+                self.load_or_generate_chunk(pos)?;
             }
         }
-        updates
+        Ok(updates)
     }
 
     /// Number of loaded chunks
@@ -305,6 +710,16 @@ impl World {
         self.chunks.len()
     }
 
+    /// Number of scheduled block ticks
+    pub fn num_scheduled_ticks(&self) -> usize {
+        self.scheduled_ticks.len()
+    }
+
+    /// Number of pending neighbor updates
+    pub fn num_neighbor_updates(&self) -> usize {
+        self.neighbor_updates.len()
+    }
+
     /// Number of loaded chunk columns
     pub fn num_loaded_chunk_columns(&self) -> usize {
         self.chunk_columns.len()
@@ -313,14 +728,53 @@ impl World {
 
 impl BlockContainer for World {
     fn is_block_full(&self, pos: BlockPos) -> bool {
-        // TODO: use BlockRegistry
-        match self.chunks.get(&pos.containing_chunk_pos()) {
-            None => false,
-            Some(chunk) => chunk.chunk.get_block_at(pos.pos_in_containing_chunk()) != 0,
-        }
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.is_solid())
+            .unwrap_or(false)
+    }
+
+    fn collision_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.collision_boxes(pos))
+            .unwrap_or_default()
+    }
+
+    fn selection_boxes(&self, pos: BlockPos) -> Vec<AABB<f64>> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map(|block| block.selection_boxes(pos))
+            .unwrap_or_default()
+    }
+
+    fn get_fluid_state(&self, pos: BlockPos) -> Option<FluidState> {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .and_then(|block| block.fluid)
     }
 }
 
+impl MobWorld for World {
+    fn get_base_block(&self, pos: BlockPos) -> BlockId {
+        self.block_registry
+            .get_value_by_id(self.get_block(pos) as u32)
+            .map_or(0, |block| block.base)
+    }
+
+    fn get_light(&self, pos: BlockPos) -> u8 {
+        World::get_light(self, pos)
+    }
+}
+
+/// A pending neighbor update: the block at `pos` must react to the change of the block at `source`.
+/// If `pos` and `source` are equal, the block at `pos` was just placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NeighborUpdate {
+    pos: BlockPos,
+    source: BlockPos,
+}
+
 /// The data for each chunk stored by the server
 struct ServerChunk {
     /// The chunk itself
@@ -333,6 +787,10 @@ struct ServerChunk {
     pub is_in_light_queue: bool,
     /// True if the chunk needs a light update, for example before it never had one or because it changed.
     pub needs_light_update: bool,
+    /// True if the chunk was modified since it was last saved
+    pub modified: bool,
+    /// The block entities of the chunk
+    pub block_entities: HashMap<BlockPos, BlockEntity>,
 }
 
 /// The data for each chunk column stored by the server